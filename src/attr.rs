@@ -0,0 +1,353 @@
+//! Typed node and edge attribute columns.
+//!
+//! Node and edge attributes are parsed once (at load time) into integer,
+//! float, or categorical columns. Integer and float columns can be summed
+//! over districts; categorical columns (e.g. county names or edge types)
+//! can only be compared.
+use serde::ser::{Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A typed attribute column (one value per node, in node index order,
+/// or one value per edge, in the order of [crate::graph::Graph::edges]).
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttrColumn {
+    /// Integer values.
+    Int(Vec<i64>),
+    /// Floating-point values.
+    Float(Vec<f64>),
+    /// Categorical values, stored as indices into a (shared) list of categories.
+    Categorical {
+        codes: Vec<u32>,
+        categories: Arc<Vec<String>>,
+    },
+}
+
+/// A sum over an integer or float attribute column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttrSum {
+    Int(i64),
+    Float(f64),
+}
+
+impl AttrSum {
+    /// Returns the sum as a float.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            AttrSum::Int(sum) => sum as f64,
+            AttrSum::Float(sum) => sum,
+        }
+    }
+}
+
+impl Serialize for AttrSum {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            AttrSum::Int(sum) => serializer.serialize_i64(sum),
+            AttrSum::Float(sum) => serializer.serialize_f64(sum),
+        }
+    }
+}
+
+impl AttrColumn {
+    /// Parses a column of raw values, inferring the narrowest column type
+    /// (integer, then float, then categorical) that fits all values.
+    pub fn parse<S: AsRef<str>>(values: impl IntoIterator<Item = S>) -> AttrColumn {
+        let mut builder = AttrColumnBuilder::default();
+        for value in values {
+            builder.push_str(value.as_ref());
+        }
+        builder.finish()
+    }
+
+    /// Returns the number of values in the column.
+    pub fn len(&self) -> usize {
+        match self {
+            AttrColumn::Int(vals) => vals.len(),
+            AttrColumn::Float(vals) => vals.len(),
+            AttrColumn::Categorical { codes, .. } => codes.len(),
+        }
+    }
+
+    /// Returns `true` if the column has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the column can be summed.
+    pub fn is_numeric(&self) -> bool {
+        !matches!(self, AttrColumn::Categorical { .. })
+    }
+
+    /// Returns the first value in a categorical column that cannot be
+    /// interpreted as a number (with its node index), or `None` for
+    /// numeric columns.
+    pub fn first_non_numeric(&self) -> Option<(usize, &str)> {
+        match self {
+            AttrColumn::Categorical { codes, categories } => {
+                codes.iter().enumerate().find_map(|(node, &code)| {
+                    let value = categories[code as usize].as_str();
+                    match parse_number(value) {
+                        Some(_) => None,
+                        None => Some((node, value)),
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Sums the column over `nodes`, or returns `None` for categorical columns.
+    pub fn sum(&self, nodes: &[usize]) -> Option<AttrSum> {
+        match self {
+            AttrColumn::Int(vals) => Some(AttrSum::Int(nodes.iter().map(|&n| vals[n]).sum())),
+            AttrColumn::Float(vals) => Some(AttrSum::Float(nodes.iter().map(|&n| vals[n]).sum())),
+            AttrColumn::Categorical { .. } => None,
+        }
+    }
+
+    /// Returns the value at `index` as a float, or `None` for categorical columns.
+    pub fn get_f64(&self, index: usize) -> Option<f64> {
+        match self {
+            AttrColumn::Int(vals) => Some(vals[index] as f64),
+            AttrColumn::Float(vals) => Some(vals[index]),
+            AttrColumn::Categorical { .. } => None,
+        }
+    }
+
+    /// Returns `true` if nodes `a` and `b` have the same value.
+    pub fn same_value(&self, a: usize, b: usize) -> bool {
+        match self {
+            AttrColumn::Int(vals) => vals[a] == vals[b],
+            AttrColumn::Float(vals) => vals[a] == vals[b],
+            AttrColumn::Categorical { codes, .. } => codes[a] == codes[b],
+        }
+    }
+
+    /// Returns `true` if the value at `node` is missing (an empty or
+    /// `null` categorical value).
+    pub fn is_missing(&self, node: usize) -> bool {
+        match self {
+            AttrColumn::Categorical { codes, categories } => {
+                let value = &categories[codes[node] as usize];
+                value.is_empty() || value == "null"
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the value at `node` as a string.
+    pub fn value_string(&self, node: usize) -> String {
+        match self {
+            AttrColumn::Int(vals) => vals[node].to_string(),
+            AttrColumn::Float(vals) => Value::from(vals[node]).to_string(),
+            AttrColumn::Categorical { codes, categories } => {
+                categories[codes[node] as usize].clone()
+            }
+        }
+    }
+
+    /// Returns an empty column of the same type (sharing categories)
+    /// with capacity for `capacity` values.
+    pub fn empty_like(&self, capacity: usize) -> AttrColumn {
+        match self {
+            AttrColumn::Int(_) => AttrColumn::Int(Vec::with_capacity(capacity)),
+            AttrColumn::Float(_) => AttrColumn::Float(Vec::with_capacity(capacity)),
+            AttrColumn::Categorical { categories, .. } => AttrColumn::Categorical {
+                codes: Vec::with_capacity(capacity),
+                categories: categories.clone(),
+            },
+        }
+    }
+
+    /// Replaces the values in this column with the values of `src` at `nodes`.
+    /// (Used to copy attributes into subgraph buffers.)
+    pub fn copy_subset(&mut self, src: &AttrColumn, nodes: &[usize]) {
+        match (self, src) {
+            (AttrColumn::Int(dst), AttrColumn::Int(vals)) => {
+                dst.clear();
+                dst.extend(nodes.iter().map(|&n| vals[n]));
+            }
+            (AttrColumn::Float(dst), AttrColumn::Float(vals)) => {
+                dst.clear();
+                dst.extend(nodes.iter().map(|&n| vals[n]));
+            }
+            (AttrColumn::Categorical { codes: dst, .. }, AttrColumn::Categorical { codes, .. }) => {
+                dst.clear();
+                dst.extend(nodes.iter().map(|&n| codes[n]));
+            }
+            (dst, src) => {
+                *dst = src.empty_like(nodes.len());
+                dst.copy_subset(src, nodes);
+            }
+        }
+    }
+}
+
+/// Parses a finite number from a string.
+fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// Incrementally builds an [AttrColumn], promoting the column type
+/// (integer to float to categorical) as values are added.
+#[derive(Default)]
+pub struct AttrColumnBuilder {
+    /// Integer values (while all values are integers).
+    ints: Vec<i64>,
+    /// Float values (once a non-integer number is seen).
+    floats: Option<Vec<f64>>,
+    /// Categorical values (once a non-numeric value is seen).
+    codes: Option<Vec<u32>>,
+    /// The categories of a categorical column, in order of appearance.
+    categories: Vec<String>,
+    /// Maps categories to codes.
+    category_codes: HashMap<String, u32>,
+}
+
+impl AttrColumnBuilder {
+    /// Adds a raw (string) value.
+    pub fn push_str(&mut self, value: &str) {
+        if self.codes.is_none() {
+            if let Ok(val) = value.trim().parse::<i64>() {
+                return self.push_int(val);
+            }
+            if let Some(val) = parse_number(value) {
+                return self.push_float(val);
+            }
+        }
+        self.push_category(value);
+    }
+
+    /// Adds a JSON value. Strings are parsed as in [AttrColumnBuilder::push_str];
+    /// `null`s and other non-numeric values are categorical.
+    pub fn push_json(&mut self, value: &Value) {
+        match value {
+            Value::Number(num) if self.codes.is_none() => match num.as_i64() {
+                Some(val) => self.push_int(val),
+                None => self.push_float(num.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => self.push_str(s),
+            other => self.push_category(&other.to_string()),
+        }
+    }
+
+    fn push_int(&mut self, val: i64) {
+        match self.floats.as_mut() {
+            Some(floats) => floats.push(val as f64),
+            None => self.ints.push(val),
+        }
+    }
+
+    fn push_float(&mut self, val: f64) {
+        let floats = self
+            .floats
+            .get_or_insert_with(|| self.ints.drain(..).map(|v| v as f64).collect());
+        floats.push(val);
+    }
+
+    fn push_category(&mut self, value: &str) {
+        if self.codes.is_none() {
+            // Promote the numeric values seen so far to categories.
+            let previous: Vec<String> = match self.floats.take() {
+                Some(floats) => floats
+                    .into_iter()
+                    .map(|v| Value::from(v).to_string())
+                    .collect(),
+                None => self.ints.drain(..).map(|v| v.to_string()).collect(),
+            };
+            let mut codes = Vec::with_capacity(previous.len() + 1);
+            for prev in previous.iter() {
+                codes.push(self.category_code(prev));
+            }
+            self.codes = Some(codes);
+        }
+        let code = self.category_code(value);
+        self.codes.as_mut().unwrap().push(code);
+    }
+
+    fn category_code(&mut self, value: &str) -> u32 {
+        if let Some(&code) = self.category_codes.get(value) {
+            return code;
+        }
+        let code = self.categories.len() as u32;
+        self.categories.push(value.to_string());
+        self.category_codes.insert(value.to_string(), code);
+        code
+    }
+
+    /// Returns the built column.
+    pub fn finish(self) -> AttrColumn {
+        match (self.codes, self.floats) {
+            (Some(codes), _) => AttrColumn::Categorical {
+                codes,
+                categories: Arc::new(self.categories),
+            },
+            (None, Some(floats)) => AttrColumn::Float(floats),
+            (None, None) => AttrColumn::Int(self.ints),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_infers_column_types() {
+        assert_eq!(
+            AttrColumn::parse(["1", "2", "-3"]),
+            AttrColumn::Int(vec![1, 2, -3])
+        );
+        assert_eq!(
+            AttrColumn::parse(["1", "2.5", "3"]),
+            AttrColumn::Float(vec![1.0, 2.5, 3.0])
+        );
+        let col = AttrColumn::parse(["1", "a", "", "a"]);
+        assert!(!col.is_numeric());
+        assert!(col.same_value(1, 3));
+        assert!(!col.same_value(0, 1));
+        assert!(col.is_missing(2));
+        assert_eq!(col.value_string(0), "1");
+        assert_eq!(col.first_non_numeric(), Some((1, "a")));
+    }
+
+    #[test]
+    fn push_json_values() {
+        let mut builder = AttrColumnBuilder::default();
+        for value in [json!(1), json!("2"), json!(0.5)] {
+            builder.push_json(&value);
+        }
+        assert_eq!(builder.finish(), AttrColumn::Float(vec![1.0, 2.0, 0.5]));
+
+        let mut builder = AttrColumnBuilder::default();
+        for value in [json!(51001), json!(null), json!("51003")] {
+            builder.push_json(&value);
+        }
+        let col = builder.finish();
+        assert!(col.is_missing(1));
+        assert_eq!(col.value_string(2), "51003");
+    }
+
+    #[test]
+    fn sums() {
+        let ints = AttrColumn::parse(["1", "2", "3"]);
+        assert_eq!(ints.sum(&[0, 2]), Some(AttrSum::Int(4)));
+        let floats = AttrColumn::parse(["0.25", "0.5", "1"]);
+        assert_eq!(floats.sum(&[0, 1]), Some(AttrSum::Float(0.75)));
+        let categories = AttrColumn::parse(["x", "y"]);
+        assert_eq!(categories.sum(&[0, 1]), None);
+    }
+
+    #[test]
+    fn copy_subset() {
+        let col = AttrColumn::parse(["x", "y", "z"]);
+        let mut buf = AttrColumn::Int(vec![]);
+        buf.copy_subset(&col, &[2, 0]);
+        assert_eq!(buf.value_string(0), "z");
+        assert_eq!(buf.value_string(1), "x");
+        assert_eq!(buf.len(), 2);
+    }
+}
@@ -4,21 +4,20 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use clap::{value_t, App, Arg};
+use frcw::cache::{read_cache, write_cache, CacheError, CacheKey};
+use frcw::cli::{output_args, OutputOptions};
 use frcw::config::parse_region_weights_config;
-use frcw::init::from_networkx;
+use frcw::graph::Pop;
+use frcw::init::{from_edge_list_csv, from_networkx};
 use frcw::recom::run::multi_chain;
 use frcw::recom::{RecomParams, RecomVariant};
-use frcw::stats::{
-    AssignmentsOnlyWriter, BenWriter, CanonicalWriter, JSONLWriter, PcompressWriter, StatsWriter,
-    TSVWriter,
-};
 use serde_json::json;
 use sha3::{Digest, Sha3_256};
 use std::path::PathBuf;
 use std::{fs, io};
 
 fn main() {
-    let mut cli = App::new("frcw")
+    let cli = App::new("frcw")
         .version("0.1.0")
         .author("Parker J. Rule <parker.rule@tufts.edu>")
         .about("A minimal implementation of the ReCom Markov chain")
@@ -26,9 +25,30 @@ fn main() {
             Arg::with_name("graph_json")
                 .long("graph-json")
                 .takes_value(true)
-                .required(true)
+                .required_unless("edge_list")
                 .help("The path of the dual graph (in NetworkX format)."),
         )
+        .arg(
+            Arg::with_name("edge_list")
+                .long("edge-list")
+                .takes_value(true)
+                .conflicts_with("graph_json")
+                .requires("node_csv")
+                .help("The path of the dual graph (as a space-delimited edge list)."),
+        )
+        .arg(
+            Arg::with_name("node_csv")
+                .long("node-csv")
+                .takes_value(true)
+                .requires("edge_list")
+                .help("The path of the node attribute CSV (used with --edge-list)."),
+        )
+        .arg(
+            Arg::with_name("graph_cache")
+                .long("graph-cache")
+                .takes_value(true)
+                .help("The path of a binary graph cache (created if missing or stale)."),
+        )
         .arg(
             Arg::with_name("n_steps")
                 .long("n-steps")
@@ -92,35 +112,13 @@ fn main() {
                 .takes_value(true)
                 .default_value("reversible"),
         ) // other options: cut_edges, district_pairs
-        .arg(
-            Arg::with_name("writer")
-                .long("writer")
-                .takes_value(true)
-                .default_value("jsonl"),
-        ) // other options: jsonl-full, tsv
-        .arg(
-            Arg::with_name("sum_cols")
-                .long("sum-cols")
-                .multiple(true)
-                .takes_value(true),
-        )
         .arg(
             Arg::with_name("region_weights")
                 .long("region-weights")
                 .takes_value(true)
                 .help("Region columns with weights for region-aware ReCom."),
         )
-        .arg(Arg::with_name("cut_edges_count").long("cut-edges-count"))
-        .arg(
-            Arg::with_name("output-file")
-                .long("output-file")
-                .short("o")
-                .takes_value(true)
-                .help("The path to write the output to."),
-        );
-    if cfg!(feature = "linalg") {
-        cli = cli.arg(Arg::with_name("spanning_tree_counts").long("st-counts"));
-    }
+        .args(&output_args());
     let matches = cli.get_matches();
     let n_steps = value_t!(matches.value_of("n_steps"), u64).unwrap_or_else(|e| e.exit());
     let rng_seed = value_t!(matches.value_of("rng_seed"), u64).unwrap_or_else(|e| e.exit());
@@ -128,23 +126,27 @@ fn main() {
     let balance_ub = value_t!(matches.value_of("balance_ub"), u32).unwrap_or_else(|e| e.exit());
     let n_threads = value_t!(matches.value_of("n_threads"), usize).unwrap_or_else(|e| e.exit());
     let batch_size = value_t!(matches.value_of("batch_size"), usize).unwrap_or_else(|e| e.exit());
-    let graph_json = fs::canonicalize(PathBuf::from(matches.value_of("graph_json").unwrap()))
-        .unwrap()
-        .into_os_string()
-        .into_string()
-        .unwrap();
+    let canonical_path = |path: &str| {
+        fs::canonicalize(PathBuf::from(path))
+            .unwrap()
+            .into_os_string()
+            .into_string()
+            .unwrap()
+    };
+    let graph_json = matches.value_of("graph_json").map(canonical_path);
+    let edge_list = matches.value_of("edge_list").map(canonical_path);
+    let node_csv = matches.value_of("node_csv").map(canonical_path);
     let pop_col = matches.value_of("pop_col").unwrap();
     let assignment_col = matches.value_of("assignment_col").unwrap();
     let variant_str = matches.value_of("variant").unwrap();
-    let writer_str = matches.value_of("writer").unwrap();
-    let st_counts = matches.is_present("spanning_tree_counts");
-    let cut_edges_count = matches.is_present("cut_edges_count");
-    let mut sum_cols: Vec<String> = matches
-        .values_of("sum_cols")
-        .unwrap_or_default()
-        .map(|c| c.to_string())
-        .collect();
     let region_weights_raw = matches.value_of("region_weights").unwrap_or_default();
+    let region_weights = parse_region_weights_config(region_weights_raw);
+    let region_cols: Vec<String> = region_weights
+        .iter()
+        .flatten()
+        .map(|(col, _)| col.clone())
+        .collect();
+    let output = OutputOptions::from_matches(&matches, Some(n_steps), region_cols);
 
     let variant = match variant_str {
         "reversible" => RecomVariant::Reversible,
@@ -157,45 +159,15 @@ fn main() {
         bad => panic!("Parameter error: invalid variant '{}'", bad),
     };
 
-    let output_buffer: Box<dyn io::Write + Send> = match matches.value_of("output-file") {
-        Some(path) => {
-            let path = std::path::Path::new(path);
-            if path.exists() {
-                panic!("Output file already exists.");
-            };
-            Box::new(io::BufWriter::new(fs::File::create(path).unwrap()))
-        }
-        None => Box::new(io::BufWriter::new(std::io::stdout())),
-    };
-
-    let writer: Box<dyn StatsWriter> = match writer_str {
-        "tsv" => Box::new(TSVWriter::new(output_buffer)),
-        "jsonl" => Box::new(JSONLWriter::new(
-            false,
-            st_counts,
-            cut_edges_count,
-            output_buffer,
-        )),
-        "pcompress" => Box::new(PcompressWriter::new(output_buffer)),
-        "jsonl-full" => Box::new(JSONLWriter::new(
-            true,
-            st_counts,
-            cut_edges_count,
-            output_buffer,
-        )),
-        "assignments" => Box::new(AssignmentsOnlyWriter::new(false, output_buffer)),
-        "canonicalized-assignments" => Box::new(AssignmentsOnlyWriter::new(true, output_buffer)),
-        "canonical" => Box::new(CanonicalWriter::new(output_buffer)),
-        "ben" => Box::new(BenWriter::new(output_buffer)),
-        bad => panic!("Parameter error: invalid writer '{}'", bad),
-    };
     if variant == RecomVariant::Reversible && balance_ub == 0 {
         panic!("For reversible ReCom, specify M > 0.");
     }
 
     assert!(tol >= 0.0 && tol <= 1.0);
 
-    let region_weights = parse_region_weights_config(region_weights_raw);
+    // The output columns must be numeric, but region columns can be categorical.
+    let mut sum_cols = output.numeric_columns();
+    let edge_cols = output.edge_columns();
     // Add the keys in the region weights to sum_cols if they are not there already
     // so that the user doesn't have to
     if let Some(weight_pairs_vec) = &region_weights {
@@ -206,12 +178,66 @@ fn main() {
         }
     }
 
-    let (graph, partition) = from_networkx(&graph_json, pop_col, assignment_col, sum_cols).unwrap();
+    // For edge list input, the hash covers the edge list followed by the node CSV.
+    let graph_paths: Vec<String> = graph_json
+        .iter()
+        .chain(edge_list.iter())
+        .chain(node_csv.iter())
+        .cloned()
+        .collect();
+    let graph_cache = matches.value_of("graph_cache");
+    let cache_key = CacheKey {
+        sources: graph_paths.clone(),
+        pop_col: pop_col.to_string(),
+        assignment_col: assignment_col.to_string(),
+        columns: sum_cols.clone(),
+        edge_columns: edge_cols.clone(),
+    };
+    // Files that are not graph caches are never overwritten.
+    let mut write_graph_cache = graph_cache.is_some();
+    let cached = match graph_cache {
+        Some(path) if std::path::Path::new(path).exists() => read_cache(path, &cache_key)
+            .unwrap_or_else(|err| {
+                eprintln!("Ignoring graph cache {}: {}", path, err);
+                write_graph_cache = err != CacheError::ErrBadMagic;
+                None
+            }),
+        _ => None,
+    };
+
+    let (graph, partition, graph_hash) = match cached {
+        Some(cached) => (cached.graph, cached.partition, cached.source_sha3),
+        None => {
+            let (graph, partition) = match (&graph_json, &edge_list, &node_csv) {
+                (Some(graph_json), _, _) => {
+                    from_networkx(graph_json, pop_col, assignment_col, sum_cols, edge_cols).unwrap()
+                }
+                (None, Some(edge_list), Some(node_csv)) => {
+                    from_edge_list_csv(edge_list, node_csv, pop_col, assignment_col, sum_cols)
+                        .unwrap_or_else(|err| panic!("Could not load graph: {}", err))
+                }
+                _ => panic!("Parameter error: specify --graph-json or --edge-list and --node-csv"),
+            };
+            let mut graph_hasher = Sha3_256::new();
+            for path in graph_paths.iter() {
+                let mut graph_file = fs::File::open(path).unwrap();
+                io::copy(&mut graph_file, &mut graph_hasher).unwrap();
+            }
+            let graph_hash = format!("{:x}", graph_hasher.finalize());
+            if let (Some(path), true) = (graph_cache, write_graph_cache) {
+                if let Err(err) = write_cache(path, &cache_key, &graph_hash, &graph, &partition) {
+                    eprintln!("Could not write graph cache {}: {}", path, err);
+                }
+            }
+            (graph, partition, graph_hash)
+        }
+    };
+    output.check_graph(&graph);
     let avg_pop = (graph.total_pop as f64) / (partition.num_dists as f64);
 
     let params = RecomParams {
-        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as Pop,
         num_steps: n_steps,
         rng_seed: rng_seed,
         balance_ub: balance_ub,
@@ -219,24 +245,31 @@ fn main() {
         region_weights: region_weights.clone(),
     };
 
-    let mut graph_file = fs::File::open(&graph_json).unwrap();
-    let mut graph_hasher = Sha3_256::new();
-    io::copy(&mut graph_file, &mut graph_hasher).unwrap();
-    let graph_hash = format!("{:x}", graph_hasher.finalize());
     let mut meta = json!({
         "assignment_col": assignment_col,
         "tol": tol,
         "pop_col": pop_col,
-        "graph_path": graph_json,
+        "graph_path": graph_paths[0],
         "graph_sha3": graph_hash,
         "batch_size": batch_size,
         "rng_seed": rng_seed,
         "num_threads": n_threads,
         "num_steps": n_steps,
         "parallel": true,
-        "graph_json": graph_json,
         "chain_variant": variant_str,
     });
+    if let Some(graph_json) = &graph_json {
+        meta.as_object_mut()
+            .unwrap()
+            .insert("graph_json".to_string(), json!(graph_json));
+    } else if let (Some(edge_list), Some(node_csv)) = (&edge_list, &node_csv) {
+        meta.as_object_mut()
+            .unwrap()
+            .insert("edge_list_path".to_string(), json!(edge_list));
+        meta.as_object_mut()
+            .unwrap()
+            .insert("node_csv_path".to_string(), json!(node_csv));
+    }
     if variant == RecomVariant::Reversible {
         meta.as_object_mut()
             .unwrap()
@@ -247,10 +280,12 @@ fn main() {
             .unwrap()
             .insert("region_weights".to_string(), json!(region_weights));
     }
-    if writer_str == "jsonl" || writer_str == "jsonl-full" {
+    output.add_meta(&mut meta);
+    if output.prints_meta() {
         // hotfix for pcompress writing
         // TODO: move this into init
         println!("{}", json!({ "meta": meta }).to_string());
     }
+    let writer = output.writer(&meta);
     multi_chain(&graph, &partition, writer, &params, n_threads, batch_size);
 }
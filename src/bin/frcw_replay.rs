@@ -0,0 +1,110 @@
+//! Replay CLI for frcw: recomputes statistics from stored chain output.
+use mimalloc::MiMalloc;
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+use clap::{App, Arg};
+use frcw::cli::{output_args, OutputOptions};
+use frcw::init::stream_graph_from_networkx;
+use frcw::replay::{replay_chain, ReplayFormat};
+use serde_json::json;
+use std::path::PathBuf;
+use std::{fs, io};
+
+fn main() {
+    let cli = App::new("frcw_replay")
+        .version("0.1.0")
+        .author("Parker J. Rule <parker.rule@tufts.edu>")
+        .about("Recomputes statistics from stored ReCom chain output")
+        .arg(
+            Arg::with_name("graph_json")
+                .long("graph-json")
+                .takes_value(true)
+                .required(true)
+                .help("The path of the dual graph (in NetworkX format)."),
+        )
+        .arg(
+            Arg::with_name("pop_col")
+                .long("pop-col")
+                .takes_value(true)
+                .required(true)
+                .help("The name of the total population column in the graph metadata."),
+        )
+        .arg(
+            Arg::with_name("chain_file")
+                .long("chain-file")
+                .takes_value(true)
+                .help("The path of the stored chain output (defaults to stdin)."),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .required(true)
+                .possible_values(&["ben", "pcompress", "canonical"])
+                .help("The format of the stored chain output."),
+        )
+        .arg(
+            Arg::with_name("region_cols")
+                .long("region-cols")
+                .multiple(true)
+                .takes_value(true)
+                .help("Region columns to record split statistics for."),
+        )
+        .args(&output_args());
+    let matches = cli.get_matches();
+    let graph_json = fs::canonicalize(PathBuf::from(matches.value_of("graph_json").unwrap()))
+        .unwrap()
+        .into_os_string()
+        .into_string()
+        .unwrap();
+    let pop_col = matches.value_of("pop_col").unwrap();
+    let region_cols: Vec<String> = matches
+        .values_of("region_cols")
+        .unwrap_or_default()
+        .map(|c| c.to_string())
+        .collect();
+    let output = OutputOptions::from_matches(&matches, None, region_cols.clone());
+
+    let format = match matches.value_of("format").unwrap() {
+        "ben" => ReplayFormat::Ben,
+        "pcompress" => ReplayFormat::Pcompress,
+        "canonical" => ReplayFormat::Canonical,
+        bad => panic!("Parameter error: invalid format '{}'", bad),
+    };
+
+    let chain_data: Box<dyn io::Read> = match matches.value_of("chain_file") {
+        Some(path) => Box::new(io::BufReader::new(
+            fs::File::open(path).expect("Could not open chain file"),
+        )),
+        None => Box::new(io::BufReader::new(io::stdin())),
+    };
+
+    // The output columns must be numeric, but region columns can be categorical.
+    let mut columns = output.numeric_columns();
+    for col in region_cols {
+        if !columns.contains(&col) {
+            columns.push(col);
+        }
+    }
+    let graph =
+        stream_graph_from_networkx(&graph_json, pop_col, columns, output.edge_columns()).unwrap();
+    output.check_graph(&graph);
+
+    let mut meta = json!({
+        "pop_col": pop_col,
+        "graph_json": graph_json,
+        "replay_format": matches.value_of("format").unwrap(),
+    });
+    if let Some(chain_file) = matches.value_of("chain_file") {
+        meta.as_object_mut()
+            .unwrap()
+            .insert("chain_file".to_string(), json!(chain_file));
+    }
+    output.add_meta(&mut meta);
+    let writer = output.writer(&meta);
+    if let Err(err) = replay_chain(&graph, chain_data, format, writer) {
+        eprintln!("Replay failed: {}", err);
+        std::process::exit(1);
+    }
+}
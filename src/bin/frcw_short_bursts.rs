@@ -3,76 +3,25 @@ use mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-use clap::{value_t, App, Arg};
+use clap::{value_t, App, Arg, Error, ErrorKind};
 use frcw::config::parse_region_weights_config;
-use frcw::graph::Graph;
-use frcw::init::from_networkx;
+use frcw::graph::{Graph, Pop};
+use frcw::init::{check_sum_columns, from_networkx};
 use frcw::partition::Partition;
-use frcw::recom::opt::{multi_short_bursts, ScoreValue};
+use frcw::recom::objectives::ObjectiveFn;
+use frcw::recom::opt::{
+    multi_pareto_short_bursts, multi_short_bursts, multi_simulated_annealing, RestartFrom,
+    ShortBurstsParams, TemperatureSchedule,
+};
 use frcw::recom::{RecomParams, RecomVariant};
-use frcw::stats::partition_attr_sums;
 use serde_json::json;
 use serde_json::Value;
 use sha3::{Digest, Sha3_256};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-fn make_objective_fn(
-    objective_config: &str,
-) -> impl Fn(&Graph, &Partition) -> ScoreValue + Send + Clone + Copy {
-    let data: Value = serde_json::from_str(objective_config).unwrap();
-    let obj_type = data["objective"].as_str().unwrap();
-
-    // For now, we only support Gingles optimization with next-partial-district
-    // augmentation. see https://github.com/vrdi/shortbursts-gingles/blob/
-    // d9fb26ec313cd93ac80171b23095c4f3dfab0422/state_experiments/gingleator.py#L253
-    assert!(obj_type == "gingles_partial");
-    let threshold = data["threshold"].as_f64().unwrap();
-    assert!(threshold > 0.0 && threshold < 1.0);
-
-    // A hack to share strings between threads:
-    // https://stackoverflow.com/a/52367953
-    let min_pop_col = &*Box::leak(
-        data["min_pop"]
-            .as_str()
-            .unwrap()
-            .to_owned()
-            .into_boxed_str(),
-    );
-    let total_pop_col = &*Box::leak(
-        data["total_pop"]
-            .as_str()
-            .unwrap()
-            .to_owned()
-            .into_boxed_str(),
-    );
-
-    move |graph: &Graph, partition: &Partition| -> ScoreValue {
-        let min_pops = partition_attr_sums(graph, partition, min_pop_col);
-        let total_pops = partition_attr_sums(graph, partition, total_pop_col);
-        let shares: Vec<f64> = min_pops
-            .iter()
-            .zip(total_pops.iter())
-            .map(|(&m, &t)| m as f64 / t as f64)
-            .collect();
-        let opportunity_count = shares.iter().filter(|&s| s >= &threshold).count();
-
-        // partial ordering on f64:
-        // see https://www.reddit.com/r/rust/comments/29kia3/no_ord_for_f32/
-        // see https://doc.rust-lang.org/std/vec/struct.Vec.html#method.sort_by
-        let mut sorted_below = shares
-            .into_iter()
-            .filter(|s| s < &threshold)
-            .collect::<Vec<f64>>();
-        sorted_below.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-        let next_highest = match sorted_below.last() {
-            Some(&v) => v,
-            None => 0.0,
-        };
-        opportunity_count as f64 + (next_highest / threshold)
-    }
-}
-
 fn main() {
     let cli = App::new("frcw_short_bursts")
         .version("0.1.0")
@@ -132,7 +81,10 @@ fn main() {
                 .long("burst-length")
                 .takes_value(true)
                 .required(true)
-                .help("The number of accepted steps per short burst."),
+                .help(
+                    "The number of accepted steps per short burst \
+                     (or per temperature update when annealing).",
+                ),
         )
         .arg(
             Arg::with_name("sum_cols")
@@ -145,7 +97,79 @@ fn main() {
                 .long("objective")
                 .takes_value(true)
                 .required(true)
-                .help("A JSON-formatted objective function configuration>"),
+                .help(
+                    "A JSON-formatted objective function configuration (a single \
+                     objective or a list of weighted objectives).",
+                ),
+        )
+        .arg(
+            Arg::with_name("optimizer")
+                .long("optimizer")
+                .takes_value(true)
+                .possible_values(&["short-bursts", "annealing", "pareto"])
+                .default_value("short-bursts")
+                .help("The optimization algorithm."),
+        )
+        .arg(
+            Arg::with_name("schedule")
+                .long("schedule")
+                .takes_value(true)
+                .required_if("optimizer", "annealing")
+                .help("A JSON-formatted temperature schedule configuration (for annealing)."),
+        )
+        .arg(
+            Arg::with_name("pareto_output")
+                .long("pareto-output")
+                .takes_value(true)
+                .required_if("optimizer", "pareto")
+                .help(
+                    "The path to write the Pareto front to (one JSON line per plan, \
+                     for the pareto optimizer).",
+                ),
+        )
+        .arg(
+            Arg::with_name("max_front_size")
+                .long("max-front-size")
+                .takes_value(true)
+                .default_value("1000")
+                .help(
+                    "The maximum number of plans in the Pareto front (for the \
+                     pareto optimizer).",
+                ),
+        )
+        .arg(
+            Arg::with_name("tilt")
+                .long("tilt")
+                .takes_value(true)
+                .default_value("0")
+                .help(
+                    "The probability of moving to a worse burst endpoint when no \
+                     burst improves on the current plan (tilted runs).",
+                ),
+        )
+        .arg(
+            Arg::with_name("restart_interval")
+                .long("restart-interval")
+                .takes_value(true)
+                .help("The number of steps between short bursts restarts."),
+        )
+        .arg(
+            Arg::with_name("restart_from")
+                .long("restart-from")
+                .takes_value(true)
+                .possible_values(&["best", "random"])
+                .requires("restart_interval")
+                .help("Restart from the best plan so far (default) or from a random plan."),
+        )
+        .arg(
+            Arg::with_name("restart_steps")
+                .long("restart-steps")
+                .takes_value(true)
+                .requires("restart_interval")
+                .help(
+                    "The number of ReCom steps from the initial plan used to \
+                     sample random restart plans (default 100).",
+                ),
         )
         .arg(
             Arg::with_name("region_weights")
@@ -167,23 +191,76 @@ fn main() {
         .unwrap();
     let pop_col = matches.value_of("pop_col").unwrap();
     let assignment_col = matches.value_of("assignment_col").unwrap();
-    let sum_cols = matches
+    let sum_cols: Vec<String> = matches
         .values_of("sum_cols")
         .unwrap_or_default()
         .map(|c| c.to_string())
         .collect();
     let objective_config = matches.value_of("objective").unwrap();
-    let objective_fn = make_objective_fn(objective_config);
+    let mut objective = ObjectiveFn::from_config(objective_config)
+        .unwrap_or_else(|err| panic!("Invalid objective: {}", err));
+    let optimizer = matches.value_of("optimizer").unwrap();
+    let max_front_size =
+        value_t!(matches.value_of("max_front_size"), usize).unwrap_or_else(|e| e.exit());
+    let tilt = value_t!(matches.value_of("tilt"), f64).unwrap_or_else(|e| e.exit());
+    // Tilted runs and restarts are only implemented for short bursts.
+    if optimizer != "short-bursts" && (tilt > 0.0 || matches.is_present("restart_interval")) {
+        Error::with_description(
+            "--tilt and --restart-interval are only supported by the short-bursts optimizer.",
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    let restart_steps = match matches.value_of("restart_steps") {
+        Some(_) => value_t!(matches.value_of("restart_steps"), usize).unwrap_or_else(|e| e.exit()),
+        None => 100,
+    };
+    let restart_from_name = matches.value_of("restart_from").unwrap_or("best");
+    let restart_from = match restart_from_name {
+        "random" => RestartFrom::Random {
+            steps: restart_steps,
+        },
+        _ => RestartFrom::Best,
+    };
+    let mut bursts = ShortBurstsParams::new(burst_length).with_tilt(tilt);
+    if matches.is_present("restart_interval") {
+        let restart_interval =
+            value_t!(matches.value_of("restart_interval"), u64).unwrap_or_else(|e| e.exit());
+        bursts = bursts.with_restarts(restart_interval, restart_from);
+    }
+    let schedule = matches.value_of("schedule").map(|raw| {
+        TemperatureSchedule::from_config(raw)
+            .unwrap_or_else(|err| panic!("Invalid temperature schedule: {}", err))
+    });
     let region_weights_raw = matches.value_of("region_weights").unwrap_or_default();
     let region_weights = parse_region_weights_config(region_weights_raw);
 
     assert!(tol >= 0.0 && tol <= 1.0);
+    assert!((0.0..=1.0).contains(&tilt));
+    assert!(max_front_size > 0);
 
-    let (graph, partition) = from_networkx(&graph_json, pop_col, assignment_col, sum_cols).unwrap();
+    // Load the columns used by the objective along with the sum columns.
+    let mut numeric_cols = sum_cols.clone();
+    numeric_cols.extend(objective.sum_columns());
+    numeric_cols.sort();
+    numeric_cols.dedup();
+    let mut columns = numeric_cols.clone();
+    columns.extend(objective.region_columns());
+    let (graph, partition) =
+        from_networkx(&graph_json, pop_col, assignment_col, columns, vec![]).unwrap();
+    check_sum_columns(&graph, &numeric_cols)
+        .unwrap_or_else(|err| panic!("Could not load graph: {}", err));
+    objective
+        .init(&graph)
+        .unwrap_or_else(|err| panic!("Invalid objective: {}", err));
+    // Objective functions are shared (by copy) between threads.
+    let objective: &'static ObjectiveFn = Box::leak(Box::new(objective));
+    let objective_fn =
+        move |graph: &Graph, partition: &mut Partition| objective.score(graph, partition);
     let avg_pop = (graph.total_pop as f64) / (partition.num_dists as f64);
     let params = RecomParams {
-        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as Pop,
         num_steps: n_steps,
         rng_seed: rng_seed,
         balance_ub: 0,
@@ -208,23 +285,94 @@ fn main() {
         "num_threads": n_threads,
         "num_steps": n_steps,
         "parallel": true,
-        "type": "short_bursts",
+        "type": match optimizer {
+            "annealing" => "simulated_annealing",
+            "pareto" => "pareto_short_bursts",
+            _ => "short_bursts",
+        },
         "burst_length": burst_length,
         "graph_json": graph_json,
+        "objective": serde_json::from_str::<Value>(objective_config).unwrap(),
     });
     if region_weights.is_some() {
         meta.as_object_mut()
             .unwrap()
             .insert("region_weights".to_string(), json!(region_weights));
     }
+    if optimizer == "short-bursts" {
+        let meta = meta.as_object_mut().unwrap();
+        meta.insert("tilt".to_string(), json!(tilt));
+        if let Some(restart_interval) = bursts.restart_interval {
+            meta.insert("restart_interval".to_string(), json!(restart_interval));
+            meta.insert("restart_from".to_string(), json!(restart_from_name));
+            if let RestartFrom::Random { steps } = restart_from {
+                meta.insert("restart_steps".to_string(), json!(steps));
+            }
+        }
+    }
+    if optimizer == "pareto" {
+        meta.as_object_mut()
+            .unwrap()
+            .insert("max_front_size".to_string(), json!(max_front_size));
+    }
+    if let Some(raw) = matches.value_of("schedule") {
+        meta.as_object_mut().unwrap().insert(
+            "schedule".to_string(),
+            serde_json::from_str::<Value>(raw).unwrap(),
+        );
+    }
     println!("{}", json!({ "meta": meta }).to_string());
-    multi_short_bursts(
-        &graph,
-        partition,
-        &params,
-        n_threads,
-        objective_fn,
-        burst_length,
-        true,
-    );
+    match optimizer {
+        "pareto" => {
+            // Open the output file up front so that a bad path fails before the run.
+            let pareto_output = Path::new(matches.value_of("pareto_output").unwrap());
+            if pareto_output.exists() {
+                panic!("Output file already exists.");
+            }
+            let mut output = io::BufWriter::new(fs::File::create(pareto_output).unwrap());
+            let objective_fns =
+                move |graph: &Graph, partition: &mut Partition| objective.scores(graph, partition);
+            let front = multi_pareto_short_bursts(
+                &graph,
+                partition,
+                &params,
+                n_threads,
+                objective_fns,
+                burst_length,
+                Some(max_front_size),
+                true,
+            );
+            for (partition, scores) in front.iter() {
+                let line = json!({
+                    "scores": scores,
+                    "assignment": partition.assignments.iter().enumerate().collect::<HashMap<usize, &u32>>(),
+                });
+                writeln!(output, "{}", line).unwrap();
+            }
+            output.flush().unwrap();
+        }
+        "annealing" => {
+            multi_simulated_annealing(
+                &graph,
+                partition,
+                &params,
+                n_threads,
+                objective_fn,
+                &schedule.unwrap(),
+                burst_length,
+                true,
+            );
+        }
+        _ => {
+            multi_short_bursts(
+                &graph,
+                partition,
+                &params,
+                n_threads,
+                objective_fn,
+                &bursts,
+                true,
+            );
+        }
+    }
 }
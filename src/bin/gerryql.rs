@@ -6,7 +6,7 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use clap::{App, Arg};
-// use frcw::init::graph_from_networkx;
+// use frcw::init::stream_graph_from_networkx;
 use petgraph::graph::{Graph, NodeIndex};
 use serde_json::Value;
 use std::collections::BTreeMap;
@@ -68,7 +68,7 @@ fn main() {
         println!("{:.3},{}", *val as f64 / 1000.0 as f64, freq);
     }
 
-    //let (graph, _) = graph_from_networkx(&graph_json, pop_col, vec![]).unwrap();
+    //let graph = stream_graph_from_networkx(&graph_json, pop_col, vec![], vec![]).unwrap();
 }
 
 /// Hardcoded stats from stdin (will be replaced later).
@@ -7,7 +7,7 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use clap::{value_t, App, Arg};
-use frcw::graph::Graph;
+use frcw::graph::{Graph, Pop};
 use frcw::partition::Partition;
 use frcw::recom::run::multi_chain;
 use frcw::recom::{RecomParams, RecomVariant};
@@ -107,8 +107,8 @@ fn main() {
     let partition = Partition::from_assignment_str(&graph, &assignments_data).unwrap();
     let avg_pop = (graph.total_pop as f64) / (partition.num_dists as f64);
     let params = RecomParams {
-        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + tol) * avg_pop as f64).ceil() as Pop,
         num_steps: n_steps,
         rng_seed: rng_seed,
         balance_ub: balance_ub,
@@ -20,6 +20,9 @@ mod subgraph {
         /// of `raw_nodes` (which are node IDs in `graph`). If a node
         /// does not appear in the subgraph, its index is -1.
         pub node_to_idx: Vec<i64>,
+        /// The index in the parent graph's `edges` of each edge in the
+        /// subgraph (only filled when edge attributes are copied).
+        pub raw_edges: Vec<usize>,
         /// A subgraph of the parent graph, with nodes relabeled to have
         /// consecutive node IDs. `nodes_to_idx` is used to map between
         /// node IDs in the subgraph and node IDs in the parent graph.
@@ -32,6 +35,7 @@ mod subgraph {
             return SubgraphBuffer {
                 raw_nodes: Vec::<usize>::with_capacity(b),
                 node_to_idx: vec![-1 as i64; n],
+                raw_edges: Vec::<usize>::new(),
                 graph: Graph::new_buffer(b),
             };
         }
@@ -40,6 +44,7 @@ mod subgraph {
         pub fn clear(&mut self) {
             self.raw_nodes.clear();
             self.node_to_idx.fill(-1);
+            self.raw_edges.clear();
             self.graph.clear();
         }
     }
@@ -73,6 +78,7 @@ mod spanning_tree {
 
 /// Buffer for spanning tree splits (used in ReCom).
 mod split {
+    use crate::graph::Pop;
     use std::collections::VecDeque;
 
     /// A reusable buffer for splits of a spanning tree.
@@ -96,7 +102,7 @@ mod split {
         pub deque: VecDeque<usize>,
         /// The populations of the subtrees rooted at each node
         /// in the BFS orientation.
-        pub tree_pops: Vec<u32>,
+        pub tree_pops: Vec<Pop>,
         /// Boolean representation of whether the population of
         /// the subtree rooted at a node (in the BFS orientation)
         /// has been computed.
@@ -116,7 +122,7 @@ mod split {
                 pred: vec![0; n],
                 succ: vec![Vec::<usize>::with_capacity(8); n],
                 deque: VecDeque::<usize>::with_capacity(n),
-                tree_pops: vec![0 as u32; n],
+                tree_pops: vec![0 as Pop; n],
                 pop_found: vec![false; n],
                 balance_nodes: Vec::<usize>::with_capacity(m),
                 in_a: vec![false; n],
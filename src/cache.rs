@@ -0,0 +1,531 @@
+//! A compact binary cache of a [Graph] and its seed [Partition].
+//!
+//! Parsing a large NetworkX JSON graph (and hashing it for chain metadata)
+//! can dominate the running time of short chains. A graph cache stores the
+//! parsed graph, the seed partition's assignment vector, and the SHA3 hash of
+//! the source files, along with enough information about the source files
+//! (paths, sizes, and modification times) and load parameters (population,
+//! assignment, attribute, and edge attribute columns) to detect when the
+//! cache is stale.
+//!
+//! All integers are little-endian; strings and vectors are prefixed with
+//! their length (as a `u64`). The payload is checksummed with SHA3-256.
+use crate::attr::AttrColumn;
+use crate::graph::{Edge, Graph, Pop};
+use crate::partition::{Partition, PartitionError};
+use sha3::{Digest, Sha3_256};
+use snafu::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// Magic bytes at the start of a graph cache file.
+const MAGIC: &[u8; 10] = b"FRCW CACHE";
+/// The current graph cache format version.
+const VERSION: u32 = 4;
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum CacheError {
+    #[snafu(display("I/O error: {message}"))]
+    ErrIO { message: String },
+    #[snafu(display("Not a graph cache file"))]
+    ErrBadMagic,
+    #[snafu(display("Unsupported graph cache version {version}"))]
+    ErrVersion { version: u32 },
+    #[snafu(display("Graph cache is truncated"))]
+    ErrTruncated,
+    #[snafu(display("Graph cache checksum mismatch"))]
+    ErrChecksum,
+    #[snafu(display("Unknown attribute column type {tag} in graph cache"))]
+    ErrAttrType { tag: u32 },
+    #[snafu(display("Population in graph cache overflows {bits}-bit populations"))]
+    ErrPopulationOverflow { bits: u32 },
+    #[snafu(display("Invalid seed partition in graph cache: {source}"))]
+    ErrPartition { source: PartitionError },
+}
+
+impl From<io::Error> for CacheError {
+    fn from(err: io::Error) -> CacheError {
+        CacheError::ErrIO {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Identifies the source data and load parameters a graph cache was built from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheKey {
+    /// The paths of the source files (e.g. a NetworkX JSON file, or an
+    /// edge list and a node attribute CSV).
+    pub sources: Vec<String>,
+    /// The population column.
+    pub pop_col: String,
+    /// The seed partition's assignment column.
+    pub assignment_col: String,
+    /// The attribute columns loaded into the graph.
+    pub columns: Vec<String>,
+    /// The edge attribute columns loaded into the graph.
+    pub edge_columns: Vec<String>,
+}
+
+/// A loaded graph cache.
+pub struct CachedGraph {
+    /// The cached graph.
+    pub graph: Graph,
+    /// The cached seed partition.
+    pub partition: Partition,
+    /// The SHA3-256 hash of the source files (as a hex string).
+    pub source_sha3: String,
+}
+
+/// Serializes cache data into a byte buffer.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u32(&mut self, val: u32) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn u64(&mut self, val: u64) {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+    }
+
+    fn usize(&mut self, val: usize) {
+        self.u64(val as u64);
+    }
+
+    // (`Pop` is `u64` with the `pop64` feature.)
+    #[allow(clippy::unnecessary_cast)]
+    fn pop(&mut self, val: Pop) {
+        self.u64(val as u64);
+    }
+
+    fn str(&mut self, val: &str) {
+        self.usize(val.len());
+        self.buf.extend_from_slice(val.as_bytes());
+    }
+
+    fn strs(&mut self, vals: &[String]) {
+        self.usize(vals.len());
+        for val in vals.iter() {
+            self.str(val);
+        }
+    }
+
+    /// Encodes a typed attribute column as a type tag followed by its values.
+    fn attr(&mut self, col: &AttrColumn) {
+        match col {
+            AttrColumn::Int(vals) => {
+                self.u32(0);
+                self.usize(vals.len());
+                for &val in vals.iter() {
+                    self.u64(val as u64);
+                }
+            }
+            AttrColumn::Float(vals) => {
+                self.u32(1);
+                self.usize(vals.len());
+                for &val in vals.iter() {
+                    self.u64(val.to_bits());
+                }
+            }
+            AttrColumn::Categorical { codes, categories } => {
+                self.u32(2);
+                self.strs(categories);
+                self.usize(codes.len());
+                for &code in codes.iter() {
+                    self.u32(code);
+                }
+            }
+        }
+    }
+}
+
+/// Deserializes cache data from a byte buffer.
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], CacheError> {
+        if self.buf.len() < len {
+            return Err(CacheError::ErrTruncated);
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, CacheError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CacheError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, CacheError> {
+        Ok(self.u64()? as usize)
+    }
+
+    fn pop(&mut self) -> Result<Pop, CacheError> {
+        Pop::try_from(self.u64()?)
+            .map_err(|_| CacheError::ErrPopulationOverflow { bits: Pop::BITS })
+    }
+
+    /// Reads a length prefix for a vector, guarding against absurd lengths.
+    fn len(&mut self, item_size: usize) -> Result<usize, CacheError> {
+        let len = self.usize()?;
+        if len.saturating_mul(item_size) > self.buf.len() {
+            return Err(CacheError::ErrTruncated);
+        }
+        Ok(len)
+    }
+
+    fn str(&mut self) -> Result<String, CacheError> {
+        let len = self.len(1)?;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn strs(&mut self) -> Result<Vec<String>, CacheError> {
+        let len = self.len(8)?;
+        (0..len).map(|_| self.str()).collect()
+    }
+
+    fn u32s(&mut self) -> Result<Vec<u32>, CacheError> {
+        let len = self.len(4)?;
+        (0..len).map(|_| self.u32()).collect()
+    }
+
+    fn attr(&mut self) -> Result<AttrColumn, CacheError> {
+        match self.u32()? {
+            0 => {
+                let len = self.len(8)?;
+                let vals = (0..len).map(|_| Ok(self.u64()? as i64));
+                Ok(AttrColumn::Int(vals.collect::<Result<_, CacheError>>()?))
+            }
+            1 => {
+                let len = self.len(8)?;
+                let vals = (0..len).map(|_| Ok(f64::from_bits(self.u64()?)));
+                Ok(AttrColumn::Float(vals.collect::<Result<_, CacheError>>()?))
+            }
+            2 => {
+                let categories = Arc::new(self.strs()?);
+                let codes = self.u32s()?;
+                if codes.iter().any(|&code| code as usize >= categories.len()) {
+                    return Err(CacheError::ErrTruncated);
+                }
+                Ok(AttrColumn::Categorical { codes, categories })
+            }
+            tag => Err(CacheError::ErrAttrType { tag }),
+        }
+    }
+}
+
+/// Encodes the size and modification time of each source file, so that
+/// stale caches can be detected without re-reading the sources.
+fn encode_sources(enc: &mut Encoder, sources: &[String]) -> Result<(), CacheError> {
+    enc.usize(sources.len());
+    for path in sources.iter() {
+        let meta = fs::metadata(path)?;
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        enc.str(path);
+        enc.u64(meta.len());
+        enc.u64(modified.as_secs());
+        enc.u32(modified.subsec_nanos());
+    }
+    Ok(())
+}
+
+/// Encodes the cache header (everything needed to check for staleness).
+fn encode_header(key: &CacheKey) -> Result<Vec<u8>, CacheError> {
+    let mut enc = Encoder::default();
+    encode_sources(&mut enc, &key.sources)?;
+    enc.str(&key.pop_col);
+    enc.str(&key.assignment_col);
+    enc.strs(&key.columns);
+    enc.strs(&key.edge_columns);
+    Ok(enc.buf)
+}
+
+/// Encodes a graph and its seed partition.
+fn encode_payload(graph: &Graph, partition: &Partition) -> Vec<u8> {
+    let mut enc = Encoder::default();
+    enc.usize(graph.pops.len());
+    // Populations are always stored as 64-bit integers (independent of `Pop`).
+    for &pop in graph.pops.iter() {
+        enc.pop(pop);
+    }
+    enc.pop(graph.total_pop);
+    for adj in graph.neighbors.iter() {
+        enc.usize(adj.len());
+        for &neighbor in adj.iter() {
+            enc.usize(neighbor);
+        }
+    }
+    enc.usize(graph.edges.len());
+    for edge in graph.edges.iter() {
+        enc.usize(edge.0);
+        enc.usize(edge.1);
+    }
+    for &start in graph.edges_start.iter() {
+        enc.usize(start);
+    }
+    let mut attr_keys: Vec<&String> = graph.attr.keys().collect();
+    attr_keys.sort();
+    enc.usize(attr_keys.len());
+    for key in attr_keys {
+        enc.str(key);
+        enc.attr(&graph.attr[key]);
+    }
+    let mut edge_attr_keys: Vec<&String> = graph.edge_attr.keys().collect();
+    edge_attr_keys.sort();
+    enc.usize(edge_attr_keys.len());
+    for key in edge_attr_keys {
+        enc.str(key);
+        enc.attr(&graph.edge_attr[key]);
+    }
+    enc.strs(&graph.node_ids);
+    enc.usize(partition.assignments.len());
+    for &assignment in partition.assignments.iter() {
+        enc.u32(assignment);
+    }
+    enc.buf
+}
+
+/// Decodes a graph and its seed partition.
+fn decode_payload(dec: &mut Decoder) -> Result<(Graph, Partition), CacheError> {
+    let num_nodes = dec.len(8)?;
+    let pops = (0..num_nodes)
+        .map(|_| dec.pop())
+        .collect::<Result<Vec<Pop>, CacheError>>()?;
+    let total_pop = dec.pop()?;
+    let mut neighbors = Vec::<Vec<usize>>::with_capacity(num_nodes);
+    for _ in 0..num_nodes {
+        let degree = dec.len(8)?;
+        neighbors.push(
+            (0..degree)
+                .map(|_| dec.usize())
+                .collect::<Result<Vec<usize>, CacheError>>()?,
+        );
+    }
+    let num_edges = dec.len(16)?;
+    let mut edges = Vec::<Edge>::with_capacity(num_edges);
+    for _ in 0..num_edges {
+        edges.push(Edge(dec.usize()?, dec.usize()?));
+    }
+    let edges_start = (0..num_nodes)
+        .map(|_| dec.usize())
+        .collect::<Result<Vec<usize>, CacheError>>()?;
+    let num_attrs = dec.len(16)?;
+    let mut attr = HashMap::<String, AttrColumn>::with_capacity(num_attrs);
+    for _ in 0..num_attrs {
+        let key = dec.str()?;
+        attr.insert(key, dec.attr()?);
+    }
+    let num_edge_attrs = dec.len(16)?;
+    let mut edge_attr = HashMap::<String, AttrColumn>::with_capacity(num_edge_attrs);
+    for _ in 0..num_edge_attrs {
+        let key = dec.str()?;
+        let vals = dec.attr()?;
+        if vals.len() != edges.len() {
+            return Err(CacheError::ErrTruncated);
+        }
+        edge_attr.insert(key, vals);
+    }
+    let node_ids = dec.strs()?;
+    let graph = Graph {
+        edges,
+        pops,
+        neighbors,
+        edges_start,
+        total_pop,
+        attr,
+        edge_attr,
+        node_ids,
+    };
+    // The seed partition is stored as a 0-indexed assignment vector
+    // (the rest of the partition is cheap to rebuild).
+    let assignments: Vec<u32> = dec.u32s()?.iter().map(|a| a + 1).collect();
+    let partition = Partition::from_assignments(&graph, &assignments)
+        .map_err(|source| CacheError::ErrPartition { source })?;
+    Ok((graph, partition))
+}
+
+/// Writes a graph cache to `path`.
+///
+/// Arguments:
+///   * `path`: The path of the cache file.
+///   * `key`: The source files and load parameters used to build the graph.
+///   * `source_sha3`: The SHA3-256 hash of the source files (as a hex string).
+///   * `graph`: The graph to cache.
+///   * `partition`: The seed partition to cache.
+pub fn write_cache(
+    path: &str,
+    key: &CacheKey,
+    source_sha3: &str,
+    graph: &Graph,
+    partition: &Partition,
+) -> Result<(), CacheError> {
+    let header = encode_header(key)?;
+    let payload = encode_payload(graph, partition);
+    let mut meta = Encoder::default();
+    meta.u32(VERSION);
+    meta.usize(header.len());
+    meta.str(source_sha3);
+
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&meta.buf)?;
+    file.write_all(&header)?;
+    file.write_all(&Sha3_256::digest(&payload))?;
+    file.write_all(&payload)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Reads a graph cache from `path`.
+///
+/// Returns `Ok(None)` if the cache is stale (that is, if the source files
+/// have changed since the cache was written or the cache was built with
+/// different load parameters). Returns an error if the cache is unreadable
+/// or corrupt.
+pub fn read_cache(path: &str, key: &CacheKey) -> Result<Option<CachedGraph>, CacheError> {
+    let raw = fs::read(path)?;
+    let mut dec = Decoder { buf: &raw };
+    if dec.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(CacheError::ErrBadMagic);
+    }
+    let version = dec.u32()?;
+    if version != VERSION {
+        return Err(CacheError::ErrVersion { version });
+    }
+    let header_len = dec.usize()?;
+    let source_sha3 = dec.str()?;
+    let header = dec.bytes(header_len)?;
+    if header != encode_header(key)?.as_slice() {
+        return Ok(None);
+    }
+    let checksum = dec.bytes(32)?;
+    if checksum != Sha3_256::digest(dec.buf).as_slice() {
+        return Err(CacheError::ErrChecksum);
+    }
+    let (graph, partition) = decode_payload(&mut dec)?;
+    Ok(Some(CachedGraph {
+        graph,
+        partition,
+        source_sha3,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Returns a path in the system temporary directory unique to this test.
+    fn temp_path(name: &str) -> String {
+        let mut path = env::temp_dir();
+        path.push(format!("frcw_cache_{}_{}", process::id(), name));
+        path.into_os_string().into_string().unwrap()
+    }
+
+    /// Builds a small graph with attributes and a cache key backed by a
+    /// source file on disk.
+    fn fixture(name: &str) -> (Graph, Partition, CacheKey) {
+        let source = temp_path(&format!("{}.src", name));
+        fs::write(&source, "source").unwrap();
+        let mut graph = Graph::rect_grid(3, 2);
+        graph.attr.insert(
+            "county".to_string(),
+            AttrColumn::parse(["a", "a", "b", "b", "c", "c"]),
+        );
+        graph.attr.insert(
+            "votes".to_string(),
+            AttrColumn::parse(["1", "2", "3", "4", "5", "6"]),
+        );
+        graph.attr.insert(
+            "share".to_string(),
+            AttrColumn::parse(["0.5", "0.25", "1", "0", "0.125", "2"]),
+        );
+        graph.edge_attr.insert(
+            "shared_perim".to_string(),
+            AttrColumn::Float((0..graph.edges.len()).map(|e| e as f64 / 2.0).collect()),
+        );
+        graph.edge_attr.insert(
+            "kind".to_string(),
+            AttrColumn::parse((0..graph.edges.len()).map(|e| ["rook", "queen"][e % 2])),
+        );
+        graph.node_ids = (10..16).map(|id| id.to_string()).collect();
+        let partition = Partition::from_assignments(&graph, &vec![1, 1, 2, 2, 3, 3]).unwrap();
+        let key = CacheKey {
+            sources: vec![source],
+            pop_col: "TOTPOP".to_string(),
+            assignment_col: "CD".to_string(),
+            columns: vec!["county".to_string()],
+            edge_columns: vec!["shared_perim".to_string(), "kind".to_string()],
+        };
+        (graph, partition, key)
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let (graph, partition, key) = fixture("round_trip");
+        let path = temp_path("round_trip.bin");
+        write_cache(&path, &key, "abc123", &graph, &partition).unwrap();
+        let cached = read_cache(&path, &key).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&key.sources[0]).unwrap();
+
+        assert_eq!(cached.source_sha3, "abc123");
+        assert_eq!(cached.graph.edges, graph.edges);
+        assert_eq!(cached.graph.edges_start, graph.edges_start);
+        assert_eq!(cached.graph.neighbors, graph.neighbors);
+        assert_eq!(cached.graph.pops, graph.pops);
+        assert_eq!(cached.graph.total_pop, graph.total_pop);
+        assert_eq!(cached.graph.attr, graph.attr);
+        assert_eq!(cached.graph.edge_attr, graph.edge_attr);
+        assert_eq!(cached.graph.node_ids, graph.node_ids);
+        assert_eq!(cached.partition.assignments, partition.assignments);
+        assert_eq!(cached.partition.dist_nodes, partition.dist_nodes);
+    }
+
+    #[test]
+    fn cache_stale_parameters() {
+        let (graph, partition, key) = fixture("stale");
+        let path = temp_path("stale.bin");
+        write_cache(&path, &key, "abc123", &graph, &partition).unwrap();
+        let other_key = CacheKey {
+            assignment_col: "SEND".to_string(),
+            ..key.clone()
+        };
+        let cached = read_cache(&path, &other_key).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&key.sources[0]).unwrap();
+        assert!(cached.is_none());
+    }
+
+    #[test]
+    fn cache_corrupt_payload() {
+        let (graph, partition, key) = fixture("corrupt");
+        let path = temp_path("corrupt.bin");
+        write_cache(&path, &key, "abc123", &graph, &partition).unwrap();
+        let mut raw = fs::read(&path).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0xff;
+        fs::write(&path, raw).unwrap();
+        let err = read_cache(&path, &key).err();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&key.sources[0]).unwrap();
+        assert_eq!(err, Some(CacheError::ErrChecksum));
+    }
+}
@@ -0,0 +1,501 @@
+//! Output options shared by the command-line tools (`frcw` and `frcw_replay`).
+use crate::config::{parse_elections_config, parse_shares_config};
+use crate::graph::Graph;
+use crate::init::check_sum_columns;
+#[cfg(feature = "sqlite")]
+use crate::stats::SqliteWriter;
+use crate::stats::{
+    AssignmentsOnlyWriter, BenWriter, CanonicalWriter, Compactness, CompressedWriter, Compression,
+    CutEdges, DiagnosticsWriter, Election, HeatmapWriter, JSONLWriter, LogSpanningTreeCounts,
+    PcompressWriter, RegionSplits, StatsWriter, SummaryWriter, TSVWriter, TeeWriter, Updater,
+};
+#[cfg(feature = "columnar")]
+use crate::stats::{ColumnarFormat, ColumnarWriter};
+use clap::{value_t, Arg, ArgMatches};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::{fs, io};
+
+/// Returns the command-line arguments that select and configure output writers.
+pub fn output_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("writer")
+            .long("writer")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .default_value("jsonl")
+            .help("The output format (repeat with --output-file to write several)."),
+        // other options: jsonl-full, tsv, summary, diagnostics, heatmaps, parquet, arrow-ipc (columnar feature), sqlite (sqlite feature)
+        Arg::with_name("sum_cols")
+            .long("sum-cols")
+            .multiple(true)
+            .takes_value(true),
+        Arg::with_name("elections")
+            .long("elections")
+            .takes_value(true)
+            .help("Elections (name -> [first party column, second party column]) for partisan metrics."),
+        Arg::with_name("shares")
+            .long("shares")
+            .takes_value(true)
+            .help("Shares (name -> [numerator column, denominator column]) for summary output."),
+        Arg::with_name("summary_bin_width")
+            .long("summary-bin-width")
+            .takes_value(true)
+            .default_value("0.001")
+            .help("The histogram bin width for shares in summary output."),
+        Arg::with_name("diagnostics")
+            .long("diagnostics")
+            .multiple(true)
+            .takes_value(true)
+            .help("Statistics (JSON pointers, e.g. /num_cut_edges or /self_loops) for diagnostics output."),
+        Arg::with_name("diagnostics_max_lag")
+            .long("diagnostics-max-lag")
+            .takes_value(true)
+            .default_value("1000")
+            .help("The maximum autocorrelation lag for diagnostics output."),
+        Arg::with_name("diagnostics_progress")
+            .long("diagnostics-progress")
+            .takes_value(true)
+            .help("Write diagnostics to stderr every N steps."),
+        Arg::with_name("anchors")
+            .long("anchors")
+            .multiple(true)
+            .takes_value(true)
+            .help("Anchor node IDs for co-assignment frequencies in heatmaps output."),
+        Arg::with_name("compactness")
+            .long("compactness")
+            .help("Record district Polsby-Popper and Schwartzberg scores and the boundary length."),
+        Arg::with_name("area_col")
+            .long("area-col")
+            .takes_value(true)
+            .default_value("area")
+            .help("The node area column (for --compactness)."),
+        Arg::with_name("perimeter_col")
+            .long("perimeter-col")
+            .takes_value(true)
+            .default_value("perimeter")
+            .help("The node perimeter column (for --compactness)."),
+        Arg::with_name("shared_perim_col")
+            .long("shared-perim-col")
+            .takes_value(true)
+            .default_value("shared_perim")
+            .help("The edge shared boundary length column (for --compactness)."),
+        Arg::with_name("cut_edges_count").long("cut-edges-count"),
+        Arg::with_name("spanning_tree_counts")
+            .long("st-counts")
+            .help("Report the log spanning tree count of each district."),
+        Arg::with_name("output-file")
+            .long("output-file")
+            .short("o")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("The path to write the output to (one per --writer)."),
+        Arg::with_name("compress")
+            .long("compress")
+            .takes_value(true)
+            .possible_values(&["zstd", "xz"])
+            .requires("output-file")
+            .help("Compress the output file (inferred from a .zst or .xz suffix)."),
+    ]
+}
+
+/// Output writer options (parsed from the arguments in [output_args]).
+pub struct OutputOptions {
+    /// The output formats, one per writer.
+    pub writers: Vec<String>,
+    /// The output paths (at most one per writer; a single writer may write to stdout).
+    pub output_paths: Vec<String>,
+    /// The output compression (inferred from each output path if `None`).
+    pub compress: Option<Compression>,
+    /// The number of steps in the chain (unknown for replayed chains).
+    pub n_steps: Option<u64>,
+    /// The attribute columns to sum over.
+    pub sum_cols: Vec<String>,
+    /// The region columns to record split statistics for.
+    pub region_cols: Vec<String>,
+    /// Elections (name, first party column, second party column).
+    pub elections: Option<Vec<(String, String, String)>>,
+    /// Shares (name, numerator column, denominator column) for summary output.
+    pub shares: Option<Vec<(String, String, String)>>,
+    /// The histogram bin width for shares in summary output.
+    pub summary_bin_width: f64,
+    /// The statistics (JSON pointers) for diagnostics output.
+    pub diagnostics: Vec<String>,
+    /// The maximum autocorrelation lag for diagnostics output.
+    pub diagnostics_max_lag: usize,
+    /// The interval (in steps) between diagnostics progress reports.
+    pub diagnostics_progress: Option<u64>,
+    /// Anchor node IDs for heatmaps output.
+    pub anchors: Vec<String>,
+    /// The area, perimeter, and shared perimeter columns (if compactness
+    /// scores are recorded).
+    pub compactness: Option<(String, String, String)>,
+    /// Record the number of cut edges in JSONL output.
+    pub cut_edges_count: bool,
+    /// Record log spanning tree counts.
+    pub st_counts: bool,
+}
+
+impl OutputOptions {
+    /// Parses output options from command-line arguments (see [output_args]).
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - The parsed command-line arguments.
+    /// * `n_steps` - The number of steps in the chain (if known).
+    /// * `region_cols` - The region columns to record split statistics for.
+    pub fn from_matches(
+        matches: &ArgMatches,
+        n_steps: Option<u64>,
+        region_cols: Vec<String>,
+    ) -> OutputOptions {
+        let strings = |name: &str| -> Vec<String> {
+            matches
+                .values_of(name)
+                .unwrap_or_default()
+                .map(|value| value.to_string())
+                .collect()
+        };
+        let writers = strings("writer");
+        let output_paths = strings("output-file");
+        // A single writer may write to stdout; multiple writers each need an output file.
+        if output_paths.len() > writers.len()
+            || (writers.len() > 1 && output_paths.len() != writers.len())
+        {
+            panic!("Parameter error: specify one --output-file per --writer");
+        }
+        let compress = matches.value_of("compress").map(|name| {
+            Compression::from_name(name)
+                .unwrap_or_else(|| panic!("Parameter error: invalid compression '{}'", name))
+        });
+        let diagnostics = match matches.values_of("diagnostics") {
+            Some(_) => strings("diagnostics"),
+            None => vec!["/num_cut_edges".to_string()],
+        };
+        let diagnostics_progress = matches.value_of("diagnostics_progress").map(|_| {
+            value_t!(matches.value_of("diagnostics_progress"), u64).unwrap_or_else(|e| e.exit())
+        });
+        let compactness = match matches.is_present("compactness") {
+            true => Some((
+                matches.value_of("area_col").unwrap().to_string(),
+                matches.value_of("perimeter_col").unwrap().to_string(),
+                matches.value_of("shared_perim_col").unwrap().to_string(),
+            )),
+            false => None,
+        };
+        OutputOptions {
+            writers,
+            output_paths,
+            compress,
+            n_steps,
+            sum_cols: strings("sum_cols"),
+            region_cols,
+            elections: parse_elections_config(matches.value_of("elections").unwrap_or_default()),
+            shares: parse_shares_config(matches.value_of("shares").unwrap_or_default()),
+            summary_bin_width: value_t!(matches.value_of("summary_bin_width"), f64)
+                .unwrap_or_else(|e| e.exit()),
+            diagnostics,
+            diagnostics_max_lag: value_t!(matches.value_of("diagnostics_max_lag"), usize)
+                .unwrap_or_else(|e| e.exit()),
+            diagnostics_progress,
+            anchors: strings("anchors"),
+            compactness,
+            cut_edges_count: matches.is_present("cut_edges_count"),
+            st_counts: matches.is_present("spanning_tree_counts"),
+        }
+    }
+
+    /// Returns the node columns that must be loaded (and must be numeric):
+    /// the sum columns, followed by the election vote columns, the share
+    /// columns, and the area and perimeter columns.
+    pub fn numeric_columns(&self) -> Vec<String> {
+        let mut numeric_cols = self.sum_cols.clone();
+        let mut score_cols: Vec<&str> = vec![];
+        for (_, first, second) in self
+            .elections
+            .iter()
+            .flatten()
+            .chain(self.shares.iter().flatten())
+        {
+            score_cols.extend([first.as_str(), second.as_str()]);
+        }
+        if let Some((area_col, perimeter_col, _)) = &self.compactness {
+            score_cols.extend([area_col.as_str(), perimeter_col.as_str()]);
+        }
+        for col in score_cols {
+            if !numeric_cols.iter().any(|c| c == col) {
+                numeric_cols.push(col.to_string());
+            }
+        }
+        numeric_cols
+    }
+
+    /// Returns the edge columns that must be loaded.
+    pub fn edge_columns(&self) -> Vec<String> {
+        self.compactness
+            .iter()
+            .map(|(_, _, shared_perim_col)| shared_perim_col.clone())
+            .collect()
+    }
+
+    /// Checks that a graph has the columns and nodes the writers need.
+    /// Panics with a parameter error otherwise.
+    pub fn check_graph(&self, graph: &Graph) {
+        check_sum_columns(graph, &self.numeric_columns())
+            .unwrap_or_else(|err| panic!("Could not load graph: {}", err));
+        for id in self.anchors.iter() {
+            if graph.node_index(id).is_none() {
+                panic!("Parameter error: unknown anchor node '{}'", id);
+            }
+        }
+        if let Some((_, _, shared_perim_col)) = &self.compactness {
+            if !graph
+                .edge_attr
+                .get(shared_perim_col)
+                .is_some_and(|col| col.is_numeric())
+            {
+                panic!(
+                    "Parameter error: --compactness requires NetworkX JSON input with numeric edge column '{}'",
+                    shared_perim_col
+                );
+            }
+        }
+    }
+
+    /// Adds the compactness, share, and election columns to run metadata.
+    pub fn add_meta(&self, meta: &mut Value) {
+        let meta = meta.as_object_mut().unwrap();
+        if let Some((area_col, perimeter_col, shared_perim_col)) = &self.compactness {
+            meta.insert(
+                "compactness".to_string(),
+                json!({
+                    "area_col": area_col,
+                    "perimeter_col": perimeter_col,
+                    "shared_perim_col": shared_perim_col,
+                }),
+            );
+        }
+        if let Some(shares) = &self.shares {
+            let shares: serde_json::Map<String, Value> = shares
+                .iter()
+                .map(|(name, numerator, denominator)| {
+                    (name.clone(), json!([numerator, denominator]))
+                })
+                .collect();
+            meta.insert("shares".to_string(), Value::Object(shares));
+        }
+        if let Some(elections) = &self.elections {
+            let elections: serde_json::Map<String, Value> = elections
+                .iter()
+                .map(|(name, first, second)| (name.clone(), json!([first, second])))
+                .collect();
+            meta.insert("elections".to_string(), Value::Object(elections));
+        }
+    }
+
+    /// Returns `true` if any writer expects run metadata on stdout.
+    pub fn prints_meta(&self) -> bool {
+        self.writers.iter().any(|writer_str| {
+            matches!(
+                writer_str.as_str(),
+                "jsonl" | "jsonl-full" | "summary" | "diagnostics" | "heatmaps"
+            )
+        })
+    }
+
+    /// Returns fresh instances of the updaters shared by all writers
+    /// (region splits, elections, and compactness scores).
+    fn updaters(&self) -> Vec<Box<dyn Updater>> {
+        let mut updaters: Vec<Box<dyn Updater>> = vec![];
+        // Region split statistics are recorded for each region column.
+        for col in self.region_cols.iter() {
+            updaters.push(Box::new(RegionSplits::new(col)));
+        }
+        for (name, first, second) in self.elections.iter().flatten() {
+            updaters.push(Box::new(Election::new(name, first, second)));
+        }
+        if let Some((area_col, perimeter_col, shared_perim_col)) = &self.compactness {
+            updaters.push(Box::new(Compactness::new(
+                area_col,
+                perimeter_col,
+                shared_perim_col,
+            )));
+        }
+        updaters
+    }
+
+    /// Builds a writer for one output format.
+    fn make_writer(
+        &self,
+        writer_str: &str,
+        output_buffer: Box<dyn io::Write + Send>,
+    ) -> Box<dyn StatsWriter> {
+        let with_updaters = |writer: JSONLWriter| -> JSONLWriter {
+            self.updaters()
+                .into_iter()
+                .fold(writer, JSONLWriter::with_updater)
+        };
+        match writer_str {
+            "tsv" => Box::new(TSVWriter::new(output_buffer)),
+            "jsonl" => Box::new(with_updaters(JSONLWriter::new(
+                false,
+                self.st_counts,
+                self.cut_edges_count,
+                output_buffer,
+            ))),
+            "pcompress" => Box::new(PcompressWriter::new(output_buffer)),
+            "jsonl-full" => Box::new(with_updaters(JSONLWriter::new(
+                true,
+                self.st_counts,
+                self.cut_edges_count,
+                output_buffer,
+            ))),
+            "assignments" => Box::new(AssignmentsOnlyWriter::new(false, output_buffer)),
+            "canonicalized-assignments" => {
+                Box::new(AssignmentsOnlyWriter::new(true, output_buffer))
+            }
+            "canonical" => Box::new(CanonicalWriter::new(output_buffer)),
+            "ben" => Box::new(BenWriter::new(output_buffer)),
+            "diagnostics" => {
+                // Cut edge counts (and spanning tree counts, if requested)
+                // can always be selected.
+                let mut writer = DiagnosticsWriter::new(
+                    self.n_steps,
+                    self.diagnostics_max_lag,
+                    self.diagnostics.clone(),
+                    self.diagnostics_progress,
+                    output_buffer,
+                )
+                .with_updater(Box::new(CutEdges::new()));
+                if self.st_counts {
+                    writer = writer.with_updater(Box::new(LogSpanningTreeCounts::new()));
+                }
+                Box::new(
+                    self.updaters()
+                        .into_iter()
+                        .fold(writer, DiagnosticsWriter::with_updater),
+                )
+            }
+            "heatmaps" => Box::new(HeatmapWriter::new(
+                self.n_steps,
+                self.anchors.clone(),
+                output_buffer,
+            )),
+            "summary" => {
+                let writer =
+                    SummaryWriter::new(self.n_steps, self.summary_bin_width, output_buffer);
+                let writer = self.shares.iter().flatten().fold(
+                    writer,
+                    |writer, (name, numerator, denominator)| {
+                        writer.with_share(name, numerator, denominator)
+                    },
+                );
+                Box::new(self.elections.iter().flatten().fold(
+                    writer,
+                    |writer, (name, first, second)| {
+                        writer.with_election(Election::new(name, first, second))
+                    },
+                ))
+            }
+            #[cfg(feature = "columnar")]
+            "parquet" => Box::new(ColumnarWriter::new(ColumnarFormat::Parquet, output_buffer)),
+            #[cfg(feature = "columnar")]
+            "arrow-ipc" => Box::new(ColumnarWriter::new(ColumnarFormat::ArrowIPC, output_buffer)),
+            bad => panic!("Parameter error: invalid writer '{}'", bad),
+        }
+    }
+
+    /// Opens the output files and builds the writers (combined with a
+    /// [TeeWriter] if there are several).
+    ///
+    /// # Arguments
+    ///
+    /// * `meta` - The run metadata (recorded by the SQLite writer).
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+    pub fn writer(&self, meta: &Value) -> Box<dyn StatsWriter> {
+        let mut writers = Vec::<Box<dyn StatsWriter>>::with_capacity(self.writers.len());
+        for (idx, writer_str) in self.writers.iter().enumerate() {
+            // The SQLite writer appends a run to a (possibly existing) database.
+            #[cfg(feature = "sqlite")]
+            if writer_str == "sqlite" {
+                let path = self
+                    .output_paths
+                    .get(idx)
+                    .expect("Parameter error: the sqlite writer requires an output file");
+                writers.push(Box::new(
+                    SqliteWriter::new(path, meta).expect("Could not open SQLite database"),
+                ));
+                continue;
+            }
+            let output_path = self.output_paths.get(idx).map(Path::new);
+            let output_buffer: Box<dyn io::Write + Send> = match output_path {
+                Some(path) => {
+                    if path.exists() {
+                        panic!("Output file already exists.");
+                    };
+                    Box::new(io::BufWriter::new(fs::File::create(path).unwrap()))
+                }
+                None => Box::new(io::BufWriter::new(std::io::stdout())),
+            };
+            let compression = self
+                .compress
+                .or_else(|| output_path.and_then(Compression::from_path));
+            writers.push(match compression {
+                Some(compression) => Box::new(
+                    CompressedWriter::new(compression, output_buffer, |output| {
+                        self.make_writer(writer_str, output)
+                    })
+                    .expect("Could not initialize output compression"),
+                ),
+                None => self.make_writer(writer_str, output_buffer),
+            });
+        }
+        match writers.len() {
+            1 => writers.pop().unwrap(),
+            _ => Box::new(TeeWriter::new(writers)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::App;
+
+    /// Parses output options from a command line (without the program name).
+    fn parse(args: &[&str]) -> OutputOptions {
+        let matches = App::new("test")
+            .args(&output_args())
+            .get_matches_from(["test"].iter().chain(args.iter()));
+        OutputOptions::from_matches(&matches, None, vec!["county".to_string()])
+    }
+
+    #[test]
+    fn output_columns() {
+        let options = parse(&[
+            "--sum-cols",
+            "BVAP",
+            "VAP",
+            "--shares",
+            r#"{"BVAP": ["BVAP", "VAP"]}"#,
+            "--elections",
+            r#"{"PRES16": ["D16", "R16"]}"#,
+            "--compactness",
+        ]);
+        assert_eq!(
+            options.numeric_columns(),
+            vec!["BVAP", "VAP", "D16", "R16", "area", "perimeter"]
+        );
+        assert_eq!(options.edge_columns(), vec!["shared_perim"]);
+        assert_eq!(options.diagnostics, vec!["/num_cut_edges"]);
+        assert_eq!(options.updaters().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "specify one --output-file per --writer")]
+    fn multiple_writers_need_output_files() {
+        parse(&["--writer", "jsonl", "--writer", "summary", "-o", "a.jsonl"]);
+    }
+}
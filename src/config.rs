@@ -17,3 +17,34 @@ pub fn parse_region_weights_config(region_weights_raw: &str) -> Option<Vec<(Stri
         }
     }
 }
+
+/// Parses a JSON object mapping names to pairs of columns, sorted by name.
+fn parse_column_pairs_config(raw: &str) -> Option<Vec<(String, String, String)>> {
+    match raw {
+        "" => None,
+        raw => {
+            let mut pairs: Vec<(String, String, String)> =
+                from_str::<HashMap<&str, (String, String)>>(raw)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(name, (first, second))| (name.to_owned(), first, second))
+                    .collect();
+            pairs.sort();
+            Some(pairs)
+        }
+    }
+}
+
+/// Parses a JSON object mapping election names to pairs of vote columns
+/// (first party, second party), e.g. `{"PRES16": ["G16DPRS", "G16RPRS"]}`.
+/// Elections are sorted by name.
+pub fn parse_elections_config(elections_raw: &str) -> Option<Vec<(String, String, String)>> {
+    parse_column_pairs_config(elections_raw)
+}
+
+/// Parses a JSON object mapping share names to pairs of columns
+/// (numerator, denominator), e.g. `{"BVAP": ["BVAP", "VAP"]}`.
+/// Shares are sorted by name.
+pub fn parse_shares_config(shares_raw: &str) -> Option<Vec<(String, String, String)>> {
+    parse_column_pairs_config(shares_raw)
+}
@@ -1,8 +1,18 @@
 //! A lightweight graph with population metadata.
+use crate::attr::AttrColumn;
 use snafu::prelude::*;
 use std::cmp::{max, min};
 use std::collections::HashMap;
 
+/// Node and district populations. Populations are 32-bit by default;
+/// building with the `pop64` feature switches to 64-bit populations
+/// (e.g. for weighted populations or large aggregate columns).
+#[cfg(not(feature = "pop64"))]
+pub type Pop = u32;
+/// Node and district populations (64-bit, as the `pop64` feature is enabled).
+#[cfg(feature = "pop64")]
+pub type Pop = u64;
+
 /// Edges are pairs of node indices.
 #[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub struct Edge(pub usize, pub usize);
@@ -26,6 +36,16 @@ pub enum GraphError {
     },
     #[snafu(display("Could not parse population value: {pop}"))]
     ErrPopulationParse { pop: String },
+    #[snafu(display("Total population overflows {bits}-bit populations (try the pop64 feature)"))]
+    ErrPopulationOverflow { bits: u32 },
+}
+
+/// Sums node populations, returning an error (rather than silently
+/// wrapping) if the total does not fit in a [Pop].
+pub fn total_pop(pops: &[Pop]) -> Result<Pop, GraphError> {
+    pops.iter()
+        .try_fold(0 as Pop, |total, &pop| total.checked_add(pop))
+        .ok_or(GraphError::ErrPopulationOverflow { bits: Pop::BITS })
 }
 
 /// A lightweight graph with population metadata.
@@ -36,7 +56,7 @@ pub struct Graph {
     /// (Nodes are represented implicitly.)
     pub edges: Vec<Edge>,
     /// The population at each node.
-    pub pops: Vec<u32>,
+    pub pops: Vec<Pop>,
     /// The graph's adjacencies (list-of-lists format).
     pub neighbors: Vec<Vec<usize>>,
     /// Maps between node indices and blocks of edges in `edges`.
@@ -45,9 +65,15 @@ pub struct Graph {
     pub edges_start: Vec<usize>,
     /// The total population over all nodes.
     /// (Should be equal to the sum of `pops`.)
-    pub total_pop: u32,
-    /// Additional node attributes (optional).
-    pub attr: HashMap<String, Vec<String>>,
+    pub total_pop: Pop,
+    /// Additional typed node attributes (optional).
+    pub attr: HashMap<String, AttrColumn>,
+    /// Additional typed edge attributes (optional), such as shared
+    /// boundary lengths or edge types. Each column is indexed like `edges`.
+    pub edge_attr: HashMap<String, AttrColumn>,
+    /// The original node identifiers (e.g. GEOIDs), in index order.
+    /// Empty when nodes are identified by their indices.
+    pub node_ids: Vec<String>,
 }
 
 impl Graph {
@@ -55,12 +81,14 @@ impl Graph {
     /// `8 * n` edges.
     pub fn new_buffer(n: usize) -> Graph {
         Graph {
-            pops: Vec::<u32>::with_capacity(n),
+            pops: Vec::<Pop>::with_capacity(n),
             neighbors: vec![Vec::<usize>::with_capacity(8); n],
             edges: Vec::<Edge>::with_capacity(8 * n),
             edges_start: vec![0 as usize; n],
             total_pop: 0,
             attr: HashMap::new(),
+            edge_attr: HashMap::new(),
+            node_ids: vec![],
         }
     }
 
@@ -76,11 +104,42 @@ impl Graph {
     /// If the edge list or populations cannot be parsed, or if the number
     /// of nodes is inconsistent between `edge_lists` and `populations`,
     /// and `Err` is returned. Empty graphs are considered invalid, as are
-    /// graphs with duplicate edges.
+    /// graphs with duplicate edges and graphs whose total population
+    /// overflows [Pop].
     ///
     /// The caller is responsible for ensuring the graph is connected
     /// (if that property is desired).
     pub fn from_edge_list(edge_list: &str, populations: &str) -> Result<Graph, GraphError> {
+        let (edges, edges_start, neighbors) = Graph::parse_edge_list(edge_list)?;
+        let mut parsed_pops = Vec::<Pop>::with_capacity(neighbors.len());
+        for pop in populations.replace('\n', "").split(' ') {
+            match pop.parse::<Pop>() {
+                Ok(parsed) => parsed_pops.push(parsed),
+                Err(_) => return Err(GraphError::ErrPopulationParse { pop: pop.into() }),
+            }
+        }
+        Graph::from_parsed_edge_list(edges, edges_start, neighbors, parsed_pops)
+    }
+
+    /// Initializes a graph from a newline-delimited edge list format representation
+    /// and a vector of (already parsed) node populations.
+    ///
+    /// Arguments:
+    ///   * `edge_list`: The list of edges in the graph (see [Graph::from_edge_list]).
+    ///   * `pops`: The population of each node in the graph, in index order.
+    ///
+    /// Errors are handled as in [Graph::from_edge_list].
+    pub fn from_edge_list_with_pops(edge_list: &str, pops: Vec<Pop>) -> Result<Graph, GraphError> {
+        let (edges, edges_start, neighbors) = Graph::parse_edge_list(edge_list)?;
+        Graph::from_parsed_edge_list(edges, edges_start, neighbors, pops)
+    }
+
+    /// Parses a newline-delimited edge list into sorted, 0-indexed edges,
+    /// the starting index of each node's block of edges, and node adjacencies.
+    #[allow(clippy::type_complexity)]
+    fn parse_edge_list(
+        edge_list: &str,
+    ) -> Result<(Vec<Edge>, Vec<usize>, Vec<Vec<usize>>), GraphError> {
         let mut edges = Vec::<Edge>::new();
         if edge_list.is_empty() {
             return Err(GraphError::ErrEmptyEdgeList);
@@ -150,13 +209,17 @@ impl Graph {
             edges_start.push(edges.len());
         }
 
-        let mut parsed_pops = Vec::<u32>::with_capacity(neighbors.len());
-        for pop in populations.replace('\n', "").split(' ') {
-            match pop.parse::<u32>() {
-                Ok(parsed) => parsed_pops.push(parsed),
-                Err(_) => return Err(GraphError::ErrPopulationParse { pop: pop.into() }),
-            }
-        }
+        Ok((edges, edges_start, neighbors))
+    }
+
+    /// Builds a graph from a parsed edge list and node populations,
+    /// checking that the number of nodes is consistent.
+    fn from_parsed_edge_list(
+        edges: Vec<Edge>,
+        edges_start: Vec<usize>,
+        neighbors: Vec<Vec<usize>>,
+        parsed_pops: Vec<Pop>,
+    ) -> Result<Graph, GraphError> {
         if parsed_pops.len() != neighbors.len() {
             return Err(GraphError::ErrNodeLengthMismatch {
                 edge_list_nodes: neighbors.len(),
@@ -165,12 +228,14 @@ impl Graph {
         }
 
         Ok(Graph {
-            total_pop: parsed_pops.iter().sum(),
+            total_pop: total_pop(&parsed_pops)?,
             pops: parsed_pops,
             neighbors: neighbors,
             edges: edges,
             edges_start: edges_start,
             attr: HashMap::new(),
+            edge_attr: HashMap::new(),
+            node_ids: vec![],
         })
     }
 
@@ -208,15 +273,54 @@ impl Graph {
             }
         }
         Graph {
-            pops: vec![1 as u32; size],
+            pops: vec![1 as Pop; size],
             neighbors: neighbors,
             edges: edges,
             edges_start: edges_start,
-            total_pop: size as u32,
+            total_pop: size as Pop,
             attr: HashMap::new(),
+            edge_attr: HashMap::new(),
+            node_ids: vec![],
+        }
+    }
+
+    /// Returns the original identifier of the node at `index`
+    /// (or the index itself if the graph has no node identifiers).
+    pub fn node_id(&self, index: usize) -> String {
+        match self.node_ids.get(index) {
+            Some(id) => id.clone(),
+            None => index.to_string(),
+        }
+    }
+
+    /// Returns the index of the node with the original identifier `id`
+    /// (or, if the graph has no node identifiers, the index `id` itself).
+    pub fn node_index(&self, id: &str) -> Option<usize> {
+        match self.node_ids.is_empty() {
+            true => id
+                .parse()
+                .ok()
+                .filter(|&index| index < self.neighbors.len()),
+            false => self.node_ids.iter().position(|node_id| node_id == id),
         }
     }
 
+    /// Returns the index in `edges` of the edge between nodes `a` and `b`
+    /// (if any).
+    pub fn edge_index(&self, a: usize, b: usize) -> Option<usize> {
+        let (src, dst) = (min(a, b), max(a, b));
+        let start = self.edges_start[src];
+        let end = match self.edges_start.get(src + 1) {
+            Some(&end) => end,
+            None => self.edges.len(),
+        };
+        // Blocks are short (and not necessarily sorted), so we scan them.
+        self.edges[start..end]
+            .iter()
+            .position(|edge| edge.1 == dst)
+            .map(|offset| start + offset)
+    }
+
     /// Resets a graph's containers.
     /// (Useful when using a graph as a subgraph buffer.)
     pub fn clear(&mut self) {
@@ -242,7 +346,7 @@ mod tests {
     fn rect_grid_1x1() {
         let grid = Graph::rect_grid(1, 1);
         assert_eq!(grid.edges.len(), 0);
-        assert_eq!(grid.pops, vec![1 as u32]);
+        assert_eq!(grid.pops, vec![1 as Pop]);
         assert_eq!(grid.neighbors, vec![vec![0 as usize; 0]]);
         assert_eq!(grid.edges_start, vec![0]);
         assert_eq!(grid.total_pop, 1);
@@ -261,7 +365,7 @@ mod tests {
             grid.edges,
             vec![Edge(0, 1), Edge(0, 2), Edge(1, 3), Edge(2, 3)]
         );
-        assert_eq!(grid.pops, vec![1 as u32; 4]);
+        assert_eq!(grid.pops, vec![1 as Pop; 4]);
         assert_eq!(
             grid.neighbors,
             vec![vec![1, 2], vec![0, 3], vec![0, 3], vec![1, 2]]
@@ -291,7 +395,7 @@ mod tests {
                 Edge(4, 5)
             ]
         );
-        assert_eq!(grid.pops, vec![1 as u32; 6]);
+        assert_eq!(grid.pops, vec![1 as Pop; 6]);
         assert_eq!(
             grid.neighbors,
             vec![
@@ -308,6 +412,15 @@ mod tests {
         assert_eq!(grid.attr.len(), 0);
     }
 
+    #[test]
+    fn edge_index_rect_grid_3x2() {
+        let grid = Graph::rect_grid(3, 2);
+        assert_eq!(grid.edge_index(0, 2), Some(1));
+        assert_eq!(grid.edge_index(5, 3), Some(5));
+        assert_eq!(grid.edge_index(5, 4), Some(6));
+        assert_eq!(grid.edge_index(0, 3), None);
+    }
+
     #[test]
     fn from_edge_list_3x2() {
         // The same graph used in `test_rect_grid_3x2`, but as a shuffled edge list.
@@ -355,6 +468,25 @@ mod tests {
         assert_eq!(grid.attr.len(), 0);
     }
 
+    #[test]
+    fn from_edge_list_with_pops_disconnected() {
+        let grid = Graph::from_edge_list_with_pops("1 2\n3 4", vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(grid.edges, vec![Edge(0, 1), Edge(2, 3)]);
+        assert_eq!(grid.pops, vec![1, 2, 3, 4]);
+        assert_eq!(grid.total_pop, 10);
+    }
+
+    #[test]
+    fn from_edge_list_with_pops_length_mismatch() {
+        assert_eq!(
+            Graph::from_edge_list_with_pops("1 2\n2 3", vec![1, 2]).unwrap_err(),
+            GraphError::ErrNodeLengthMismatch {
+                edge_list_nodes: 3,
+                pop_list_nodes: 2
+            }
+        );
+    }
+
     #[test]
     fn from_edge_list_duplicate_edge() {
         assert_eq!(
@@ -421,4 +553,13 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn from_edge_list_population_overflow() {
+        let big = Pop::MAX.to_string();
+        assert_eq!(
+            Graph::from_edge_list("1 2", &format!("{} 1", big)).unwrap_err(),
+            GraphError::ErrPopulationOverflow { bits: Pop::BITS }
+        );
+    }
 }
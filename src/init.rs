@@ -1,10 +1,63 @@
 //! Utility functions for loading graph and partition data.
-use crate::graph::{Edge, Graph};
-use crate::partition::Partition;
+mod streaming;
+
+use crate::attr::AttrColumnBuilder;
+use crate::graph::{Graph, GraphError, Pop};
+use crate::partition::{Partition, PartitionError};
+use csv::StringRecord;
+use serde_json::Error as SerdeError;
 use serde_json::Result as SerdeResult;
 use serde_json::Value;
+use snafu::prelude::*;
 use std::collections::HashMap;
 use std::fs;
+use std::io::{self, Read};
+use std::str::FromStr;
+use streaming::stream_networkx;
+
+/// Errors raised while loading graph and partition data from
+/// an edge list and a CSV of node attributes.
+#[derive(Debug, PartialEq, Snafu)]
+pub enum InitError {
+    #[snafu(display("Could not read {path}: {message}"))]
+    ErrRead { path: String, message: String },
+    #[snafu(display("Could not parse node CSV at row {row}: {message}"))]
+    ErrCsvRow { row: usize, message: String },
+    #[snafu(display("Column {column} not found in node CSV header"))]
+    ErrMissingColumn { column: String },
+    #[snafu(display("Could not parse value {value} in column {column} at row {row}"))]
+    ErrParseValue {
+        column: String,
+        row: usize,
+        value: String,
+    },
+    #[snafu(display("Invalid graph: {source}"))]
+    ErrGraph { source: GraphError },
+    #[snafu(display("Invalid seed partition: {source}"))]
+    ErrPartition { source: PartitionError },
+    #[snafu(display("Non-numeric value {value} in sum column {column} at node {node}"))]
+    ErrNonNumericColumn {
+        column: String,
+        node: String,
+        value: String,
+    },
+}
+
+/// Checks that the attribute columns `columns` of `graph` are numeric
+/// (so that they can be summed over districts). Columns that were not
+/// loaded are ignored.
+pub fn check_sum_columns(graph: &Graph, columns: &[String]) -> Result<(), InitError> {
+    for column in columns.iter() {
+        if let Some((node, value)) = graph.attr.get(column).and_then(|v| v.first_non_numeric()) {
+            return Err(InitError::ErrNonNumericColumn {
+                column: column.clone(),
+                node: graph.node_id(node),
+                value: value.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
 
 /// Loads graph and partition data in the NetworkX `adjacency_data` format
 /// used by [GerryChain](https://github.com/mggg/gerrychain). Returns a
@@ -19,125 +72,396 @@ use std::fs;
 /// * `assignment_col` - A column in the graph JSON corresponding to a
 ///    a seed partition. This column should be integer-valued and 1-indexed.
 /// * `columns` - The metadata columns to sum over (per district).
+/// * `edge_columns` - The numeric edge columns to load (e.g. shared
+///   boundary lengths).
 pub fn from_networkx(
     path: &str,
     pop_col: &str,
     assignment_col: &str,
     columns: Vec<String>,
+    edge_columns: Vec<String>,
 ) -> SerdeResult<(Graph, Partition)> {
-    let (graph, data) = match graph_from_networkx(path, pop_col, columns) {
-        Ok(v) => v,
-        Err(e) => return Err(e),
-    };
-
-    let raw_nodes = data["nodes"].as_array().unwrap();
-    let assignments: Vec<u32> = raw_nodes
-        .iter()
-        .enumerate()
-        .map(|(i, node)| match &node[assignment_col] {
-            serde_json::Value::Number(num) => num.as_u64().unwrap() as u32,
-            serde_json::Value::String(ref s) => s.parse::<u32>().unwrap(),
-            _ => panic!(
-                "{}{}{}",
-                "Unexpected entry type in assignment column. ",
-                format!(
-                    "Found {:?} at index {:?} in column {:?}.",
-                    node[assignment_col], i, assignment_col
-                ),
-                "Please make sure that all entries can be interpreted as positive numbers."
-            ),
-        })
-        .collect();
+    let file = fs::File::open(path).map_err(SerdeError::io)?;
+    let (graph, assignments) = stream_networkx(
+        io::BufReader::new(file),
+        pop_col,
+        Some(assignment_col),
+        &columns,
+        &edge_columns,
+    )?;
     let partition = Partition::from_assignments(&graph, &assignments).unwrap();
     return Ok((graph, partition));
 }
 
-/// Loads graph data in the NetworkX `adjacency_data` format
-/// used by [GerryChain](https://github.com/mggg/gerrychain). Returns a
-/// [serde_json::Result] containing a [graph::Graph] and the raw
-/// graph JSON tree upon a successful load.
+/// Loads graph data in the NetworkX `adjacency_data` or `node_link_data`
+/// format without materializing the full JSON tree. Only the population
+/// column and `columns` are kept from each node, which makes this loader
+/// suitable for very large (e.g. census block) graphs.
 ///
 /// # Arguments
 ///
 /// * `path` - the path of the graph JSON file.
 /// * `pop_col` - The column in the graph JSON corresponding to total node
-///    population. This column should be integer-valued.
+///   population. This column should be integer-valued.
 /// * `columns` - The metadata columns to sum over (per district).
+/// * `edge_columns` - The numeric edge columns to load (e.g. shared
+///   boundary lengths).
+pub fn stream_graph_from_networkx(
+    path: &str,
+    pop_col: &str,
+    columns: Vec<String>,
+    edge_columns: Vec<String>,
+) -> SerdeResult<Graph> {
+    let file = fs::File::open(path).map_err(SerdeError::io)?;
+    let (graph, _) = stream_networkx(
+        io::BufReader::new(file),
+        pop_col,
+        None,
+        &columns,
+        &edge_columns,
+    )?;
+    Ok(graph)
+}
+
+/// Loads graph data in the NetworkX `adjacency_data` or `node_link_data`
+/// format. Returns a [serde_json::Result] containing a [graph::Graph] and
+/// the raw graph JSON tree upon a successful load.
+///
+/// The graph is loaded with [stream_graph_from_networkx]; the raw JSON
+/// tree is parsed separately and held in memory in full.
+///
+/// # Arguments
+///
+/// * `path` - the path of the graph JSON file.
+/// * `pop_col` - The column in the graph JSON corresponding to total node
+///   population. This column should be integer-valued.
+/// * `columns` - The metadata columns to sum over (per district).
+/// * `edge_columns` - The numeric edge columns to load (e.g. shared
+///   boundary lengths).
+#[deprecated(note = "use stream_graph_from_networkx, which does not keep the raw JSON tree")]
 pub fn graph_from_networkx(
     path: &str,
     pop_col: &str,
     columns: Vec<String>,
+    edge_columns: Vec<String>,
 ) -> SerdeResult<(Graph, Value)> {
-    // TODO: should load from a generic buffer.
-    let raw = fs::read_to_string(path).expect("Could not load graph");
-    let data: Value = serde_json::from_str(&raw)?;
-
-    let raw_nodes = data["nodes"].as_array().unwrap();
-    let raw_adj = data["adjacency"].as_array().unwrap();
-    let num_nodes = raw_nodes.len();
-    let mut pops = Vec::<u32>::with_capacity(num_nodes);
-    let mut neighbors = Vec::<Vec<usize>>::with_capacity(num_nodes);
-    let mut edges = Vec::<Edge>::new();
-    let mut edges_start = vec![0 as usize; num_nodes];
+    let graph = stream_graph_from_networkx(path, pop_col, columns, edge_columns)?;
+    let file = fs::File::open(path).map_err(SerdeError::io)?;
+    let data: Value = serde_json::from_reader(io::BufReader::new(file))?;
+    Ok((graph, data))
+}
+
+/// Loads graph and partition data from a newline-delimited edge list
+/// (see [Graph::from_edge_list]) and a CSV of node attributes with a
+/// header row. The CSV has one row per node, in node index order.
+///
+/// # Arguments
+///
+/// * `edge_list_path` - the path of the edge list file.
+/// * `node_csv_path` - the path of the node attribute CSV file.
+/// * `pop_col` - The column in the CSV corresponding to total node
+///   population. This column should be integer-valued.
+/// * `assignment_col` - A column in the CSV corresponding to a
+///   a seed partition. This column should be integer-valued and 1-indexed.
+/// * `columns` - The attribute columns to load (e.g. for per-district sums
+///   or region-aware ReCom).
+pub fn from_edge_list_csv(
+    edge_list_path: &str,
+    node_csv_path: &str,
+    pop_col: &str,
+    assignment_col: &str,
+    columns: Vec<String>,
+) -> Result<(Graph, Partition), InitError> {
+    let edge_list = fs::read_to_string(edge_list_path).map_err(|e| InitError::ErrRead {
+        path: edge_list_path.to_string(),
+        message: e.to_string(),
+    })?;
+    let node_csv = fs::File::open(node_csv_path).map_err(|e| InitError::ErrRead {
+        path: node_csv_path.to_string(),
+        message: e.to_string(),
+    })?;
+    load_edge_list_csv(&edge_list, node_csv, pop_col, assignment_col, columns)
+}
+
+/// Parses a (numeric) value from column `column` (at `index`) of a node CSV record.
+fn parse_csv_value<T: FromStr>(
+    record: &StringRecord,
+    row: usize,
+    column: &str,
+    index: usize,
+) -> Result<T, InitError> {
+    let value = record.get(index).unwrap_or_default();
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| InitError::ErrParseValue {
+            column: column.to_string(),
+            row,
+            value: value.to_string(),
+        })
+}
+
+/// Loads graph and partition data from edge list text and a node attribute
+/// CSV reader (see [from_edge_list_csv]).
+fn load_edge_list_csv(
+    edge_list: &str,
+    node_csv: impl Read,
+    pop_col: &str,
+    assignment_col: &str,
+    columns: Vec<String>,
+) -> Result<(Graph, Partition), InitError> {
+    let mut reader = csv::Reader::from_reader(node_csv);
+    let header = reader
+        .headers()
+        .map_err(|e| InitError::ErrCsvRow {
+            row: 0,
+            message: e.to_string(),
+        })?
+        .clone();
+    let column_index = |column: &str| match header.iter().position(|h| h == column) {
+        Some(index) => Ok(index),
+        None => Err(InitError::ErrMissingColumn {
+            column: column.to_string(),
+        }),
+    };
+    let pop_index = column_index(pop_col)?;
+    let assignment_index = column_index(assignment_col)?;
+    let mut attr_indices = Vec::<(String, usize)>::with_capacity(columns.len());
+    for col in columns.into_iter() {
+        let index = column_index(&col)?;
+        attr_indices.push((col, index));
+    }
+
+    let mut pops = Vec::<Pop>::new();
+    let mut assignments = Vec::<u32>::new();
     let mut attr = HashMap::new();
-    for col in columns.to_vec().into_iter() {
-        attr.insert(col, Vec::<String>::with_capacity(num_nodes));
+    for (col, _) in attr_indices.iter() {
+        attr.insert(col.clone(), AttrColumnBuilder::default());
+    }
+    for (row, record) in reader.records().enumerate() {
+        // Rows are 1-indexed after the header.
+        let row = row + 1;
+        let record = record.map_err(|e| InitError::ErrCsvRow {
+            row,
+            message: e.to_string(),
+        })?;
+        pops.push(parse_csv_value(&record, row, pop_col, pop_index)?);
+        assignments.push(parse_csv_value(
+            &record,
+            row,
+            assignment_col,
+            assignment_index,
+        )?);
+        for (col, index) in attr_indices.iter() {
+            let value = record.get(*index).unwrap_or_default();
+            attr.get_mut(col).unwrap().push_str(value);
+        }
     }
 
-    for (index, (node, adj)) in raw_nodes.iter().zip(raw_adj.iter()).enumerate() {
-        edges_start[index] = edges.len();
-        let node_neighbors: Vec<usize> = adj
-            .as_array()
-            .unwrap()
-            .into_iter()
-            .map(|n| n.as_object().unwrap()["id"].as_u64().unwrap() as usize)
-            .collect();
-        for col in columns.iter() {
-            if let Some(data) = attr.get_mut(col) {
-                match node.get(col) {
-                    Some(value) => data.push(value.to_string()),
-                    None => {
-                        eprintln!(
-                            "Failed to unwrap at column '{}', value {:?}",
-                            col, node[col]
-                        );
-                        panic!("Unexpected None while unwrapping.");
-                    }
-                }
+    let mut graph = Graph::from_edge_list_with_pops(edge_list, pops)
+        .map_err(|source| InitError::ErrGraph { source })?;
+    graph.attr = attr
+        .into_iter()
+        .map(|(col, data)| (col, data.finish()))
+        .collect();
+    let partition = Partition::from_assignments(&graph, &assignments)
+        .map_err(|source| InitError::ErrPartition { source })?;
+    Ok((graph, partition))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::graph::Edge;
+    use serde_json::{json, Value};
+
+    /// Loads a graph from NetworkX JSON data with the streaming loader.
+    fn load_networkx(
+        data: Value,
+        columns: Vec<String>,
+        edge_columns: Vec<String>,
+    ) -> SerdeResult<Graph> {
+        let raw = data.to_string();
+        let (graph, _) =
+            stream_networkx(raw.as_bytes(), "TOTPOP", None, &columns, &edge_columns)?;
+        Ok(graph)
+    }
+
+    const EDGE_LIST: &str = "1 2\n1 3\n2 4\n3 4";
+    const NODE_CSV: &str = "GEOID,TOTPOP,district,county\n\
+                            a,10,1,x\n\
+                            b,20,1,y\n\
+                            c,30,2,x\n\
+                            d,40,2,y\n";
+
+    #[test]
+    fn edge_list_csv_2x2() {
+        let (graph, partition) = load_edge_list_csv(
+            EDGE_LIST,
+            NODE_CSV.as_bytes(),
+            "TOTPOP",
+            "district",
+            vec!["county".to_string()],
+        )
+        .unwrap();
+        assert_eq!(graph.pops, vec![10, 20, 30, 40]);
+        assert_eq!(graph.total_pop, 100);
+        assert_eq!(
+            graph.attr["county"],
+            AttrColumn::parse(["x", "y", "x", "y"])
+        );
+        assert_eq!(partition.assignments, vec![0, 0, 1, 1]);
+        assert_eq!(partition.dist_pops, vec![30, 70]);
+    }
+
+    #[test]
+    fn edge_list_csv_missing_column() {
+        assert_eq!(
+            load_edge_list_csv(EDGE_LIST, NODE_CSV.as_bytes(), "POP", "district", vec![])
+                .unwrap_err(),
+            InitError::ErrMissingColumn {
+                column: "POP".to_string()
             }
-        }
-        let new_pop = match &node[pop_col] {
-            serde_json::Value::Number(num) => num.as_u64().unwrap() as u32,
-            serde_json::Value::String(ref s) => s.parse::<u32>().unwrap(),
-            _ => panic!(
-                "{}{}{}",
-                "Unexpected entry type in population column. ",
-                format!(
-                    "Found {:?} at index {:?} in column {:?}.",
-                    node[pop_col], index, pop_col
-                ),
-                "Please make sure that all entries can be interpreted as positive numbers."
-            ),
-        };
-        pops.push(new_pop);
-        neighbors.push(node_neighbors.clone());
-
-        for neighbor in &node_neighbors {
-            if neighbor > &index {
-                let edge = Edge(index, *neighbor);
-                edges.push(edge.clone());
+        );
+    }
+
+    #[test]
+    fn edge_list_csv_invalid_population() {
+        let node_csv = "TOTPOP,district\n10,1\nabc,1\n30,2\n40,2\n";
+        assert_eq!(
+            load_edge_list_csv(EDGE_LIST, node_csv.as_bytes(), "TOTPOP", "district", vec![])
+                .unwrap_err(),
+            InitError::ErrParseValue {
+                column: "TOTPOP".to_string(),
+                row: 2,
+                value: "abc".to_string()
             }
-        }
+        );
     }
 
-    let total_pop = pops.iter().sum();
-    let graph = Graph {
-        pops: pops,
-        neighbors: neighbors,
-        edges: edges.clone(),
-        edges_start: edges_start.clone(),
-        total_pop: total_pop,
-        attr: attr,
-    };
-    return Ok((graph, data));
+    #[test]
+    fn edge_list_csv_row_count_mismatch() {
+        let node_csv = "TOTPOP,district\n10,1\n20,2\n";
+        assert_eq!(
+            load_edge_list_csv(EDGE_LIST, node_csv.as_bytes(), "TOTPOP", "district", vec![])
+                .unwrap_err(),
+            InitError::ErrGraph {
+                source: GraphError::ErrNodeLengthMismatch {
+                    edge_list_nodes: 4,
+                    pop_list_nodes: 2
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn networkx_adjacency_positional_ids() {
+        let data = json!({
+            "nodes": [
+                {"id": 0, "TOTPOP": 1},
+                {"id": 1, "TOTPOP": 2},
+                {"id": 2, "TOTPOP": "3"}
+            ],
+            "adjacency": [[{"id": 1}], [{"id": 0}, {"id": 2}], [{"id": 1}]]
+        });
+        let graph = load_networkx(data, vec![], vec![]).unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 1), Edge(1, 2)]);
+        assert_eq!(graph.pops, vec![1, 2, 3]);
+        assert!(graph.node_ids.is_empty());
+        assert_eq!(graph.node_id(2), "2");
+        assert_eq!(graph.node_index("2"), Some(2));
+        assert_eq!(graph.node_index("3"), None);
+    }
+
+    #[test]
+    fn networkx_node_link_string_ids() {
+        let data = json!({
+            "nodes": [
+                {"id": "51001", "TOTPOP": 1},
+                {"id": "51003", "TOTPOP": 2},
+                {"id": "51005", "TOTPOP": 3}
+            ],
+            "links": [
+                {"source": "51005", "target": "51001"},
+                {"source": "51001", "target": "51003"}
+            ]
+        });
+        let graph = load_networkx(data, vec![], vec![]).unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 1), Edge(0, 2)]);
+        assert_eq!(graph.neighbors, vec![vec![1, 2], vec![0], vec![0]]);
+        assert_eq!(graph.node_ids, vec!["51001", "51003", "51005"]);
+        assert_eq!(graph.node_id(1), "51003");
+        assert_eq!(graph.node_index("51005"), Some(2));
+        assert_eq!(graph.node_index("1"), None);
+    }
+
+    #[test]
+    fn networkx_node_link_edges_key() {
+        let data = json!({
+            "nodes": [{"id": 10, "TOTPOP": 1}, {"id": 20, "TOTPOP": 2}],
+            "edges": [{"source": 20, "target": 10}]
+        });
+        let graph = load_networkx(data, vec![], vec![]).unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 1)]);
+        assert_eq!(graph.node_ids, vec!["10", "20"]);
+    }
+
+    #[test]
+    fn networkx_node_link_unknown_id() {
+        let data = json!({
+            "nodes": [{"id": "a", "TOTPOP": 1}, {"id": "b", "TOTPOP": 2}],
+            "links": [{"source": "a", "target": "c"}]
+        });
+        let err = load_networkx(data, vec![], vec![]).unwrap_err();
+        assert!(err.to_string().contains("Unknown node ID \"c\""));
+    }
+
+    #[test]
+    fn networkx_edge_columns() {
+        let data = json!({
+            "nodes": [{"id": 0, "TOTPOP": 1}, {"id": 1, "TOTPOP": 2}, {"id": 2, "TOTPOP": 3}],
+            "adjacency": [
+                [{"id": 2, "kind": "water", "weight": 2}, {"id": 1, "kind": "rook", "weight": 1}],
+                [{"id": 0, "kind": "rook", "weight": 1}],
+                [{"id": 0, "kind": "water", "weight": 2}]
+            ]
+        });
+        let cols = vec!["kind".to_string(), "weight".to_string()];
+        let graph = load_networkx(data, vec![], cols).unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 2), Edge(0, 1)]);
+        assert_eq!(
+            graph.edge_attr["kind"],
+            AttrColumn::parse(["water", "rook"])
+        );
+        assert_eq!(graph.edge_attr["weight"], AttrColumn::Int(vec![2, 1]));
+    }
+
+    #[test]
+    fn networkx_typed_columns() {
+        let data = json!({
+            "nodes": [
+                {"id": "a", "TOTPOP": 1, "votes": 10, "share": 0.5, "county": "x"},
+                {"id": "b", "TOTPOP": 2, "votes": "20", "share": 1, "county": "y"}
+            ],
+            "links": [{"source": "a", "target": "b"}]
+        });
+        let columns = vec![
+            "votes".to_string(),
+            "share".to_string(),
+            "county".to_string(),
+        ];
+        let graph = load_networkx(data, columns.clone(), vec![]).unwrap();
+        assert_eq!(graph.attr["votes"], AttrColumn::Int(vec![10, 20]));
+        assert_eq!(graph.attr["share"], AttrColumn::Float(vec![0.5, 1.0]));
+        assert!(check_sum_columns(&graph, &columns[..2]).is_ok());
+        assert_eq!(
+            check_sum_columns(&graph, &columns).unwrap_err(),
+            InitError::ErrNonNumericColumn {
+                column: "county".to_string(),
+                node: "a".to_string(),
+                value: "x".to_string(),
+            }
+        );
+    }
 }
@@ -0,0 +1,641 @@
+//! A streaming NetworkX JSON deserializer that builds a [Graph] directly.
+//!
+//! This loader never materializes the full JSON tree: node records are
+//! reduced to the population column, the (optional) assignment column, and
+//! the requested attribute columns as they are read, and all other keys are
+//! skipped.
+use crate::attr::AttrColumnBuilder;
+use crate::graph::{total_pop, Edge, Graph, Pop};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Result as SerdeResult;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+/// A NetworkX node ID. Integer IDs are stored inline so that
+/// buffered adjacencies of large integer-labeled graphs stay compact.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum NodeKey {
+    Int(i64),
+    Str(Box<str>),
+}
+
+impl fmt::Display for NodeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeKey::Int(id) => write!(f, "{}", id),
+            NodeKey::Str(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<NodeKey, D::Error> {
+        struct NodeKeyVisitor;
+
+        impl<'de> Visitor<'de> for NodeKeyVisitor {
+            type Value = NodeKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a node ID")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<NodeKey, E> {
+                Ok(NodeKey::Int(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<NodeKey, E> {
+                match i64::try_from(v) {
+                    Ok(v) => Ok(NodeKey::Int(v)),
+                    Err(_) => Ok(NodeKey::Str(v.to_string().into())),
+                }
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<NodeKey, E> {
+                Ok(NodeKey::Str(Value::from(v).to_string().into()))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<NodeKey, E> {
+                Ok(NodeKey::Str(v.into()))
+            }
+        }
+
+        deserializer.deserialize_any(NodeKeyVisitor)
+    }
+}
+
+/// An entry in a node's adjacency list (`adjacency_data` format).
+struct AdjacencyEntry {
+    id: NodeKey,
+    /// The values of the requested edge columns.
+    values: Box<[Value]>,
+}
+
+/// An edge (`node_link_data` format).
+struct Link {
+    source: NodeKey,
+    target: NodeKey,
+    /// The values of the requested edge columns.
+    values: Box<[Value]>,
+}
+
+/// An adjacency entry or link, as read from the JSON object.
+#[derive(Default)]
+struct EdgeRecord {
+    id: Option<NodeKey>,
+    source: Option<NodeKey>,
+    target: Option<NodeKey>,
+    values: Box<[Value]>,
+}
+
+/// The columns to keep from each adjacency entry or link.
+#[derive(Clone, Copy)]
+struct EdgeColumns<'a> {
+    columns: &'a [String],
+}
+
+/// The columns to keep from each node record.
+struct NodeColumns<'a> {
+    pop_col: &'a str,
+    assignment_col: Option<&'a str>,
+    columns: &'a [String],
+    edge_columns: EdgeColumns<'a>,
+}
+
+/// Node data accumulated while streaming the `nodes` array.
+#[derive(Default)]
+struct Nodes {
+    ids: Vec<NodeKey>,
+    pops: Vec<Pop>,
+    assignments: Vec<u32>,
+    attr: HashMap<String, AttrColumnBuilder>,
+}
+
+/// Graph data accumulated while streaming the top-level object.
+struct StreamedGraph {
+    nodes: Nodes,
+    adjacency: Option<Vec<Vec<AdjacencyEntry>>>,
+    links: Option<Vec<Link>>,
+}
+
+/// Parses a non-negative integer (e.g. a population or an assignment)
+/// from a JSON number or string.
+fn parse_uint<T, E>(value: &Value, col: &str, index: usize) -> Result<T, E>
+where
+    T: TryFrom<u64> + FromStr,
+    E: de::Error,
+{
+    let parsed = match value {
+        Value::Number(num) => num.as_u64().and_then(|v| T::try_from(v).ok()),
+        Value::String(s) => s.parse::<T>().ok(),
+        _ => None,
+    };
+    parsed.ok_or_else(|| {
+        E::custom(format!(
+            "Found {} at index {} in column {:?}; expected a non-negative integer",
+            value, index, col
+        ))
+    })
+}
+
+/// Deserializes a sequence of elements with a (copyable) seed.
+#[derive(Clone, Copy)]
+struct SeqSeed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de> + Copy> DeserializeSeed<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de> + Copy> Visitor<'de> for SeqSeed<S> {
+    type Value = Vec<S::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element_seed(self.0)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for EdgeColumns<'a> {
+    type Value = EdgeRecord;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<EdgeRecord, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for EdgeColumns<'a> {
+    type Value = EdgeRecord;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an adjacency entry or link object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<EdgeRecord, A::Error> {
+        let mut record = EdgeRecord::default();
+        let mut values = vec![None; self.columns.len()];
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "id" => record.id = Some(map.next_value()?),
+                "source" => record.source = Some(map.next_value()?),
+                "target" => record.target = Some(map.next_value()?),
+                _ => match self.columns.iter().position(|col| *col == key) {
+                    Some(idx) => values[idx] = Some(map.next_value()?),
+                    None => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                },
+            }
+        }
+        record.values = values
+            .into_iter()
+            .zip(self.columns.iter())
+            .map(|(value, col)| {
+                value.ok_or_else(|| de::Error::custom(format!("Missing edge column {:?}", col)))
+            })
+            .collect::<Result<_, A::Error>>()?;
+        Ok(record)
+    }
+}
+
+/// Deserializes an [AdjacencyEntry] (keeping the requested edge columns).
+#[derive(Clone, Copy)]
+struct AdjacencyEntrySeed<'a>(EdgeColumns<'a>);
+
+impl<'de, 'a> DeserializeSeed<'de> for AdjacencyEntrySeed<'a> {
+    type Value = AdjacencyEntry;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<AdjacencyEntry, D::Error> {
+        let record = self.0.deserialize(deserializer)?;
+        Ok(AdjacencyEntry {
+            id: record.id.ok_or_else(|| de::Error::missing_field("id"))?,
+            values: record.values,
+        })
+    }
+}
+
+/// Deserializes a [Link] (keeping the requested edge columns).
+#[derive(Clone, Copy)]
+struct LinkSeed<'a>(EdgeColumns<'a>);
+
+impl<'de, 'a> DeserializeSeed<'de> for LinkSeed<'a> {
+    type Value = Link;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Link, D::Error> {
+        let record = self.0.deserialize(deserializer)?;
+        Ok(Link {
+            source: record
+                .source
+                .ok_or_else(|| de::Error::missing_field("source"))?,
+            target: record
+                .target
+                .ok_or_else(|| de::Error::missing_field("target"))?,
+            values: record.values,
+        })
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for &NodeColumns<'a> {
+    type Value = Nodes;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Nodes, D::Error> {
+        deserializer.deserialize_seq(NodesVisitor { columns: self })
+    }
+}
+
+struct NodesVisitor<'a, 'b> {
+    columns: &'b NodeColumns<'a>,
+}
+
+impl<'de, 'a, 'b> Visitor<'de> for NodesVisitor<'a, 'b> {
+    type Value = Nodes;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Nodes, A::Error> {
+        let mut nodes = Nodes::default();
+        for col in self.columns.columns.iter() {
+            nodes.attr.insert(col.clone(), AttrColumnBuilder::default());
+        }
+        while seq
+            .next_element_seed(NodeSeed {
+                columns: self.columns,
+                nodes: &mut nodes,
+            })?
+            .is_some()
+        {}
+        Ok(nodes)
+    }
+}
+
+/// Streams a single node record into the accumulated [Nodes].
+struct NodeSeed<'a, 'b> {
+    columns: &'b NodeColumns<'a>,
+    nodes: &'b mut Nodes,
+}
+
+impl<'de, 'a, 'b> DeserializeSeed<'de> for NodeSeed<'a, 'b> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, 'b> Visitor<'de> for NodeSeed<'a, 'b> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a node object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let index = self.nodes.pops.len();
+        let mut id = None;
+        let mut pop = None;
+        let mut assignment = None;
+        let mut values = HashMap::<String, Value>::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let is_pop = key == self.columns.pop_col;
+            let is_assignment = self.columns.assignment_col == Some(key.as_str());
+            let is_column = self.columns.columns.contains(&key);
+            if key == "id" && !is_pop && !is_assignment && !is_column {
+                id = Some(map.next_value::<NodeKey>()?);
+            } else if is_pop || is_assignment || is_column {
+                let value = map.next_value::<Value>()?;
+                if key == "id" {
+                    id = Some(NodeKey::deserialize(&value).map_err(de::Error::custom)?);
+                }
+                if is_pop {
+                    pop = Some(parse_uint(&value, &key, index)?);
+                }
+                if is_assignment {
+                    assignment = Some(parse_uint(&value, &key, index)?);
+                }
+                if is_column {
+                    values.insert(key, value);
+                }
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        let missing =
+            |col: &str| de::Error::custom(format!("Missing column {:?} at index {}", col, index));
+        self.nodes
+            .ids
+            .push(id.unwrap_or(NodeKey::Int(index as i64)));
+        self.nodes
+            .pops
+            .push(pop.ok_or_else(|| missing(self.columns.pop_col))?);
+        if let Some(assignment_col) = self.columns.assignment_col {
+            self.nodes
+                .assignments
+                .push(assignment.ok_or_else(|| missing(assignment_col))?);
+        }
+        for col in self.columns.columns.iter() {
+            let value = values.get(col).ok_or_else(|| missing(col))?;
+            self.nodes.attr.get_mut(col).unwrap().push_json(value);
+        }
+        Ok(())
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for NodeColumns<'a> {
+    type Value = StreamedGraph;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<StreamedGraph, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for NodeColumns<'a> {
+    type Value = StreamedGraph;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a NetworkX graph object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<StreamedGraph, A::Error> {
+        let mut nodes = None;
+        let mut adjacency = None;
+        let mut links = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "nodes" => nodes = Some(map.next_value_seed(&self)?),
+                "adjacency" => {
+                    let seed = SeqSeed(SeqSeed(AdjacencyEntrySeed(self.edge_columns)));
+                    adjacency = Some(map.next_value_seed(seed)?);
+                }
+                "links" | "edges" => {
+                    links = Some(map.next_value_seed(SeqSeed(LinkSeed(self.edge_columns)))?)
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        match nodes {
+            Some(nodes) => Ok(StreamedGraph {
+                nodes,
+                adjacency,
+                links,
+            }),
+            None => Err(de::Error::missing_field("nodes")),
+        }
+    }
+}
+
+/// Streams a NetworkX JSON graph (in either the `adjacency_data` or the
+/// `node_link_data` format) from `reader`, keeping only the population
+/// column, the assignment column (if any), the node columns `columns`,
+/// and the edge columns `edge_columns`.
+///
+/// Returns the graph and the raw (1-indexed) assignments, which are empty
+/// if no assignment column is given.
+pub(super) fn stream_networkx(
+    reader: impl Read,
+    pop_col: &str,
+    assignment_col: Option<&str>,
+    columns: &[String],
+    edge_columns: &[String],
+) -> SerdeResult<(Graph, Vec<u32>)> {
+    let node_columns = NodeColumns {
+        pop_col,
+        assignment_col,
+        columns,
+        edge_columns: EdgeColumns {
+            columns: edge_columns,
+        },
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let streamed = node_columns.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    let Nodes {
+        ids,
+        pops,
+        assignments,
+        attr,
+    } = streamed.nodes;
+    let num_nodes = ids.len();
+    let mut id_to_index = HashMap::<&NodeKey, usize>::with_capacity(num_nodes);
+    for (index, id) in ids.iter().enumerate() {
+        if id_to_index.insert(id, index).is_some() {
+            return Err(de::Error::custom(format!("Duplicate node ID \"{}\"", id)));
+        }
+    }
+    let node_index = |id: &NodeKey| match id_to_index.get(id) {
+        Some(index) => Ok(*index),
+        None => Err(de::Error::custom(format!("Unknown node ID \"{}\"", id))),
+    };
+
+    let mut neighbors = vec![Vec::<usize>::new(); num_nodes];
+    // Edge column values, keyed by edge (only kept if edge columns are requested).
+    let mut edge_values = HashMap::<Edge, Box<[Value]>>::new();
+    let mut keep_values = |a: usize, b: usize, values: Box<[Value]>| {
+        if !edge_columns.is_empty() {
+            edge_values
+                .entry(Edge(a.min(b), a.max(b)))
+                .or_insert(values);
+        }
+    };
+    match (streamed.adjacency, streamed.links) {
+        (Some(adjacency), _) => {
+            for (index, (node_neighbors, adj)) in neighbors.iter_mut().zip(adjacency).enumerate() {
+                for neighbor in adj.into_iter() {
+                    let neighbor_index = node_index(&neighbor.id)?;
+                    node_neighbors.push(neighbor_index);
+                    keep_values(index, neighbor_index, neighbor.values);
+                }
+            }
+        }
+        (None, Some(links)) => {
+            for link in links.into_iter() {
+                let source = node_index(&link.source)?;
+                let target = node_index(&link.target)?;
+                if source != target && !neighbors[source].contains(&target) {
+                    neighbors[source].push(target);
+                    neighbors[target].push(source);
+                    keep_values(source, target, link.values);
+                }
+            }
+            for node_neighbors in neighbors.iter_mut() {
+                node_neighbors.sort_unstable();
+            }
+        }
+        (None, None) => {
+            return Err(de::Error::custom(
+                "Expected an \"adjacency\", \"links\", or \"edges\" key",
+            ))
+        }
+    }
+
+    let mut edges = Vec::<Edge>::new();
+    let mut edges_start = vec![0; num_nodes];
+    let mut edge_attr: HashMap<String, AttrColumnBuilder> = edge_columns
+        .iter()
+        .map(|col| (col.clone(), AttrColumnBuilder::default()))
+        .collect();
+    for (index, node_neighbors) in neighbors.iter().enumerate() {
+        edges_start[index] = edges.len();
+        for &neighbor in node_neighbors.iter() {
+            if neighbor > index {
+                let edge = Edge(index, neighbor);
+                if let Some(values) = edge_values.get(&edge) {
+                    for (col, value) in edge_columns.iter().zip(values.iter()) {
+                        edge_attr.get_mut(col).unwrap().push_json(value);
+                    }
+                }
+                edges.push(edge);
+            }
+        }
+    }
+
+    let positional = ids
+        .iter()
+        .enumerate()
+        .all(|(index, id)| *id == NodeKey::Int(index as i64));
+    let node_ids = match positional {
+        true => vec![],
+        false => ids.iter().map(|id| id.to_string()).collect(),
+    };
+
+    let graph = Graph {
+        total_pop: total_pop(&pops).map_err(de::Error::custom)?,
+        pops,
+        neighbors,
+        edges,
+        edges_start,
+        attr: attr
+            .into_iter()
+            .map(|(col, data)| (col, data.finish()))
+            .collect(),
+        edge_attr: edge_attr
+            .into_iter()
+            .map(|(col, data)| (col, data.finish()))
+            .collect(),
+        node_ids,
+    };
+    Ok((graph, assignments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+
+    #[test]
+    fn stream_adjacency_keeps_requested_columns() {
+        let raw = r#"{
+            "directed": false,
+            "graph": {"name": "path"},
+            "nodes": [
+                {"id": 0, "TOTPOP": 1, "CD": 1, "county": "x", "geometry": [1, 2]},
+                {"id": 1, "TOTPOP": "2", "CD": "2", "county": "y", "geometry": [3, 4]}
+            ],
+            "adjacency": [[{"id": 1, "shared_perim": 1.5}], [{"id": 0}]]
+        }"#;
+        let (graph, assignments) = stream_networkx(
+            raw.as_bytes(),
+            "TOTPOP",
+            Some("CD"),
+            &["county".to_string()],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 1)]);
+        assert_eq!(graph.pops, vec![1, 2]);
+        assert_eq!(graph.total_pop, 3);
+        assert_eq!(assignments, vec![1, 2]);
+        assert_eq!(graph.attr.len(), 1);
+        assert_eq!(graph.attr["county"], AttrColumn::parse(["x", "y"]));
+        assert!(graph.node_ids.is_empty());
+    }
+
+    #[test]
+    fn stream_node_link_string_ids() {
+        let raw = r#"{
+            "links": [{"source": "c", "target": "a"}, {"source": "a", "target": "b"}],
+            "nodes": [{"id": "a", "POP": 1}, {"id": "b", "POP": 2}, {"id": "c", "POP": 3}]
+        }"#;
+        let (graph, assignments) = stream_networkx(raw.as_bytes(), "POP", None, &[], &[]).unwrap();
+        assert_eq!(graph.edges, vec![Edge(0, 1), Edge(0, 2)]);
+        assert_eq!(graph.neighbors, vec![vec![1, 2], vec![0], vec![0]]);
+        assert_eq!(graph.node_ids, vec!["a", "b", "c"]);
+        assert!(assignments.is_empty());
+    }
+
+    #[test]
+    fn stream_missing_population() {
+        let raw = r#"{"nodes": [{"id": 0}], "adjacency": [[]]}"#;
+        let err = stream_networkx(raw.as_bytes(), "POP", None, &[], &[]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Missing column \"POP\" at index 0"));
+    }
+
+    #[test]
+    fn stream_edge_columns() {
+        let adjacency = r#"{
+            "nodes": [{"id": 0, "POP": 1}, {"id": 1, "POP": 2}, {"id": 2, "POP": 3}],
+            "adjacency": [
+                [
+                    {"id": 2, "shared_perim": 2.5, "kind": "queen"},
+                    {"id": 1, "shared_perim": "1", "kind": "rook"}
+                ],
+                [{"id": 0, "shared_perim": 1, "kind": "rook"}],
+                [{"id": 0, "shared_perim": 2.5, "kind": "queen"}]
+            ]
+        }"#;
+        let links = r#"{
+            "nodes": [{"id": 0, "POP": 1}, {"id": 1, "POP": 2}, {"id": 2, "POP": 3}],
+            "links": [
+                {"source": 2, "target": 0, "shared_perim": 2.5, "kind": "queen", "weight": 3},
+                {"source": 0, "target": 1, "shared_perim": 1, "kind": "rook"}
+            ]
+        }"#;
+        let cols = ["shared_perim".to_string(), "kind".to_string()];
+        for raw in [adjacency, links] {
+            let (graph, _) = stream_networkx(raw.as_bytes(), "POP", None, &[], &cols).unwrap();
+            let shared_perim = &graph.edge_attr["shared_perim"];
+            let kind = &graph.edge_attr["kind"];
+            assert_eq!(shared_perim.len(), graph.edges.len());
+            let (rook, queen) = (
+                graph.edge_index(0, 1).unwrap(),
+                graph.edge_index(0, 2).unwrap(),
+            );
+            assert_eq!(shared_perim.get_f64(rook), Some(1.0));
+            assert_eq!(shared_perim.get_f64(queen), Some(2.5));
+            assert_eq!(kind.value_string(rook), "rook");
+            assert_eq!(kind.value_string(queen), "queen");
+        }
+
+        let missing = r#"{
+            "nodes": [{"id": 0, "POP": 1}, {"id": 1, "POP": 1}],
+            "links": [{"source": 0, "target": 1}]
+        }"#;
+        let err = stream_networkx(missing.as_bytes(), "POP", None, &[], &cols).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Missing edge column \"shared_perim\""));
+    }
+}
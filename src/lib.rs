@@ -1,9 +1,13 @@
 //! Library definition for frcw.
+pub mod attr;
 mod buffers;
+pub mod cache;
+pub mod cli;
 pub mod config;
 pub mod graph;
 pub mod init;
 pub mod partition;
 pub mod recom;
+pub mod replay;
 mod spanning_tree;
 pub mod stats;
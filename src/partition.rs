@@ -2,7 +2,7 @@ use snafu::prelude::*;
 
 /// Data structures for partitionings (districting plans).
 use crate::buffers::SubgraphBuffer;
-use crate::graph::{Edge, Graph};
+use crate::graph::{Edge, Graph, Pop};
 use crate::recom::RecomProposal;
 
 #[derive(Debug, PartialEq, Snafu)]
@@ -37,17 +37,20 @@ pub struct Partition {
     /// This should be consistent with `assignments`.
     pub dist_nodes: Vec<Vec<usize>>,
     /// The population in each district.
-    pub dist_pops: Vec<u32>,
+    pub dist_pops: Vec<Pop>,
     /// The cut edges (that is, edges that connect nodes in different
-    /// districts) in the partitioning.
+    /// districts) in the partitioning, sorted by index.
     /// This should be consistent with `dist_nodes`.
-    /// Computed lazily.
+    /// Computed lazily (and then updated incrementally).
     cut_edges: Option<Vec<usize>>,
     /// A flattened district adjacency matrix. A district pair's entry
     /// is the number of cut edges between the pair; nonadjacency is
     /// represented by a cut edge count of 0.
-    /// Computed lazily.
+    /// Computed lazily (and then updated incrementally).
     dist_adj: Option<Vec<u32>>,
+    /// The districts changed by proposals since `cut_edges` and `dist_adj`
+    /// were last refreshed.
+    stale_dists: Vec<usize>,
 }
 
 impl Partition {
@@ -64,13 +67,93 @@ impl Partition {
         for &node in proposal.b_nodes.iter() {
             self.assignments[node] = proposal.b_label as u32;
         }
-        // Reset lazily computed derived properties.
-        self.cut_edges = None;
-        self.dist_adj = None;
+        // Mark lazily computed derived properties for refreshing.
+        if self.cut_edges.is_some() || self.dist_adj.is_some() {
+            for dist in [proposal.a_label, proposal.b_label] {
+                if !self.stale_dists.contains(&dist) {
+                    self.stale_dists.push(dist);
+                }
+            }
+        }
+    }
+
+    /// Refreshes the partition's cut edges and district adjacency matrix
+    /// (if they have been computed) after proposals have been applied.
+    ///
+    /// Nodes only move between the districts changed by proposals, so only
+    /// edges incident to the nodes in those districts can change.
+    fn refresh(&mut self, graph: &Graph) {
+        if self.stale_dists.is_empty() {
+            return;
+        }
+        let mut stale = vec![false; self.num_dists as usize];
+        for &dist in self.stale_dists.iter() {
+            stale[dist] = true;
+        }
+        // Collect the cut edges incident to nodes in the changed districts
+        // (as node pairs and district pairs). Edges between two changed
+        // districts are seen from both ends, so we only count them from
+        // their lower-indexed node.
+        let mut changed = vec![];
+        for &dist in self.stale_dists.iter() {
+            for &node in self.dist_nodes[dist].iter() {
+                for &neighbor in graph.neighbors[node].iter() {
+                    let neighbor_dist = self.assignments[neighbor] as usize;
+                    if neighbor_dist != dist && (!stale[neighbor_dist] || node < neighbor) {
+                        changed.push((node, neighbor, dist, neighbor_dist));
+                    }
+                }
+            }
+        }
+
+        if let Some(mut dist_adj) = self.dist_adj.take() {
+            let n = self.num_dists as usize;
+            for &dist in self.stale_dists.iter() {
+                for other in 0..n {
+                    dist_adj[dist * n + other] = 0;
+                    dist_adj[other * n + dist] = 0;
+                }
+            }
+            for &(_, _, dist_a, dist_b) in changed.iter() {
+                dist_adj[dist_a * n + dist_b] += 1;
+                dist_adj[dist_b * n + dist_a] += 1;
+            }
+            self.dist_adj = Some(dist_adj);
+        }
+
+        if let Some(cut_edges) = self.cut_edges.take() {
+            let mut added: Vec<usize> = changed
+                .iter()
+                .map(|&(node, neighbor, _, _)| graph.edge_index(node, neighbor).unwrap())
+                .collect();
+            added.sort_unstable();
+            let kept = cut_edges.iter().copied().filter(|&idx| {
+                let edge = &graph.edges[idx];
+                !stale[self.assignments[edge.0] as usize]
+                    && !stale[self.assignments[edge.1] as usize]
+            });
+            // Merge the (sorted) unchanged and changed cut edges.
+            let mut merged = Vec::with_capacity(cut_edges.len() + added.len());
+            let mut added = added.into_iter().peekable();
+            for idx in kept {
+                while let Some(&next) = added.peek() {
+                    if next > idx {
+                        break;
+                    }
+                    merged.push(next);
+                    added.next();
+                }
+                merged.push(idx);
+            }
+            merged.extend(added);
+            self.cut_edges = Some(merged);
+        }
+        self.stale_dists.clear();
     }
 
     /// Computes the partition's cut edges.
     pub fn cut_edges(&mut self, graph: &Graph) -> &Vec<usize> {
+        self.refresh(graph);
         if self.cut_edges.is_none() {
             let mut cut_edges = Vec::<usize>::new();
             for (index, edge) in graph.edges.iter().enumerate() {
@@ -87,6 +170,7 @@ impl Partition {
 
     /// Computes the partition's district adjacency matrix.
     pub fn dist_adj(&mut self, graph: &Graph) -> &Vec<u32> {
+        self.refresh(graph);
         if self.dist_adj.is_none() {
             let mut dist_adj = vec![0 as u32; (self.num_dists * self.num_dists) as usize];
             for edge in graph.edges.iter() {
@@ -140,6 +224,7 @@ impl Partition {
             buf.graph.pops.push(graph.pops[node]);
         }
         buf.graph.total_pop = self.dist_pops[a] + self.dist_pops[b];
+        self.subgraph_edge_attr(graph, buf);
     }
 
     /// Copies the subgraph induced by the union of districts `a` and `b`
@@ -164,24 +249,49 @@ impl Partition {
         self.subgraph(graph, buf, a, b);
         for key in attrs {
             let vals = graph.attr.get(key).unwrap();
-            if buf.graph.attr.contains_key(key) {
-                buf.graph.attr.get_mut(key).unwrap().clear();
-            } else {
-                buf.graph.attr.insert(
-                    key.to_string(),
-                    Vec::<String>::with_capacity(buf.raw_nodes.len()),
-                );
+            match buf.graph.attr.get_mut(key) {
+                Some(buf_vals) => buf_vals.copy_subset(vals, &buf.raw_nodes),
+                None => {
+                    let mut buf_vals = vals.empty_like(buf.raw_nodes.len());
+                    buf_vals.copy_subset(vals, &buf.raw_nodes);
+                    buf.graph.attr.insert(key.to_string(), buf_vals);
+                }
             }
-            let buf_vals = buf.graph.attr.get_mut(key).unwrap();
-            for &node in buf.raw_nodes.iter() {
-                buf_vals.push(vals[node].clone());
+        }
+    }
+
+    /// Copies the edge attributes of the parent graph into a buffer
+    /// (called by `subgraph` after the subgraph's edges are copied).
+    /// This is a no-op for graphs without edge attributes.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The underlying graph of the [Partition].
+    /// * `buf` - The buffer containing the subgraph.
+    fn subgraph_edge_attr(&self, graph: &Graph, buf: &mut SubgraphBuffer) {
+        if graph.edge_attr.is_empty() {
+            return;
+        }
+        buf.raw_edges.clear();
+        for edge in buf.graph.edges.iter() {
+            let (src, dst) = (buf.raw_nodes[edge.0], buf.raw_nodes[edge.1]);
+            buf.raw_edges.push(graph.edge_index(src, dst).unwrap());
+        }
+        for (key, vals) in graph.edge_attr.iter() {
+            match buf.graph.edge_attr.get_mut(key) {
+                Some(buf_vals) => buf_vals.copy_subset(vals, &buf.raw_edges),
+                None => {
+                    let mut buf_vals = vals.empty_like(buf.raw_edges.len());
+                    buf_vals.copy_subset(vals, &buf.raw_edges);
+                    buf.graph.edge_attr.insert(key.to_string(), buf_vals);
+                }
             }
         }
     }
 
     /// Copies the subgraph induced by the union of districts `a` and `b`
-    /// into a buffer. Similar to `subgraph`, but *all* node attributes are
-    /// also copied.
+    /// into a buffer. Similar to `subgraph`, but *all* node attributes
+    /// are also copied.
     ///
     /// # Arguments
     ///
@@ -213,7 +323,7 @@ impl Partition {
 
         let num_dists = *assignments.iter().max().unwrap(); // guaranteed nonempty
         let mut dist_nodes = vec![Vec::<usize>::new(); num_dists as usize];
-        let mut dist_pops = vec![0 as u32; num_dists as usize];
+        let mut dist_pops = vec![0 as Pop; num_dists as usize];
         let assignments_zeroed = assignments.iter().map(|a| a - 1).collect::<Vec<u32>>();
         for (node, &assignment) in assignments_zeroed.iter().enumerate() {
             assert!(assignment < num_dists);
@@ -232,6 +342,7 @@ impl Partition {
             assignments: assignments_zeroed,
             cut_edges: None,
             dist_adj: None,
+            stale_dists: vec![],
             dist_pops: dist_pops,
             dist_nodes: dist_nodes,
         };
@@ -352,4 +463,80 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn update_refreshes_cut_edges_and_dist_adj() {
+        let grid = Graph::rect_grid(4, 4);
+        let assignments: Vec<u32> = (0..16).map(|n| (n / 4) + 1).collect();
+        let mut partition = Partition::from_assignments(&grid, &assignments).unwrap();
+        partition.cut_edges(&grid);
+        partition.dist_adj(&grid);
+        let proposals = [
+            // Two vertical strips -> two squares.
+            (0, 1, vec![0, 1, 4, 5], vec![2, 3, 6, 7]),
+            // Two vertical strips -> an L shape and a bar.
+            (2, 3, vec![8, 9, 10, 12], vec![11, 13, 14, 15]),
+            // A square and an L shape -> two new districts.
+            (1, 2, vec![2, 3, 6, 7, 10], vec![8, 9, 12]),
+        ];
+        for (step, (a_label, b_label, a_nodes, b_nodes)) in proposals.iter().enumerate() {
+            let proposal = RecomProposal {
+                a_label: *a_label,
+                b_label: *b_label,
+                a_pop: a_nodes.len() as Pop,
+                b_pop: b_nodes.len() as Pop,
+                a_nodes: a_nodes.clone(),
+                b_nodes: b_nodes.clone(),
+            };
+            partition.update(&proposal);
+            let assignments: Vec<u32> = partition.assignments.iter().map(|a| a + 1).collect();
+            let mut fresh = Partition::from_assignments(&grid, &assignments).unwrap();
+            // Alternate which derived property is refreshed first.
+            if step % 2 == 0 {
+                assert_eq!(partition.cut_edges(&grid), fresh.cut_edges(&grid));
+                assert_eq!(partition.dist_adj(&grid), fresh.dist_adj(&grid));
+            } else {
+                assert_eq!(partition.dist_adj(&grid), fresh.dist_adj(&grid));
+                assert_eq!(partition.cut_edges(&grid), fresh.cut_edges(&grid));
+            }
+        }
+    }
+
+    #[test]
+    fn subgraph_copies_edge_attr() {
+        use crate::attr::AttrColumn;
+        let mut grid = Graph::rect_grid(3, 2);
+        grid.edge_attr.insert(
+            "kind".to_string(),
+            AttrColumn::parse(["rook", "queen", "water", "rook", "queen", "rook", "water"]),
+        );
+        let partition = Partition::from_assignments(&grid, &vec![1, 1, 2, 2, 3, 3]).unwrap();
+        let mut buf = SubgraphBuffer::new(6, 6);
+        partition.subgraph_with_attr(&grid, &mut buf, 0, 1);
+        assert_eq!(
+            buf.graph.edges,
+            vec![Edge(0, 1), Edge(0, 2), Edge(1, 3), Edge(2, 3)]
+        );
+        assert_eq!(buf.raw_edges, vec![0, 1, 2, 3]);
+        assert_eq!(
+            buf.graph.edge_attr["kind"],
+            AttrColumn::parse(["rook", "queen", "water", "rook"])
+        );
+
+        partition.subgraph(&grid, &mut buf, 1, 2);
+        assert_eq!(buf.raw_edges, vec![3, 4, 5, 6]);
+        assert_eq!(
+            buf.graph.edge_attr["kind"],
+            AttrColumn::parse(["rook", "queen", "rook", "water"])
+        );
+
+        // Edge attributes are copied even when no node attributes are.
+        partition.subgraph_with_attr_subset(&grid, &mut buf, std::iter::empty(), 2, 1);
+        assert_eq!(buf.raw_edges, vec![4, 6, 5, 3]);
+        let kinds: Vec<String> = (0..4)
+            .map(|edge| buf.graph.edge_attr["kind"].value_string(edge))
+            .collect();
+        assert_eq!(kinds, vec!["queen", "water", "rook", "rook"]);
+        assert!(buf.graph.attr.is_empty());
+    }
 }
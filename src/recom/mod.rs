@@ -1,10 +1,12 @@
 //! Data structures and algorithms for the recombination (ReCom) Markov chain.
 use crate::buffers::SplitBuffer;
-use crate::graph::Graph;
+use crate::graph::{Graph, Pop};
 use crate::partition::Partition;
 use rand::rngs::SmallRng;
 use rand::Rng;
 
+/// Built-in objective functions for ReCom-based optimization.
+pub mod objectives;
 /// ReCom-based optimization.
 pub mod opt;
 /// ReCom batch size autotuning.
@@ -28,9 +30,9 @@ pub struct RecomProposal {
     /// The label of the `b`-district in the merge-split proposal.
     pub b_label: usize,
     /// The population of the proposed  `a`-district.
-    pub a_pop: u32,
+    pub a_pop: Pop,
     /// The population of the proposed  `b`-district.
-    pub b_pop: u32,
+    pub b_pop: Pop,
     /// The node indices in the proposed `a`-district.
     pub a_nodes: Vec<usize>,
     /// The node indices in the proposed `b`-district.
@@ -85,9 +87,9 @@ pub enum RecomVariant {
 #[derive(Clone)]
 pub struct RecomParams {
     /// The minimum population of a district.
-    pub min_pop: u32,
+    pub min_pop: Pop,
     /// The maximum population of a district.
-    pub max_pop: u32,
+    pub max_pop: Pop,
     /// A soft upper bound on the number of ε-balance nodes in a spanning tree.
     /// Only used for reversible ReCom.
     pub balance_ub: u32,
@@ -396,7 +398,7 @@ fn choose_region_aware_random_cut(
         // We favor balance edges that cleanly separate regions.
         let mut edge_weight = 0.0;
         for (attr, attr_weight) in region_weights.iter() {
-            if subgraph.attr[attr][lhs] != subgraph.attr[attr][rhs] {
+            if !subgraph.attr[attr].same_value(lhs, rhs) {
                 edge_weight += *attr_weight;
             }
         }
@@ -431,7 +433,7 @@ fn choose_region_aware_random_cut(
 /// populations (`pop`) and the maximum district population (`max_pop`).
 ///
 /// Used to choose buffer sizes for recombination steps.
-fn node_bound(pops: &Vec<u32>, max_pop: u32) -> usize {
+fn node_bound(pops: &Vec<Pop>, max_pop: Pop) -> usize {
     let mut sorted_pops = pops.clone();
     sorted_pops.sort();
     let mut node_bound = 0;
@@ -0,0 +1,633 @@
+//! Built-in objective functions for ReCom-based optimization.
+//!
+//! Objectives are selected with a JSON configuration: either a single
+//! objective object, such as
+//! `{"objective": "cut_edges"}`, or a list of objective objects with
+//! optional weights, such as
+//! `[{"objective": "cut_edges", "weight": 0.01}, {"objective": "county_splits", "col": "COUNTY"}]`,
+//! which is scored as the weighted sum of its terms.
+//!
+//! All objectives are oriented so that higher scores are better (the
+//! optimizers maximize): objectives that are naturally minimized, like
+//! the number of cut edges, are negated.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::opt::ScoreValue;
+use crate::stats::{partition_attr_sums, ElectionResults, RegionIndex};
+use serde_json::{Map, Value};
+use snafu::prelude::*;
+use std::collections::HashMap;
+
+/// Errors raised while parsing an objective configuration.
+#[derive(Debug, PartialEq, Snafu)]
+pub enum ObjectiveError {
+    #[snafu(display("Could not parse objective configuration: {message}"))]
+    ErrParse { message: String },
+    #[snafu(display("Unknown objective {name}"))]
+    ErrUnknownObjective { name: String },
+    #[snafu(display("Objective {objective} requires field {field}"))]
+    ErrMissingField { objective: String, field: String },
+    #[snafu(display("Invalid value for field {field} of objective {objective}"))]
+    ErrInvalidField { objective: String, field: String },
+    #[snafu(display("Column {column} (required by an objective) not found in graph"))]
+    ErrMissingColumn { column: String },
+}
+
+/// A built-in objective function.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Objective {
+    /// Minimizes the number of cut edges (`"cut_edges"`).
+    CutEdges,
+    /// Minimizes the number of regions (e.g. counties) split between
+    /// two or more districts (`"county_splits"`).
+    CountySplits {
+        /// The region column.
+        col: String,
+    },
+    /// Maximizes the number of competitive districts: districts where the
+    /// first party's two-party vote share is within `margin` of 1/2
+    /// (`"competitive_districts"`).
+    Competitive {
+        /// The first party's vote column.
+        first_col: String,
+        /// The second party's vote column.
+        second_col: String,
+        /// The maximum distance from an even split.
+        margin: f64,
+    },
+    /// Minimizes the distance between the efficiency gap of an election
+    /// and a target value (`"efficiency_gap"`).
+    EfficiencyGap {
+        /// The first party's vote column.
+        first_col: String,
+        /// The second party's vote column.
+        second_col: String,
+        /// The target efficiency gap.
+        target: f64,
+    },
+    /// Maximizes the number of Gingles (opportunity) districts, with
+    /// partial credit for the district closest to the threshold
+    /// (`"gingles_partial"`). Multiple minority columns are summed, which
+    /// scores coalition districts.
+    ///
+    /// See [the shortbursts-gingles implementation](https://github.com/vrdi/shortbursts-gingles/blob/d9fb26ec313cd93ac80171b23095c4f3dfab0422/state_experiments/gingleator.py#L253).
+    GinglesPartial {
+        /// The minority population columns.
+        min_pop_cols: Vec<String>,
+        /// The total population column.
+        total_pop_col: String,
+        /// The minimum minority share of an opportunity district.
+        threshold: f64,
+    },
+}
+
+/// Returns a required field of an objective configuration.
+fn field<'a>(
+    objective: &str,
+    config: &'a Map<String, Value>,
+    name: &str,
+) -> Result<&'a Value, ObjectiveError> {
+    config.get(name).context(ErrMissingFieldSnafu {
+        objective,
+        field: name,
+    })
+}
+
+/// Returns a required string field of an objective configuration.
+fn str_field(
+    objective: &str,
+    config: &Map<String, Value>,
+    name: &str,
+) -> Result<String, ObjectiveError> {
+    field(objective, config, name)?
+        .as_str()
+        .map(|s| s.to_string())
+        .context(ErrInvalidFieldSnafu {
+            objective,
+            field: name,
+        })
+}
+
+/// Returns a float field of an objective configuration (or `default`
+/// if the field is optional and not present).
+fn f64_field(
+    objective: &str,
+    config: &Map<String, Value>,
+    name: &str,
+    default: Option<f64>,
+) -> Result<f64, ObjectiveError> {
+    let value = match (config.get(name), default) {
+        (None, Some(default)) => return Ok(default),
+        _ => field(objective, config, name)?,
+    };
+    value
+        .as_f64()
+        .filter(|v| v.is_finite())
+        .context(ErrInvalidFieldSnafu {
+            objective,
+            field: name,
+        })
+}
+
+/// Returns the vote columns of an objective configuration
+/// (`"election": [first, second]`).
+fn election_field(
+    objective: &str,
+    config: &Map<String, Value>,
+) -> Result<(String, String), ObjectiveError> {
+    let cols = field(objective, config, "election")?
+        .as_array()
+        .filter(|cols| cols.len() == 2 && cols.iter().all(|col| col.is_string()))
+        .context(ErrInvalidFieldSnafu {
+            objective,
+            field: "election",
+        })?;
+    Ok((
+        cols[0].as_str().unwrap().to_string(),
+        cols[1].as_str().unwrap().to_string(),
+    ))
+}
+
+impl Objective {
+    /// Parses a single objective configuration object.
+    pub fn from_config(config: &Map<String, Value>) -> Result<Objective, ObjectiveError> {
+        let name = config
+            .get("objective")
+            .and_then(|name| name.as_str())
+            .context(ErrMissingFieldSnafu {
+                objective: "(unnamed)",
+                field: "objective",
+            })?;
+        match name {
+            "cut_edges" => Ok(Objective::CutEdges),
+            "county_splits" => Ok(Objective::CountySplits {
+                col: str_field(name, config, "col")?,
+            }),
+            "competitive_districts" => {
+                let (first_col, second_col) = election_field(name, config)?;
+                Ok(Objective::Competitive {
+                    first_col,
+                    second_col,
+                    margin: f64_field(name, config, "margin", Some(0.05))?,
+                })
+            }
+            "efficiency_gap" => {
+                let (first_col, second_col) = election_field(name, config)?;
+                Ok(Objective::EfficiencyGap {
+                    first_col,
+                    second_col,
+                    target: f64_field(name, config, "target", Some(0.0))?,
+                })
+            }
+            "gingles_partial" => {
+                let threshold = f64_field(name, config, "threshold", None)?;
+                ensure!(
+                    threshold > 0.0 && threshold < 1.0,
+                    ErrInvalidFieldSnafu {
+                        objective: name,
+                        field: "threshold",
+                    }
+                );
+                // A single minority column or a list of columns (for coalitions).
+                let min_pop_cols = match field(name, config, "min_pop")? {
+                    Value::String(col) => Some(vec![col.clone()]),
+                    Value::Array(cols) if !cols.is_empty() => cols
+                        .iter()
+                        .map(|col| col.as_str().map(|col| col.to_string()))
+                        .collect(),
+                    _ => None,
+                }
+                .context(ErrInvalidFieldSnafu {
+                    objective: name,
+                    field: "min_pop",
+                })?;
+                Ok(Objective::GinglesPartial {
+                    min_pop_cols,
+                    total_pop_col: str_field(name, config, "total_pop")?,
+                    threshold,
+                })
+            }
+            _ => ErrUnknownObjectiveSnafu { name }.fail(),
+        }
+    }
+
+    /// Returns the numeric node columns used by the objective.
+    pub fn sum_columns(&self) -> Vec<String> {
+        match self {
+            Objective::CutEdges | Objective::CountySplits { .. } => vec![],
+            Objective::Competitive {
+                first_col,
+                second_col,
+                ..
+            }
+            | Objective::EfficiencyGap {
+                first_col,
+                second_col,
+                ..
+            } => vec![first_col.clone(), second_col.clone()],
+            Objective::GinglesPartial {
+                min_pop_cols,
+                total_pop_col,
+                ..
+            } => {
+                let mut cols = min_pop_cols.clone();
+                cols.push(total_pop_col.clone());
+                cols
+            }
+        }
+    }
+
+    /// Returns the region (categorical) node columns used by the objective.
+    pub fn region_columns(&self) -> Vec<String> {
+        match self {
+            Objective::CountySplits { col } => vec![col.clone()],
+            _ => vec![],
+        }
+    }
+
+    /// Scores a partition (higher is better). Region objectives use the
+    /// region indices in `regions` (keyed by region column).
+    fn score(
+        &self,
+        graph: &Graph,
+        partition: &mut Partition,
+        regions: &HashMap<String, RegionIndex>,
+    ) -> ScoreValue {
+        match self {
+            Objective::CutEdges => -(partition.cut_edges(graph).len() as f64),
+            Objective::CountySplits { col } => {
+                let index = &regions[col];
+                // The first district seen in each region, and whether
+                // each region is split.
+                let mut first_dist: Vec<Option<u32>> = vec![None; index.num_regions];
+                let mut split = vec![false; index.num_regions];
+                for (node, region) in index.node_regions.iter().enumerate() {
+                    if let Some(region) = *region {
+                        let dist = partition.assignments[node];
+                        match first_dist[region] {
+                            None => first_dist[region] = Some(dist),
+                            Some(first) => split[region] |= first != dist,
+                        }
+                    }
+                }
+                -(split.iter().filter(|&&s| s).count() as f64)
+            }
+            Objective::Competitive {
+                first_col,
+                second_col,
+                margin,
+            } => {
+                let first = partition_attr_sums(graph, partition, first_col);
+                let second = partition_attr_sums(graph, partition, second_col);
+                first
+                    .iter()
+                    .zip(second.iter())
+                    .filter(|(a, b)| {
+                        let total = a.as_f64() + b.as_f64();
+                        total > 0.0 && (a.as_f64() / total - 0.5).abs() <= *margin
+                    })
+                    .count() as f64
+            }
+            Objective::EfficiencyGap {
+                first_col,
+                second_col,
+                target,
+            } => {
+                let first: Vec<f64> = partition_attr_sums(graph, partition, first_col)
+                    .iter()
+                    .map(|sum| sum.as_f64())
+                    .collect();
+                let second: Vec<f64> = partition_attr_sums(graph, partition, second_col)
+                    .iter()
+                    .map(|sum| sum.as_f64())
+                    .collect();
+                let results = ElectionResults::from_votes(&first, &second);
+                -(results.efficiency_gap - target).abs()
+            }
+            Objective::GinglesPartial {
+                min_pop_cols,
+                total_pop_col,
+                threshold,
+            } => {
+                let mut min_pops = vec![0.0; partition.num_dists as usize];
+                for col in min_pop_cols.iter() {
+                    for (total, sum) in min_pops
+                        .iter_mut()
+                        .zip(partition_attr_sums(graph, partition, col))
+                    {
+                        *total += sum.as_f64();
+                    }
+                }
+                let total_pops = partition_attr_sums(graph, partition, total_pop_col);
+                let shares: Vec<f64> = min_pops
+                    .iter()
+                    .zip(total_pops.iter())
+                    .map(|(m, t)| m / t.as_f64())
+                    .collect();
+                let opportunity_count = shares.iter().filter(|&s| s >= threshold).count();
+                // The highest share below the threshold earns partial credit.
+                let next_highest = shares
+                    .iter()
+                    .cloned()
+                    .filter(|s| s < threshold)
+                    .fold(0.0, f64::max);
+                opportunity_count as f64 + (next_highest / threshold)
+            }
+        }
+    }
+}
+
+/// A weighted linear combination of built-in objectives.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectiveFn {
+    /// The objectives and their weights.
+    terms: Vec<(f64, Objective)>,
+    /// The region indices of the objectives' region columns (set by
+    /// [ObjectiveFn::init]).
+    regions: HashMap<String, RegionIndex>,
+}
+
+impl ObjectiveFn {
+    /// Parses an objective configuration: a single objective object or a
+    /// list of objective objects, each with an optional `"weight"`
+    /// (1 by default).
+    pub fn from_config(raw: &str) -> Result<ObjectiveFn, ObjectiveError> {
+        let data: Value = serde_json::from_str(raw).map_err(|err| ObjectiveError::ErrParse {
+            message: err.to_string(),
+        })?;
+        let configs = match data {
+            Value::Array(configs) if !configs.is_empty() => configs,
+            Value::Object(_) => vec![data],
+            _ => {
+                return ErrParseSnafu {
+                    message: "expected an objective object or a non-empty list of objectives",
+                }
+                .fail()
+            }
+        };
+        let terms = configs
+            .iter()
+            .map(|config| {
+                let config = config.as_object().context(ErrParseSnafu {
+                    message: "expected an objective object",
+                })?;
+                let objective = Objective::from_config(config)?;
+                let name = config["objective"].as_str().unwrap();
+                let weight = f64_field(name, config, "weight", Some(1.0))?;
+                Ok((weight, objective))
+            })
+            .collect::<Result<Vec<(f64, Objective)>, ObjectiveError>>()?;
+        Ok(ObjectiveFn {
+            terms,
+            regions: HashMap::new(),
+        })
+    }
+
+    /// Returns the weighted objectives.
+    pub fn terms(&self) -> &[(f64, Objective)] {
+        &self.terms
+    }
+
+    /// Returns the numeric node columns used by any of the objectives.
+    pub fn sum_columns(&self) -> Vec<String> {
+        let mut cols: Vec<String> = self
+            .terms
+            .iter()
+            .flat_map(|(_, objective)| objective.sum_columns())
+            .collect();
+        cols.sort();
+        cols.dedup();
+        cols
+    }
+
+    /// Returns the region node columns used by any of the objectives.
+    pub fn region_columns(&self) -> Vec<String> {
+        let mut cols: Vec<String> = self
+            .terms
+            .iter()
+            .flat_map(|(_, objective)| objective.region_columns())
+            .collect();
+        cols.sort();
+        cols.dedup();
+        cols
+    }
+
+    /// Checks that the objectives' columns are present in `graph` and
+    /// indexes the regions of the region columns.
+    pub fn init(&mut self, graph: &Graph) -> Result<(), ObjectiveError> {
+        for column in self.sum_columns().into_iter().chain(self.region_columns()) {
+            ensure!(
+                graph.attr.contains_key(&column),
+                ErrMissingColumnSnafu { column }
+            );
+        }
+        self.regions = self
+            .region_columns()
+            .into_iter()
+            .map(|col| {
+                let index = RegionIndex::new(graph, &col);
+                (col, index)
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Scores a partition (the weighted sum of the objectives' scores).
+    pub fn score(&self, graph: &Graph, partition: &mut Partition) -> ScoreValue {
+        self.terms
+            .iter()
+            .map(|(weight, objective)| weight * objective.score(graph, partition, &self.regions))
+            .sum()
+    }
+
+    /// Scores a partition with each objective separately (the weighted
+    /// score of each objective, in configuration order).
+    pub fn scores(&self, graph: &Graph, partition: &mut Partition) -> Vec<ScoreValue> {
+        self.terms
+            .iter()
+            .map(|(weight, objective)| weight * objective.score(graph, partition, &self.regions))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::recom::RecomProposal;
+
+    /// A 4x4 grid with four vertical districts and vote, population, and
+    /// county columns.
+    fn fixture() -> (Graph, Partition) {
+        let mut graph = Graph::rect_grid(4, 4);
+        let column = |values: Vec<i64>| AttrColumn::Int(values);
+        // Districts (columns of the grid) have first-party vote shares of
+        // 1/2, 5/8, 3/4, and 7/8.
+        graph.attr.insert(
+            "dem".to_string(),
+            column((0..16).map(|n| 4 + (n / 4)).collect()),
+        );
+        graph.attr.insert(
+            "rep".to_string(),
+            column((0..16).map(|n| 4 - (n / 4)).collect()),
+        );
+        // Districts have BVAP shares of 0, 0, 1/3, and 1/3 and
+        // BVAP + HVAP shares of 0, 1/3, 2/3, and 1.
+        graph.attr.insert("vap".to_string(), column(vec![3; 16]));
+        graph.attr.insert(
+            "bvap".to_string(),
+            column((0..16).map(|n| (n / 4) / 2).collect()),
+        );
+        graph.attr.insert(
+            "hvap".to_string(),
+            column((0..16).map(|n| ((n / 4) + 1) / 2).collect()),
+        );
+        // Counties are 2x2 blocks.
+        graph.attr.insert(
+            "county".to_string(),
+            AttrColumn::parse((0..16).map(|n| format!("c{}{}", n / 8, (n % 4) / 2))),
+        );
+        let assignments: Vec<u32> = (0..16).map(|n| (n / 4) + 1).collect();
+        let partition = Partition::from_assignments(&graph, &assignments).unwrap();
+        (graph, partition)
+    }
+
+    /// Parses and initializes an objective configuration.
+    fn objective(graph: &Graph, raw: &str) -> ObjectiveFn {
+        let mut obj = ObjectiveFn::from_config(raw).unwrap();
+        obj.init(graph).unwrap();
+        obj
+    }
+
+    #[test]
+    fn builtin_objective_scores() {
+        let (graph, mut partition) = fixture();
+        let mut score = |raw: &str| objective(&graph, raw).score(&graph, &mut partition);
+        assert_eq!(score(r#"{"objective": "cut_edges"}"#), -12.0);
+        assert_eq!(
+            score(r#"{"objective": "county_splits", "col": "county"}"#),
+            -4.0
+        );
+        assert_eq!(
+            score(r#"{"objective": "competitive_districts", "election": ["dem", "rep"]}"#),
+            1.0
+        );
+        assert_eq!(
+            score(
+                r#"{"objective": "competitive_districts", "election": ["dem", "rep"],
+                    "margin": 0.125}"#
+            ),
+            2.0
+        );
+        // Net wasted votes (for the second party, which loses the tied
+        // district) are -16 + 8 + 0 - 8 = -16 of 128.
+        let eg: f64 = -16.0 / 128.0;
+        assert_eq!(
+            score(r#"{"objective": "efficiency_gap", "election": ["dem", "rep"]}"#),
+            -eg.abs()
+        );
+        assert_eq!(
+            score(
+                r#"{"objective": "efficiency_gap", "election": ["dem", "rep"],
+                    "target": -0.2}"#
+            ),
+            -(eg + 0.2).abs()
+        );
+    }
+
+    #[test]
+    fn cut_edges_score_tracks_updates() {
+        let (graph, mut partition) = fixture();
+        let obj = objective(&graph, r#"{"objective": "cut_edges"}"#);
+        assert_eq!(obj.score(&graph, &mut partition), -12.0);
+        // Merge and split the two leftmost districts into two squares.
+        partition.update(&RecomProposal {
+            a_label: 0,
+            b_label: 1,
+            a_pop: 4,
+            b_pop: 4,
+            a_nodes: vec![0, 1, 4, 5],
+            b_nodes: vec![2, 3, 6, 7],
+        });
+        let assignments = &partition.assignments;
+        let cut = graph
+            .edges
+            .iter()
+            .filter(|edge| assignments[edge.0] != assignments[edge.1])
+            .count();
+        assert_eq!(obj.score(&graph, &mut partition), -(cut as f64));
+    }
+
+    #[test]
+    fn gingles_partial_scores_coalitions() {
+        let (graph, mut partition) = fixture();
+        let mut score = |raw: &str| objective(&graph, raw).score(&graph, &mut partition);
+        assert_eq!(
+            score(
+                r#"{"objective": "gingles_partial", "min_pop": "bvap",
+                    "total_pop": "vap", "threshold": 0.5}"#
+            ),
+            (4.0 / 12.0) / 0.5
+        );
+        assert_eq!(
+            score(
+                r#"{"objective": "gingles_partial", "min_pop": ["bvap", "hvap"],
+                    "total_pop": "vap", "threshold": 0.5}"#
+            ),
+            2.0 + (4.0 / 12.0) / 0.5
+        );
+    }
+
+    #[test]
+    fn weighted_objectives_are_summed() {
+        let (graph, mut partition) = fixture();
+        let obj = objective(
+            &graph,
+            r#"[{"objective": "cut_edges", "weight": 0.5},
+                {"objective": "county_splits", "col": "county", "weight": 2},
+                {"objective": "competitive_districts", "election": ["dem", "rep"]}]"#,
+        );
+        assert_eq!(obj.terms().len(), 3);
+        assert_eq!(obj.score(&graph, &mut partition), -6.0 - 8.0 + 1.0);
+        assert_eq!(obj.scores(&graph, &mut partition), vec![-6.0, -8.0, 1.0]);
+        assert_eq!(obj.sum_columns(), vec!["dem", "rep"]);
+        assert_eq!(obj.region_columns(), vec!["county"]);
+    }
+
+    #[test]
+    fn invalid_objective_configs() {
+        let (graph, _) = fixture();
+        let err = |raw: &str| ObjectiveFn::from_config(raw).unwrap_err();
+        assert_eq!(
+            err(r#"{"objective": "polsby_popper"}"#),
+            ObjectiveError::ErrUnknownObjective {
+                name: "polsby_popper".to_string()
+            }
+        );
+        assert_eq!(
+            err(r#"{"objective": "county_splits"}"#),
+            ObjectiveError::ErrMissingField {
+                objective: "county_splits".to_string(),
+                field: "col".to_string()
+            }
+        );
+        assert_eq!(
+            err(r#"{"objective": "gingles_partial", "min_pop": "bvap",
+                    "total_pop": "vap", "threshold": 1.5}"#),
+            ObjectiveError::ErrInvalidField {
+                objective: "gingles_partial".to_string(),
+                field: "threshold".to_string()
+            }
+        );
+        assert!(matches!(err("[]"), ObjectiveError::ErrParse { .. }));
+        let mut obj =
+            ObjectiveFn::from_config(r#"{"objective": "county_splits", "col": "COUNTYFP"}"#)
+                .unwrap();
+        assert_eq!(
+            obj.init(&graph),
+            Err(ObjectiveError::ErrMissingColumn {
+                column: "COUNTYFP".to_string()
+            })
+        );
+    }
+}
@@ -1,9 +1,11 @@
-//! ReCom-based optimization using short bursts.
+//! ReCom-based optimization using short bursts or simulated annealing.
 //!
 //! We use the "short bursts" heuristic introduced in Cannon et al. 2020
 //! (see "Voting Rights, Markov Chains, and Optimization by Short Bursts",
 //!  arXiv: 2011.02288) to maximize arbitrary partition-level objective
-//! functions.
+//! functions. Short bursts is greedy; simulated annealing, which
+//! accepts worse proposals with a probability that decreases over the
+//! course of the run, can escape plateaus that short bursts cannot.
 use super::{
     node_bound, random_split, uniform_dist_pair, RecomParams, RecomProposal, RecomVariant,
 };
@@ -14,8 +16,11 @@ use crate::spanning_tree::{RMSTSampler, RegionAwareSampler, SpanningTreeSampler}
 use crossbeam::scope;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use rand::rngs::SmallRng;
-use rand::SeedableRng;
-use serde_json::json;
+use rand::{Rng, SeedableRng};
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde_json::Result as SerdeResult;
+use serde_json::{json, Value};
 use std::collections::HashMap;
 pub type ScoreValue = f64;
 
@@ -26,6 +31,11 @@ struct OptJobPacket {
     /// The change in the chain state since the last batch of work.
     /// If no new proposals are accepted, this may be `None`.
     diff: Option<Partition>,
+    /// Whether the burst is part of a tilted run. Tilted bursts start from
+    /// the main thread's current plan (rather than continuing from the
+    /// previous burst's final state when the plan has not changed) and
+    /// return their final state.
+    tilted: bool,
     /// A sentinel used to kill the worker thread.
     terminate: bool,
 }
@@ -37,6 +47,186 @@ struct OptResultPacket {
     best_partition: Option<Partition>,
     /// The score of the best proposal.
     best_score: Option<ScoreValue>,
+    /// The final state of the chain and its score (if requested).
+    endpoint: Option<(Partition, ScoreValue)>,
+}
+
+/// The plan to restart short bursts from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RestartFrom {
+    /// The best plan found so far.
+    #[default]
+    Best,
+    /// A random plan, sampled by running `steps` (unbiased) ReCom steps
+    /// from the initial plan. (Failed proposals count as self-loops.)
+    Random { steps: usize },
+}
+
+/// Parameters for the short bursts optimizer.
+///
+/// By default, short bursts is greedy: the current plan only moves to
+/// burst plans that are at least as good. Tilted runs (see Cannon et
+/// al. 2020) and periodic restarts allow the optimizer to leave a basin.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShortBurstsParams {
+    /// The number of steps per burst.
+    pub burst_length: usize,
+    /// The probability of moving to a (worse) burst endpoint when no burst
+    /// in a round finds a plan at least as good as the current plan.
+    pub tilt: f64,
+    /// The number of steps between restarts (no restarts if `None`).
+    pub restart_interval: Option<u64>,
+    /// The plan to restart from.
+    pub restart_from: RestartFrom,
+}
+
+impl ShortBurstsParams {
+    /// Creates parameters for greedy short bursts.
+    pub fn new(burst_length: usize) -> ShortBurstsParams {
+        ShortBurstsParams {
+            burst_length,
+            tilt: 0.0,
+            restart_interval: None,
+            restart_from: RestartFrom::Best,
+        }
+    }
+
+    /// Moves to a worse burst endpoint with probability `tilt` when
+    /// no burst improves on the current plan.
+    pub fn with_tilt(mut self, tilt: f64) -> ShortBurstsParams {
+        self.tilt = tilt;
+        self
+    }
+
+    /// Restarts from `restart_from` every `interval` steps.
+    pub fn with_restarts(mut self, interval: u64, restart_from: RestartFrom) -> ShortBurstsParams {
+        self.restart_interval = Some(interval);
+        self.restart_from = restart_from;
+        self
+    }
+}
+
+/// A move of the current plan in a short bursts run that is not an
+/// improvement found by a burst.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BurstEvent {
+    /// The current plan moved to a (worse) burst endpoint.
+    Tilt,
+    /// The current plan was restarted.
+    Restart(RestartFrom),
+}
+
+/// The result of a short bursts run.
+#[derive(Clone, Debug)]
+pub struct ShortBurstsResult {
+    /// The best plan found.
+    pub best: Partition,
+    /// The score of the best plan.
+    pub best_score: ScoreValue,
+    /// The tilted moves and restarts (with the step they occurred at),
+    /// in order.
+    pub events: Vec<(u64, BurstEvent)>,
+}
+
+/// Returns the verbose output line for the current plan of a short bursts
+/// run, marking tilted moves and restarts with `"tilt"` and `"restart"` keys.
+fn short_bursts_line(
+    step: u64,
+    score: ScoreValue,
+    partition: &Partition,
+    event: Option<BurstEvent>,
+) -> Value {
+    let mut line = json!({
+        "step": step,
+        "score": score,
+        "assignment": partition.assignments.clone().into_iter().enumerate().collect::<HashMap<usize, u32>>()
+    });
+    let entry = match event {
+        Some(BurstEvent::Tilt) => Some(("tilt", json!(true))),
+        Some(BurstEvent::Restart(RestartFrom::Best)) => Some(("restart", json!("best"))),
+        Some(BurstEvent::Restart(RestartFrom::Random { .. })) => Some(("restart", json!("random"))),
+        None => None,
+    };
+    if let Some((key, value)) = entry {
+        line.as_object_mut().unwrap().insert(key.to_string(), value);
+    }
+    line
+}
+
+/// ReCom proposal sampling state for an optimization thread.
+struct ProposalSampler {
+    /// The thread's RNG.
+    rng: SmallRng,
+    /// A buffer for the merged subgraph of two districts.
+    subgraph_buf: SubgraphBuffer,
+    /// A buffer for spanning trees of merged subgraphs.
+    st_buf: SpanningTreeBuffer,
+    /// A buffer for balanced cuts of spanning trees.
+    split_buf: SplitBuffer,
+    /// The most recently sampled proposal.
+    proposal: RecomProposal,
+    /// The spanning tree sampler.
+    st_sampler: Box<dyn SpanningTreeSampler>,
+}
+
+impl ProposalSampler {
+    /// Creates a proposal sampler for `graph`. (See [start_opt_thread]
+    /// for `buf_size`.)
+    fn new(graph: &Graph, params: &RecomParams, rng_seed: u64, buf_size: usize) -> ProposalSampler {
+        // TODO: consider supporting other ReCom variants.
+        // We generally don't (or can't) care about distributional
+        // properties, so it would make little sense to support reversible
+        // ReCom or the like. RMST sampling is asymptotically more efficient
+        // than UST sampling, so we use it as the default for now.
+        let n = graph.pops.len();
+        let st_sampler: Box<dyn SpanningTreeSampler>;
+        if params.variant == RecomVariant::DistrictPairsRegionAware {
+            st_sampler = Box::new(RegionAwareSampler::new(
+                buf_size,
+                params.region_weights.clone().unwrap(),
+            ));
+        } else if params.variant == RecomVariant::DistrictPairsRMST {
+            st_sampler = Box::new(RMSTSampler::new(buf_size));
+        } else {
+            panic!("ReCom variant not supported by optimizer.");
+        }
+        ProposalSampler {
+            rng: SeedableRng::seed_from_u64(rng_seed),
+            subgraph_buf: SubgraphBuffer::new(n, buf_size),
+            st_buf: SpanningTreeBuffer::new(buf_size),
+            split_buf: SplitBuffer::new(buf_size, params.balance_ub as usize),
+            proposal: RecomProposal::new_buffer(buf_size),
+            st_sampler,
+        }
+    }
+
+    /// Attempts to sample a ReCom proposal for `partition` (stored in
+    /// `self.proposal`). Returns `false` if no valid proposal was found.
+    fn sample(&mut self, graph: &Graph, partition: &mut Partition, params: &RecomParams) -> bool {
+        let dist_pair = uniform_dist_pair(graph, partition, &mut self.rng);
+        if dist_pair.is_none() {
+            return false;
+        }
+        let (dist_a, dist_b) = dist_pair.unwrap();
+        partition.subgraph_with_attr(graph, &mut self.subgraph_buf, dist_a, dist_b);
+        self.st_sampler.random_spanning_tree(
+            &self.subgraph_buf.graph,
+            &mut self.st_buf,
+            &mut self.rng,
+        );
+        random_split(
+            &self.subgraph_buf.graph,
+            &mut self.rng,
+            &self.st_buf.st,
+            dist_a,
+            dist_b,
+            &mut self.split_buf,
+            &mut self.proposal,
+            &self.subgraph_buf.raw_nodes,
+            params,
+        )
+        .is_ok()
+    }
 }
 
 /// Starts a ReCom optimization thread.
@@ -57,68 +247,35 @@ fn start_opt_thread(
     graph: Graph,
     mut partition: Partition,
     params: RecomParams,
-    obj_fn: impl Fn(&Graph, &Partition) -> ScoreValue + Send + Clone + Copy,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> ScoreValue + Send + Clone + Copy,
     rng_seed: u64,
     buf_size: usize,
     job_recv: Receiver<OptJobPacket>,
     result_send: Sender<OptResultPacket>,
 ) {
-    // TODO: consider supporting other ReCom variants.
-    // We generally don't (or can't) care about distributional
-    // properties, so it would make little sense to support reversible
-    // ReCom or the like. RMST sampling is asymptotically more efficient
-    // than UST sampling, so we use it as the default for now.
-    let n = graph.pops.len();
-    let mut rng: SmallRng = SeedableRng::seed_from_u64(rng_seed);
-    let mut subgraph_buf = SubgraphBuffer::new(n, buf_size);
-    let mut st_buf = SpanningTreeBuffer::new(buf_size);
-    let mut split_buf = SplitBuffer::new(buf_size, params.balance_ub as usize);
-    let mut proposal_buf = RecomProposal::new_buffer(buf_size);
-    let mut st_sampler: Box<dyn SpanningTreeSampler>;
-    if params.variant == RecomVariant::DistrictPairsRegionAware {
-        st_sampler = Box::new(RegionAwareSampler::new(
-            buf_size,
-            params.region_weights.clone().unwrap(),
-        ));
-    } else if params.variant == RecomVariant::DistrictPairsRMST {
-        st_sampler = Box::new(RMSTSampler::new(buf_size));
-    } else {
-        panic!("ReCom variant not supported by optimizer.");
-    }
-
+    let mut sampler = ProposalSampler::new(&graph, &params, rng_seed, buf_size);
+    // The main thread's current plan (only tracked for tilted runs).
+    let mut start: Option<Partition> = None;
     let mut next: OptJobPacket = job_recv.recv().unwrap();
     while !next.terminate {
-        if next.diff.is_some() {
+        if next.tilted {
+            let start = start.get_or_insert_with(|| partition.clone());
+            if let Some(diff) = next.diff {
+                *start = diff;
+            }
+            partition.clone_from(start);
+        } else if next.diff.is_some() {
             partition = next.diff.unwrap();
         }
 
         let mut best_partition: Option<Partition> = None;
-        let mut score = obj_fn(&graph, &partition);
+        let mut score = obj_fn(&graph, &mut partition);
         let mut best_score: ScoreValue = score;
         let mut step = 0;
         while step < next.n_steps {
-            // Sample a ReCom step.
-            let dist_pair = uniform_dist_pair(&graph, &mut partition, &mut rng);
-            if dist_pair.is_none() {
-                continue;
-            }
-            let (dist_a, dist_b) = dist_pair.unwrap();
-            partition.subgraph_with_attr(&graph, &mut subgraph_buf, dist_a, dist_b);
-            st_sampler.random_spanning_tree(&subgraph_buf.graph, &mut st_buf, &mut rng);
-            let split = random_split(
-                &subgraph_buf.graph,
-                &mut rng,
-                &st_buf.st,
-                dist_a,
-                dist_b,
-                &mut split_buf,
-                &mut proposal_buf,
-                &subgraph_buf.raw_nodes,
-                &params,
-            );
-            if split.is_ok() {
-                score = obj_fn(&graph, &partition);
-                partition.update(&proposal_buf);
+            if sampler.sample(&graph, &mut partition, &params) {
+                partition.update(&sampler.proposal);
+                score = obj_fn(&graph, &mut partition);
                 if score >= best_score {
                     // TODO: reduce allocations by keeping a separate
                     // buffer for the best partition.
@@ -128,14 +285,20 @@ fn start_opt_thread(
                 step += 1;
             }
         }
+        let endpoint = match next.tilted {
+            true => Some((partition.clone(), score)),
+            false => None,
+        };
         let result = match best_partition {
             Some(partition) => OptResultPacket {
                 best_partition: Some(partition.clone()),
                 best_score: Some(best_score),
+                endpoint,
             },
             None => OptResultPacket {
                 best_partition: None,
                 best_score: None,
+                endpoint,
             },
         };
         result_send.send(result).unwrap();
@@ -144,10 +307,11 @@ fn start_opt_thread(
 }
 
 /// Sends a batch of work to a ReCom optimization thread.
-fn next_batch(send: &Sender<OptJobPacket>, diff: Option<Partition>, burst_length: usize) {
+fn next_batch(send: &Sender<OptJobPacket>, diff: Option<Partition>, bursts: &ShortBurstsParams) {
     send.send(OptJobPacket {
-        n_steps: burst_length,
+        n_steps: bursts.burst_length,
         diff: diff,
+        tilted: bursts.tilt > 0.0,
         terminate: false,
     })
     .unwrap();
@@ -158,30 +322,34 @@ fn stop_opt_thread(send: &Sender<OptJobPacket>) {
     send.send(OptJobPacket {
         n_steps: 0,
         diff: None,
+        tilted: false,
         terminate: true,
     })
     .unwrap();
 }
 
-/// Runs a multi-threaded ReCom short bursts optimizer.
+/// Runs a multi-threaded ReCom short bursts optimizer and returns the best
+/// plan found (along with the tilted moves and restarts).
 ///
 /// # Arguments
 ///
 /// * `graph` - The graph associated with `partition`.
-/// * `partition` - The partition to start the chain run from (updated in place).
-/// * `writer` - The statistics writer.
+/// * `partition` - The partition to start the chain run from.
 /// * `params` - The parameters of the ReCom chain runs.
 /// * `n_threads` - The number of worker threads (excluding the main thread).
-/// * `burst_length` - The number of steps per burst.
+/// * `obj_fn` - The objective function to maximize.
+/// * `bursts` - The short bursts parameters (burst length, tilt, and restarts).
+/// * `verbose` - Print the current plan whenever it changes (tilted moves
+///   and restarts are marked with `"tilt"` and `"restart"` keys).
 pub fn multi_short_bursts(
     graph: &Graph,
     mut partition: Partition,
     params: &RecomParams,
     n_threads: usize,
-    obj_fn: impl Fn(&Graph, &Partition) -> ScoreValue + Send + Clone + Copy,
-    burst_length: usize,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> ScoreValue + Send + Clone + Copy,
+    bursts: &ShortBurstsParams,
     verbose: bool,
-) -> Partition {
+) -> ShortBurstsResult {
     let mut step = 0;
     let node_ub = node_bound(&graph.pops, params.max_pop);
     let mut job_sends = vec![]; // main thread sends work to job threads
@@ -194,7 +362,16 @@ pub fn multi_short_bursts(
     // All optimization threads send a summary of chain results back to the main thread.
     let (result_send, result_recv): (Sender<OptResultPacket>, Receiver<OptResultPacket>) =
         unbounded();
-    let mut score = obj_fn(&graph, &partition);
+    let mut score = obj_fn(&graph, &mut partition);
+    // The current plan may be worse than the best plan after tilted
+    // moves and restarts.
+    let initial = partition.clone();
+    let mut best = partition.clone();
+    let mut best_score = score;
+    let mut last_restart = 0;
+    let mut events = vec![];
+    // The main thread samples random restarts and tilted moves.
+    let mut sampler = ProposalSampler::new(graph, params, params.rng_seed, node_ub);
 
     scope(|scope| {
         // Start optimization threads.
@@ -221,12 +398,13 @@ pub fn multi_short_bursts(
 
         if params.num_steps > 0 {
             for job in job_sends.iter() {
-                next_batch(job, None, burst_length);
+                next_batch(job, None, bursts);
             }
         }
 
         while step <= params.num_steps {
             let mut diff = None;
+            let mut endpoints = vec![];
             for _ in 0..n_threads {
                 let packet: OptResultPacket = result_recv.recv().unwrap();
                 if packet.best_partition.is_some() && packet.best_score.unwrap() >= score {
@@ -234,25 +412,1004 @@ pub fn multi_short_bursts(
                     score = packet.best_score.unwrap();
                     diff = Some(partition.clone());
                 }
+                endpoints.extend(packet.endpoint);
             }
-            step += (n_threads * burst_length) as u64;
+            step += (n_threads * bursts.burst_length) as u64;
+            if score > best_score {
+                best = partition.clone();
+                best_score = score;
+            }
+
+            let mut event = None;
+            if bursts
+                .restart_interval
+                .is_some_and(|interval| step - last_restart >= interval)
+            {
+                last_restart = step;
+                partition = match bursts.restart_from {
+                    RestartFrom::Best => best.clone(),
+                    RestartFrom::Random { steps } => {
+                        // As in annealing, failed samples are self-loops.
+                        let mut random = initial.clone();
+                        for _ in 0..steps {
+                            if sampler.sample(graph, &mut random, params) {
+                                random.update(&sampler.proposal);
+                            }
+                        }
+                        random
+                    }
+                };
+                score = obj_fn(graph, &mut partition);
+                if score > best_score {
+                    best = partition.clone();
+                    best_score = score;
+                }
+                diff = Some(partition.clone());
+                event = Some(BurstEvent::Restart(bursts.restart_from));
+            } else if diff.is_none()
+                && !endpoints.is_empty()
+                && sampler.rng.gen::<f64>() < bursts.tilt
+            {
+                // No burst found a plan at least as good as the current
+                // plan, so all endpoints are worse.
+                let idx = sampler.rng.gen_range(0..endpoints.len());
+                let (endpoint, endpoint_score) = endpoints.swap_remove(idx);
+                partition = endpoint;
+                score = endpoint_score;
+                diff = Some(partition.clone());
+                event = Some(BurstEvent::Tilt);
+            }
+            events.extend(event.map(|event| (step, event)));
+
             if diff.is_some() && verbose {
+                println!("{}", short_bursts_line(step, score, &partition, event));
+            }
+            for job in job_sends.iter() {
+                next_batch(job, diff.clone(), bursts);
+            }
+        }
+
+        // Terminate worker threads.
+        for job in job_sends.iter() {
+            stop_opt_thread(job);
+        }
+        ShortBurstsResult {
+            best,
+            best_score,
+            events,
+        }
+    })
+    .unwrap()
+}
+
+/// A temperature schedule for simulated annealing.
+///
+/// Schedules are configured with JSON objects tagged by `"schedule"`, e.g.
+/// `{"schedule": "geometric", "start": 1.0, "end": 0.001}`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "schedule", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TemperatureSchedule {
+    /// Interpolates linearly between the `start` and `end` temperatures.
+    Linear { start: f64, end: f64 },
+    /// Interpolates geometrically between the `start` and `end` temperatures.
+    Geometric { start: f64, end: f64 },
+    /// Starts at the `start` temperature and adjusts the temperature by
+    /// `factor` after each batch of work to track a target acceptance rate
+    /// of worse proposals, which decreases linearly from `acceptance` to 0.
+    Adaptive {
+        start: f64,
+        acceptance: f64,
+        #[serde(default = "default_adaptive_factor")]
+        factor: f64,
+    },
+}
+
+/// The default adjustment factor of adaptive temperature schedules.
+fn default_adaptive_factor() -> f64 {
+    1.1
+}
+
+impl TemperatureSchedule {
+    /// Parses a temperature schedule from a JSON configuration string.
+    pub fn from_config(raw: &str) -> SerdeResult<TemperatureSchedule> {
+        let schedule: TemperatureSchedule = serde_json::from_str(raw)?;
+        let valid = match schedule {
+            TemperatureSchedule::Linear { start, end } => start >= 0.0 && end >= 0.0,
+            TemperatureSchedule::Geometric { start, end } => start > 0.0 && end > 0.0,
+            TemperatureSchedule::Adaptive {
+                start,
+                acceptance,
+                factor,
+            } => start > 0.0 && acceptance > 0.0 && acceptance < 1.0 && factor > 1.0,
+        };
+        match valid {
+            true => Ok(schedule),
+            false => Err(serde_json::Error::custom(
+                "temperatures must be nonnegative (positive for geometric and \
+                 adaptive schedules), acceptance rates must be in (0, 1), \
+                 and adjustment factors must be greater than 1",
+            )),
+        }
+    }
+
+    /// Returns the initial temperature.
+    pub fn start(&self) -> f64 {
+        match *self {
+            TemperatureSchedule::Linear { start, .. }
+            | TemperatureSchedule::Geometric { start, .. }
+            | TemperatureSchedule::Adaptive { start, .. } => start,
+        }
+    }
+
+    /// Returns the temperature of the next batch of work.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - The temperature of the last batch.
+    /// * `progress` - The fraction of the run completed (in [0, 1]).
+    /// * `acceptance` - The acceptance rate of worse proposals in the last
+    ///   batch (`None` if there were no worse proposals).
+    pub fn next(&self, temperature: f64, progress: f64, acceptance: Option<f64>) -> f64 {
+        let progress = progress.clamp(0.0, 1.0);
+        match *self {
+            TemperatureSchedule::Linear { start, end } => start + (end - start) * progress,
+            TemperatureSchedule::Geometric { start, end } => start * (end / start).powf(progress),
+            TemperatureSchedule::Adaptive {
+                acceptance: target,
+                factor,
+                ..
+            } => match acceptance {
+                Some(rate) if rate > target * (1.0 - progress) => temperature / factor,
+                Some(_) => temperature * factor,
+                None => temperature,
+            },
+        }
+    }
+}
+
+/// A unit of multithreaded simulated annealing work.
+struct AnnealJobPacket {
+    /// The number of steps to sample (*not* the number of unique plans).
+    n_steps: usize,
+    /// The temperature to sample at.
+    temperature: f64,
+    /// A sentinel used to kill the worker thread.
+    terminate: bool,
+}
+
+/// The result of a unit of multithreaded simulated annealing work.
+struct AnnealResultPacket {
+    /// The best plan found by the thread so far, if it was found
+    /// in this unit of work.
+    best_partition: Option<Partition>,
+    /// The score of the best plan found by the thread so far.
+    best_score: ScoreValue,
+    /// The number of proposals that were worse than the current plan.
+    worse: usize,
+    /// The number of worse proposals that were accepted.
+    worse_accepted: usize,
+}
+
+/// Starts a simulated annealing thread.
+/// Each thread runs an independent annealing chain; the main thread
+/// sets the temperature of each batch of work and tracks the best plan
+/// found across all threads.
+///
+/// Arguments:
+/// * `graph` - The graph associated with the chain.
+/// * `partition` - The initial state of the chain.
+/// * `params` - The chain parameters.
+/// * `obj_fn` - The objective function to evaluate proposals against.
+/// * `rng_seed` - The RNG seed for the job thread. (This should differ across threads.)
+/// * `buf_size` - The buffer size for various chain buffers. This should usually be twice
+///   the maximum possible district size (in nodes).
+/// * `job_recv` - A Crossbeam channel for receiving batches of work from the main thread.
+/// * `result_send` - A Crossbeam channel for sending completed batches to the main thread.
+#[allow(clippy::too_many_arguments)]
+fn start_anneal_thread(
+    graph: Graph,
+    mut partition: Partition,
+    params: RecomParams,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> ScoreValue + Send + Copy,
+    rng_seed: u64,
+    buf_size: usize,
+    job_recv: Receiver<AnnealJobPacket>,
+    result_send: Sender<AnnealResultPacket>,
+) {
+    let mut sampler = ProposalSampler::new(&graph, &params, rng_seed, buf_size);
+    // Rejected proposals are undone by applying the reverse proposal.
+    let mut reverse = RecomProposal::new_buffer(buf_size);
+    let mut score = obj_fn(&graph, &mut partition);
+    let mut best_score = score;
+
+    let mut next: AnnealJobPacket = job_recv.recv().unwrap();
+    while !next.terminate {
+        let mut best_partition: Option<Partition> = None;
+        let mut worse = 0;
+        let mut worse_accepted = 0;
+        let mut step = 0;
+        while step < next.n_steps {
+            // Failed samples count as self-loops, so a plan with no valid
+            // proposals cannot stall the thread.
+            step += 1;
+            if !sampler.sample(&graph, &mut partition, &params) {
+                continue;
+            }
+            let (a, b) = (sampler.proposal.a_label, sampler.proposal.b_label);
+            reverse.clear();
+            reverse.a_label = a;
+            reverse.b_label = b;
+            reverse.a_pop = partition.dist_pops[a];
+            reverse.b_pop = partition.dist_pops[b];
+            reverse.a_nodes.extend_from_slice(&partition.dist_nodes[a]);
+            reverse.b_nodes.extend_from_slice(&partition.dist_nodes[b]);
+            partition.update(&sampler.proposal);
+
+            let proposed_score = obj_fn(&graph, &mut partition);
+            let delta = score - proposed_score;
+            if delta > 0.0 {
+                // Accept worse proposals with probability exp(-Δ/T).
+                worse += 1;
+                let accept = next.temperature > 0.0
+                    && sampler.rng.gen::<f64>() < (-delta / next.temperature).exp();
+                if !accept {
+                    partition.update(&reverse);
+                    continue;
+                }
+                worse_accepted += 1;
+            }
+            score = proposed_score;
+            if score > best_score {
+                best_partition = Some(partition.clone());
+                best_score = score;
+            }
+        }
+        result_send
+            .send(AnnealResultPacket {
+                best_partition,
+                best_score,
+                worse,
+                worse_accepted,
+            })
+            .unwrap();
+        next = job_recv.recv().unwrap();
+    }
+}
+
+/// Runs a multi-threaded ReCom simulated annealing optimizer.
+///
+/// Each thread runs an independent annealing chain from `partition`.
+/// Every `batch_length` steps (per thread), the main thread records the
+/// best plan found so far and updates the temperature according to
+/// `schedule`. Returns the best plan found.
+///
+/// # Arguments
+///
+/// * `graph` - The graph associated with `partition`.
+/// * `partition` - The partition to start the chains from.
+/// * `params` - The parameters of the ReCom chain runs.
+/// * `n_threads` - The number of worker threads (excluding the main thread).
+/// * `obj_fn` - The objective function to maximize.
+/// * `schedule` - The temperature schedule.
+/// * `batch_length` - The number of steps per thread between temperature updates.
+/// * `verbose` - Print the best plan (with its score and the current
+///   temperature) whenever it improves.
+#[allow(clippy::too_many_arguments)]
+pub fn multi_simulated_annealing(
+    graph: &Graph,
+    mut partition: Partition,
+    params: &RecomParams,
+    n_threads: usize,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> ScoreValue + Send + Copy,
+    schedule: &TemperatureSchedule,
+    batch_length: usize,
+    verbose: bool,
+) -> Partition {
+    let mut step = 0;
+    let node_ub = node_bound(&graph.pops, params.max_pop);
+    let mut job_sends = vec![]; // main thread sends work to job threads
+    let mut job_recvs = vec![]; // job threads receive work from main thread
+    for _ in 0..n_threads {
+        let (s, r): (Sender<AnnealJobPacket>, Receiver<AnnealJobPacket>) = unbounded();
+        job_sends.push(s);
+        job_recvs.push(r);
+    }
+    // All annealing threads send a summary of chain results back to the main thread.
+    let (result_send, result_recv): (Sender<AnnealResultPacket>, Receiver<AnnealResultPacket>) =
+        unbounded();
+    let mut score = obj_fn(graph, &mut partition);
+    let mut temperature = schedule.start();
+
+    scope(|scope| {
+        // Start annealing threads.
+        for (t_idx, job_recv) in job_recvs.iter().enumerate() {
+            let rng_seed = params.rng_seed + t_idx as u64 + 1;
+            let job_recv = job_recv.clone();
+            let result_send = result_send.clone();
+            let partition = partition.clone();
+
+            scope.spawn(move |_| {
+                start_anneal_thread(
+                    graph.clone(),
+                    partition,
+                    params.clone(),
+                    obj_fn,
+                    rng_seed,
+                    node_ub,
+                    job_recv,
+                    result_send,
+                );
+            });
+        }
+
+        while step < params.num_steps {
+            for job in job_sends.iter() {
+                job.send(AnnealJobPacket {
+                    n_steps: batch_length,
+                    temperature,
+                    terminate: false,
+                })
+                .unwrap();
+            }
+            let mut improved = false;
+            let mut worse = 0;
+            let mut worse_accepted = 0;
+            for _ in 0..n_threads {
+                let packet: AnnealResultPacket = result_recv.recv().unwrap();
+                worse += packet.worse;
+                worse_accepted += packet.worse_accepted;
+                if let Some(best) = packet.best_partition {
+                    if packet.best_score > score {
+                        partition = best;
+                        score = packet.best_score;
+                        improved = true;
+                    }
+                }
+            }
+            step += (n_threads * batch_length) as u64;
+            if improved && verbose {
                 println!("{}", json!({
                     "step": step,
                     "score": score,
+                    "temperature": temperature,
                     "assignment": partition.assignments.clone().into_iter().enumerate().collect::<HashMap<usize, u32>>()
-                }).to_string());
+                }));
+            }
+            let acceptance = match worse {
+                0 => None,
+                _ => Some(worse_accepted as f64 / worse as f64),
+            };
+            let progress = step as f64 / params.num_steps as f64;
+            temperature = schedule.next(temperature, progress, acceptance);
+        }
+
+        // Terminate worker threads.
+        for job in job_sends.iter() {
+            job.send(AnnealJobPacket {
+                n_steps: 0,
+                temperature,
+                terminate: true,
+            })
+            .unwrap();
+        }
+        partition
+    })
+    .unwrap()
+}
+
+/// An archive of non-dominated plans (a Pareto front) for multi-objective
+/// optimization.
+///
+/// A plan dominates another plan if it scores at least as well on every
+/// objective and strictly better on at least one objective. Only one plan
+/// is kept for each score vector.
+///
+/// Fronts are unbounded by default. The size of a front can be capped
+/// with [`ParetoFront::with_max_len`]; when a front is full, its most
+/// crowded plan is evicted.
+#[derive(Clone, Default)]
+pub struct ParetoFront {
+    /// The non-dominated plans and their score vectors.
+    plans: Vec<(Partition, Vec<ScoreValue>)>,
+    /// The maximum number of plans in the front (unbounded if `None`).
+    max_len: Option<usize>,
+}
+
+/// Returns `true` if score vector `a` dominates score vector `b`.
+fn dominates(a: &[ScoreValue], b: &[ScoreValue]) -> bool {
+    a.iter().zip(b.iter()).all(|(x, y)| x >= y) && a.iter().zip(b.iter()).any(|(x, y)| x > y)
+}
+
+impl ParetoFront {
+    /// Creates an empty Pareto front.
+    pub fn new() -> ParetoFront {
+        ParetoFront::default()
+    }
+
+    /// Caps the number of plans in the front at `max_len` (at least 1).
+    ///
+    /// When a plan is added to a full front, the plan with the smallest
+    /// crowding distance (see Deb et al. 2002) is evicted, so the front
+    /// stays spread out along the trade-off curve. An evicted plan is
+    /// forgotten: a later plan that it dominates may be added to the front.
+    pub fn with_max_len(mut self, max_len: usize) -> ParetoFront {
+        assert!(max_len > 0, "Pareto fronts must hold at least one plan");
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Returns `true` if a plan with score vector `scores` would not be
+    /// added to the front (because it is dominated by a plan in the
+    /// front or has the same scores as a plan in the front).
+    pub fn rejects(&self, scores: &[ScoreValue]) -> bool {
+        self.plans
+            .iter()
+            .any(|(_, other)| other.as_slice() == scores || dominates(other, scores))
+    }
+
+    /// Adds a plan to the front (removing any plans it dominates) unless
+    /// the front rejects it. Returns `true` if the plan was added (and
+    /// was not immediately evicted from a full front).
+    pub fn insert(&mut self, partition: Partition, scores: Vec<ScoreValue>) -> bool {
+        if self.rejects(&scores) {
+            return false;
+        }
+        self.plans.retain(|(_, other)| !dominates(&scores, other));
+        self.plans.push((partition, scores));
+        match self.max_len {
+            Some(max_len) if self.plans.len() > max_len => {
+                self.evict_most_crowded() != self.plans.len()
+            }
+            _ => true,
+        }
+    }
+
+    /// Removes the plan with the smallest crowding distance from the front
+    /// and returns its index.
+    ///
+    /// The crowding distance of a plan is the sum over objectives of the
+    /// (normalized) distance between its neighbors on that objective.
+    /// Plans with the best or worst score on an objective are never evicted
+    /// while other plans remain.
+    fn evict_most_crowded(&mut self) -> usize {
+        let n = self.plans.len();
+        let mut distances = vec![0.0; n];
+        let mut order: Vec<usize> = (0..n).collect();
+        for dim in 0..self.plans[0].1.len() {
+            let score = |idx: usize| self.plans[idx].1[dim];
+            order.sort_by(|&a, &b| {
+                score(a)
+                    .partial_cmp(&score(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let range = score(order[n - 1]) - score(order[0]);
+            distances[order[0]] = f64::INFINITY;
+            distances[order[n - 1]] = f64::INFINITY;
+            if range > 0.0 {
+                for window in order.windows(3) {
+                    distances[window[1]] += (score(window[2]) - score(window[0])) / range;
+                }
             }
+        }
+        let idx = (0..n)
+            .min_by(|&a, &b| {
+                distances[a]
+                    .partial_cmp(&distances[b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap();
+        self.plans.remove(idx);
+        idx
+    }
+
+    /// Returns the plans in the front and their score vectors.
+    pub fn plans(&self) -> &[(Partition, Vec<ScoreValue>)] {
+        &self.plans
+    }
+
+    /// Returns the number of plans in the front.
+    pub fn len(&self) -> usize {
+        self.plans.len()
+    }
+
+    /// Returns `true` if the front is empty.
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty()
+    }
+
+    /// Returns the plans in the front, sorted by score vector (in
+    /// descending lexicographic order).
+    pub fn into_sorted(mut self) -> Vec<(Partition, Vec<ScoreValue>)> {
+        self.plans
+            .sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        self.plans
+    }
+}
+
+/// A unit of multithreaded Pareto optimization work.
+struct ParetoJobPacket {
+    /// The number of steps to sample (*not* the number of unique plans).
+    n_steps: usize,
+    /// The plan to start the burst from.
+    start: Option<Partition>,
+    /// A sentinel used to kill the worker thread.
+    terminate: bool,
+}
+
+/// The result of a unit of multithreaded Pareto optimization work.
+struct ParetoResultPacket {
+    /// The non-dominated plans visited by the burst.
+    front: ParetoFront,
+}
+
+/// Starts a Pareto optimization thread.
+/// Pareto optimization threads run short bursts from plans chosen by the
+/// main thread and return the non-dominated plans they visit.
+///
+/// Arguments:
+/// * `graph` - The graph associated with the chain.
+/// * `params` - The chain parameters.
+/// * `obj_fn` - The objective functions to evaluate proposals against.
+/// * `rng_seed` - The RNG seed for the job thread. (This should differ across threads.)
+/// * `buf_size` - The buffer size for various chain buffers. This should usually be twice
+///   the maximum possible district size (in nodes).
+/// * `job_recv` - A Crossbeam channel for receiving batches of work from the main thread.
+/// * `result_send` - A Crossbeam channel for sending completed batches to the main thread.
+fn start_pareto_thread(
+    graph: Graph,
+    params: RecomParams,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> Vec<ScoreValue> + Send + Copy,
+    rng_seed: u64,
+    buf_size: usize,
+    job_recv: Receiver<ParetoJobPacket>,
+    result_send: Sender<ParetoResultPacket>,
+) {
+    let mut sampler = ProposalSampler::new(&graph, &params, rng_seed, buf_size);
+    let mut next: ParetoJobPacket = job_recv.recv().unwrap();
+    while !next.terminate {
+        let mut partition = next.start.unwrap();
+        let mut front = ParetoFront::new();
+        let mut step = 0;
+        while step < next.n_steps {
+            // Failed samples count as self-loops, as in the annealing threads.
+            step += 1;
+            if sampler.sample(&graph, &mut partition, &params) {
+                partition.update(&sampler.proposal);
+                let scores = obj_fn(&graph, &mut partition);
+                front.insert(partition.clone(), scores);
+            }
+        }
+        result_send.send(ParetoResultPacket { front }).unwrap();
+        next = job_recv.recv().unwrap();
+    }
+}
+
+/// Runs a multi-threaded multi-objective ReCom optimizer and returns the
+/// Pareto front of the plans it visits (sorted by score vector).
+///
+/// In each round, every thread runs a short burst from a plan chosen
+/// uniformly at random from the current front; the non-dominated plans
+/// visited by the bursts are then merged into the front.
+///
+/// # Arguments
+///
+/// * `graph` - The graph associated with `partition`.
+/// * `partition` - The partition to start the optimizer from.
+/// * `params` - The parameters of the ReCom chain runs.
+/// * `n_threads` - The number of worker threads (excluding the main thread).
+/// * `obj_fn` - The objective functions to maximize (as a score vector).
+/// * `burst_length` - The number of steps per burst.
+/// * `max_front_len` - The maximum number of plans in the front (unbounded
+///   if `None`; see [`ParetoFront::with_max_len`]).
+/// * `verbose` - Print the size of the front and the score vectors added
+///   to and removed from the front whenever it changes.
+#[allow(clippy::too_many_arguments)]
+pub fn multi_pareto_short_bursts(
+    graph: &Graph,
+    mut partition: Partition,
+    params: &RecomParams,
+    n_threads: usize,
+    obj_fn: impl Fn(&Graph, &mut Partition) -> Vec<ScoreValue> + Send + Copy,
+    burst_length: usize,
+    max_front_len: Option<usize>,
+    verbose: bool,
+) -> Vec<(Partition, Vec<ScoreValue>)> {
+    let mut step = 0;
+    let node_ub = node_bound(&graph.pops, params.max_pop);
+    let mut job_sends = vec![]; // main thread sends work to job threads
+    let mut job_recvs = vec![]; // job threads receive work from main thread
+    for _ in 0..n_threads {
+        let (s, r): (Sender<ParetoJobPacket>, Receiver<ParetoJobPacket>) = unbounded();
+        job_sends.push(s);
+        job_recvs.push(r);
+    }
+    // All optimization threads send their fronts back to the main thread.
+    let (result_send, result_recv): (Sender<ParetoResultPacket>, Receiver<ParetoResultPacket>) =
+        unbounded();
+    let mut rng: SmallRng = SeedableRng::seed_from_u64(params.rng_seed);
+    let mut front = match max_front_len {
+        Some(max_len) => ParetoFront::new().with_max_len(max_len),
+        None => ParetoFront::new(),
+    };
+    let scores = obj_fn(graph, &mut partition);
+    front.insert(partition, scores);
+
+    scope(|scope| {
+        // Start optimization threads.
+        for (t_idx, job_recv) in job_recvs.iter().enumerate() {
+            let rng_seed = params.rng_seed + t_idx as u64 + 1;
+            let job_recv = job_recv.clone();
+            let result_send = result_send.clone();
+
+            scope.spawn(move |_| {
+                start_pareto_thread(
+                    graph.clone(),
+                    params.clone(),
+                    obj_fn,
+                    rng_seed,
+                    node_ub,
+                    job_recv,
+                    result_send,
+                );
+            });
+        }
+
+        while step < params.num_steps {
             for job in job_sends.iter() {
-                next_batch(job, diff.clone(), burst_length);
+                let start = &front.plans()[rng.gen_range(0..front.len())].0;
+                job.send(ParetoJobPacket {
+                    n_steps: burst_length,
+                    start: Some(start.clone()),
+                    terminate: false,
+                })
+                .unwrap();
+            }
+            let before: Vec<Vec<ScoreValue>> = match verbose {
+                true => front
+                    .plans()
+                    .iter()
+                    .map(|(_, scores)| scores.clone())
+                    .collect(),
+                false => vec![],
+            };
+            let mut changed = false;
+            for _ in 0..n_threads {
+                let packet: ParetoResultPacket = result_recv.recv().unwrap();
+                for (partition, scores) in packet.front.plans {
+                    changed |= front.insert(partition, scores);
+                }
+            }
+            step += (n_threads * burst_length) as u64;
+            if changed && verbose {
+                let after: Vec<&Vec<ScoreValue>> =
+                    front.plans().iter().map(|(_, scores)| scores).collect();
+                let added: Vec<&Vec<ScoreValue>> = after
+                    .iter()
+                    .filter(|scores| !before.contains(scores))
+                    .copied()
+                    .collect();
+                let removed: Vec<&Vec<ScoreValue>> = before
+                    .iter()
+                    .filter(|scores| !after.contains(scores))
+                    .collect();
+                println!(
+                    "{}",
+                    json!({
+                        "step": step,
+                        "front_size": front.len(),
+                        "added": added,
+                        "removed": removed,
+                    })
+                );
             }
         }
 
         // Terminate worker threads.
         for job in job_sends.iter() {
-            stop_opt_thread(job);
+            job.send(ParetoJobPacket {
+                n_steps: 0,
+                start: None,
+                terminate: true,
+            })
+            .unwrap();
         }
-        partition
+        front.into_sorted()
     })
     .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_schedules() {
+        let linear =
+            TemperatureSchedule::from_config(r#"{"schedule": "linear", "start": 2.0, "end": 0.0}"#)
+                .unwrap();
+        assert_eq!(linear.start(), 2.0);
+        assert_eq!(linear.next(2.0, 0.25, None), 1.5);
+        assert_eq!(linear.next(1.5, 1.5, None), 0.0);
+
+        let geometric = TemperatureSchedule::from_config(
+            r#"{"schedule": "geometric", "start": 1.0, "end": 0.01}"#,
+        )
+        .unwrap();
+        assert!((geometric.next(1.0, 0.5, None) - 0.1).abs() < 1e-12);
+
+        let adaptive = TemperatureSchedule::from_config(
+            r#"{"schedule": "adaptive", "start": 1.0, "acceptance": 0.5, "factor": 2.0}"#,
+        )
+        .unwrap();
+        // The target acceptance rate at the halfway point is 0.25.
+        assert_eq!(adaptive.next(1.0, 0.5, Some(0.3)), 0.5);
+        assert_eq!(adaptive.next(1.0, 0.5, Some(0.2)), 2.0);
+        assert_eq!(adaptive.next(1.0, 0.5, None), 1.0);
+
+        for invalid in [
+            r#"{"schedule": "geometric", "start": 1.0, "end": 0.0}"#,
+            r#"{"schedule": "adaptive", "start": 1.0, "acceptance": 1.5}"#,
+            r#"{"schedule": "linear", "start": 1.0}"#,
+            r#"{"schedule": "exponential", "start": 1.0, "end": 0.1}"#,
+        ] {
+            assert!(TemperatureSchedule::from_config(invalid).is_err());
+        }
+    }
+
+    /// A 6x6 grid with four districts and chain parameters.
+    fn grid_fixture() -> (Graph, Partition, RecomParams) {
+        let graph = Graph::rect_grid(6, 6);
+        let assignments: Vec<u32> = (0..36).map(|n| (n / 9) + 1).collect();
+        let partition = Partition::from_assignments(&graph, &assignments).unwrap();
+        let params = RecomParams {
+            min_pop: 7,
+            max_pop: 11,
+            num_steps: 1000,
+            rng_seed: 0,
+            balance_ub: 0,
+            variant: RecomVariant::DistrictPairsRMST,
+            region_weights: None,
+        };
+        (graph, partition, params)
+    }
+
+    /// Scores a plan by its number of cut edges (to be maximized).
+    fn cut_edges(graph: &Graph, partition: &mut Partition) -> ScoreValue {
+        graph
+            .edges
+            .iter()
+            .filter(|edge| partition.assignments[edge.0] != partition.assignments[edge.1])
+            .count() as ScoreValue
+    }
+
+    /// Checks that a plan's districts are consistent and population-balanced.
+    fn assert_valid(partition: &Partition, params: &RecomParams) {
+        for (dist, nodes) in partition.dist_nodes.iter().enumerate() {
+            let pop = partition.dist_pops[dist];
+            assert!(pop >= params.min_pop && pop <= params.max_pop);
+            assert_eq!(pop as usize, nodes.len());
+            assert!(nodes
+                .iter()
+                .all(|&node| partition.assignments[node] == dist as u32));
+        }
+    }
+
+    #[test]
+    fn annealing_improves_objective() {
+        let (graph, partition, params) = grid_fixture();
+        let schedule = TemperatureSchedule::from_config(
+            r#"{"schedule": "geometric", "start": 2.0, "end": 0.01}"#,
+        )
+        .unwrap();
+        let mut best = multi_simulated_annealing(
+            &graph,
+            partition.clone(),
+            &params,
+            2,
+            cut_edges,
+            &schedule,
+            10,
+            false,
+        );
+        assert!(cut_edges(&graph, &mut best) > cut_edges(&graph, &mut partition.clone()));
+        assert_valid(&best, &params);
+    }
+
+    #[test]
+    fn annealing_terminates_without_valid_proposals() {
+        let (graph, partition, mut params) = grid_fixture();
+        // Merged pairs of districts have 18 nodes, so no split leaves both
+        // halves with at least 10 nodes.
+        params.min_pop = 10;
+        params.max_pop = 11;
+        let schedule =
+            TemperatureSchedule::from_config(r#"{"schedule": "linear", "start": 1.0, "end": 0.0}"#)
+                .unwrap();
+        let best = multi_simulated_annealing(
+            &graph,
+            partition.clone(),
+            &params,
+            2,
+            cut_edges,
+            &schedule,
+            10,
+            false,
+        );
+        assert_eq!(best.assignments, partition.assignments);
+    }
+
+    #[test]
+    fn tilted_short_bursts_with_restarts_return_best_plan() {
+        let (graph, partition, params) = grid_fixture();
+        for restart_from in [RestartFrom::Best, RestartFrom::Random { steps: 5 }] {
+            let bursts = ShortBurstsParams::new(10)
+                .with_tilt(0.5)
+                .with_restarts(200, restart_from);
+            let mut result = multi_short_bursts(
+                &graph,
+                partition.clone(),
+                &params,
+                2,
+                cut_edges,
+                &bursts,
+                false,
+            );
+            assert!(result.best_score > cut_edges(&graph, &mut partition.clone()));
+            assert_eq!(result.best_score, cut_edges(&graph, &mut result.best));
+            assert_valid(&result.best, &params);
+            // Rounds are 20 steps long, so the run restarts at steps
+            // 200, 400, ..., 1000.
+            let restarts: Vec<u64> = result
+                .events
+                .iter()
+                .filter(|(_, event)| *event == BurstEvent::Restart(restart_from))
+                .map(|&(step, _)| step)
+                .collect();
+            assert_eq!(restarts, vec![200, 400, 600, 800, 1000]);
+        }
+    }
+
+    #[test]
+    fn tilted_short_bursts_move_to_worse_plans() {
+        let (graph, partition, params) = grid_fixture();
+        // Every plan other than the initial plan is worse than the initial
+        // plan, so bursts rarely find a plan that is at least as good.
+        let initial = &partition.assignments;
+        let obj_fn = |_: &Graph, partition: &mut Partition| {
+            -(partition
+                .assignments
+                .iter()
+                .zip(initial.iter())
+                .filter(|(a, b)| a != b)
+                .count() as ScoreValue)
+        };
+        let bursts = ShortBurstsParams::new(10).with_tilt(1.0);
+        let result = multi_short_bursts(
+            &graph,
+            partition.clone(),
+            &params,
+            2,
+            obj_fn,
+            &bursts,
+            false,
+        );
+        assert_eq!(result.best_score, 0.0);
+        assert!(!result.events.is_empty());
+        assert!(result
+            .events
+            .iter()
+            .all(|(_, event)| *event == BurstEvent::Tilt));
+
+        // Greedy runs never tilt.
+        let result = multi_short_bursts(
+            &graph,
+            partition.clone(),
+            &params,
+            2,
+            obj_fn,
+            &ShortBurstsParams::new(10),
+            false,
+        );
+        assert!(result.events.is_empty());
+    }
+
+    #[test]
+    fn short_bursts_lines_mark_events() {
+        let (_, partition, _) = grid_fixture();
+        let line = short_bursts_line(20, 1.0, &partition, None);
+        assert_eq!(line["step"], 20);
+        assert_eq!(line["assignment"]["0"], partition.assignments[0]);
+        assert!(line.get("tilt").is_none() && line.get("restart").is_none());
+        let line = short_bursts_line(20, 1.0, &partition, Some(BurstEvent::Tilt));
+        assert_eq!(line["tilt"], true);
+        let line = short_bursts_line(
+            20,
+            1.0,
+            &partition,
+            Some(BurstEvent::Restart(RestartFrom::Best)),
+        );
+        assert_eq!(line["restart"], "best");
+        let line = short_bursts_line(
+            20,
+            1.0,
+            &partition,
+            Some(BurstEvent::Restart(RestartFrom::Random { steps: 5 })),
+        );
+        assert_eq!(line["restart"], "random");
+    }
+
+    #[test]
+    fn pareto_front_keeps_non_dominated_plans() {
+        let (_, partition, _) = grid_fixture();
+        let mut front = ParetoFront::new();
+        assert!(front.insert(partition.clone(), vec![1.0, 1.0]));
+        assert!(front.insert(partition.clone(), vec![2.0, 0.0]));
+        // Dominated and duplicate score vectors are rejected.
+        assert!(front.rejects(&[1.0, 0.5]));
+        assert!(!front.insert(partition.clone(), vec![2.0, 0.0]));
+        // A plan that dominates [1, 1] (but not [2, 0]) replaces it.
+        assert!(front.insert(partition.clone(), vec![1.0, 2.0]));
+        assert!(front.insert(partition.clone(), vec![0.0, 3.0]));
+        assert_eq!(front.len(), 3);
+        let scores: Vec<Vec<ScoreValue>> = front
+            .into_sorted()
+            .into_iter()
+            .map(|(_, scores)| scores)
+            .collect();
+        assert_eq!(scores, vec![vec![2.0, 0.0], vec![1.0, 2.0], vec![0.0, 3.0]]);
+    }
+
+    #[test]
+    fn pareto_front_max_len_evicts_crowded_plans() {
+        let (_, partition, _) = grid_fixture();
+        let mut front = ParetoFront::new().with_max_len(3);
+        assert!(front.insert(partition.clone(), vec![0.0, 3.0]));
+        assert!(front.insert(partition.clone(), vec![1.0, 2.0]));
+        assert!(front.insert(partition.clone(), vec![1.1, 1.9]));
+        // [1, 2] is the most crowded plan, so it is evicted.
+        assert!(front.insert(partition.clone(), vec![3.0, 0.0]));
+        assert_eq!(front.len(), 3);
+        // A new plan can be the most crowded plan.
+        assert!(!front.insert(partition.clone(), vec![2.0, 1.0]));
+        let scores: Vec<Vec<ScoreValue>> = front
+            .into_sorted()
+            .into_iter()
+            .map(|(_, scores)| scores)
+            .collect();
+        assert_eq!(scores, vec![vec![3.0, 0.0], vec![1.1, 1.9], vec![0.0, 3.0]]);
+    }
+
+    #[test]
+    fn pareto_short_bursts_return_non_dominated_plans() {
+        let (graph, partition, params) = grid_fixture();
+        // Trade off cut edges against the population of the first district.
+        let obj_fn = |graph: &Graph, partition: &mut Partition| {
+            vec![
+                cut_edges(graph, partition),
+                partition.dist_pops[0] as ScoreValue,
+            ]
+        };
+        let front = multi_pareto_short_bursts(
+            &graph,
+            partition.clone(),
+            &params,
+            2,
+            obj_fn,
+            10,
+            None,
+            false,
+        );
+        assert!(!front.is_empty());
+        for (idx, (plan, scores)) in front.iter().enumerate() {
+            assert_valid(plan, &params);
+            assert_eq!(&obj_fn(&graph, &mut plan.clone()), scores);
+            for (other_idx, (_, other)) in front.iter().enumerate() {
+                assert!(idx == other_idx || !dominates(other, scores));
+            }
+        }
+
+        let capped =
+            multi_pareto_short_bursts(&graph, partition, &params, 2, obj_fn, 10, Some(2), false);
+        assert!(!capped.is_empty() && capped.len() <= 2);
+    }
+}
@@ -0,0 +1,533 @@
+//! Replays stored chain output through statistics writers.
+//!
+//! Long chain runs are often stored in a compact, assignment-only format
+//! (BEN, pcompress, or canonical JSONL). The replay engine decodes such
+//! output, reconstructs the [Partition] at each step, and feeds the
+//! reconstructed chain to an arbitrary [StatsWriter], so that new
+//! statistics can be computed without re-running the chain.
+//!
+//! Stored output does not record *why* the chain self-looped, so all
+//! replayed self-loops are reported as [SelfLoopReason::Unknown].
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::{SelfLoopCounts, SelfLoopReason, StatsWriter};
+use ben::decode::BenDecoder;
+use serde_json::Value;
+use snafu::prelude::*;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+
+#[derive(Debug, PartialEq, Snafu)]
+pub enum ReplayError {
+    #[snafu(display("I/O error: {message}"))]
+    ErrIO { message: String },
+    #[snafu(display("Chain output contains no plans"))]
+    ErrEmptyChain,
+    #[snafu(display("Chain output ends in the middle of a record"))]
+    ErrTruncated,
+    #[snafu(display("Could not parse line {line} of canonical JSONL"))]
+    ErrCanonicalParse { line: usize },
+    #[snafu(display("Invalid initial partition: {reason}"))]
+    ErrInitialPartition { reason: String },
+    #[snafu(display(
+        "Mismatch: graph has {graph_nodes} nodes, chain output has {record_nodes} nodes"
+    ))]
+    ErrNodeCountMismatch {
+        graph_nodes: usize,
+        record_nodes: usize,
+    },
+    #[snafu(display("District label {label} out of range at step {step}"))]
+    ErrDistrictLabel { step: u64, label: usize },
+    #[snafu(display("Step {step} changes {changed} districts (expected 2)"))]
+    ErrNotRecomStep { step: u64, changed: usize },
+}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> ReplayError {
+        ReplayError::ErrIO {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Chain output formats supported by the replay engine.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ReplayFormat {
+    /// BEN output (as written by [crate::stats::BenWriter]).
+    Ben,
+    /// pcompress output (as written by [crate::stats::PcompressWriter]).
+    Pcompress,
+    /// Canonical JSONL output (as written by [crate::stats::CanonicalWriter]).
+    Canonical,
+}
+
+/// A single decoded record of stored chain output.
+enum ReplayRecord {
+    /// A full 1-indexed assignment vector, repeated `count` times.
+    Assignment { assignment: Vec<u32>, count: usize },
+    /// The nodes in each district changed by a step, indexed by
+    /// (0-indexed) district label. An empty diff is a self-loop.
+    Diff(Vec<Vec<usize>>),
+}
+
+type RecordIter<'a> = Box<dyn Iterator<Item = Result<ReplayRecord, ReplayError>> + 'a>;
+
+/// Decodes pcompress diffs.
+///
+/// Each diff is a sequence of big-endian `u16` node indices. Districts
+/// are separated by a `u16::MAX - 1` marker followed by a single byte
+/// (the number of districts to skip), and each diff is terminated by
+/// a `u16::MAX` marker.
+struct PcompressRecords<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> Iterator for PcompressRecords<R> {
+    type Item = Result<ReplayRecord, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut diff = vec![Vec::<usize>::new()];
+        let mut district = 0;
+        let mut started = false;
+        loop {
+            let mut buf = [0u8; 2];
+            match self.reader.read_exact(&mut buf) {
+                Ok(()) => (),
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && !started => return None,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return Some(Err(ReplayError::ErrTruncated))
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+            started = true;
+            let state = u16::from_be_bytes(buf);
+            if state == u16::MAX {
+                return Some(Ok(ReplayRecord::Diff(diff)));
+            } else if state == u16::MAX - 1 {
+                let mut skip = [0u8; 1];
+                if let Err(e) = self.reader.read_exact(&mut skip) {
+                    return Some(Err(match e.kind() {
+                        ErrorKind::UnexpectedEof => ReplayError::ErrTruncated,
+                        _ => e.into(),
+                    }));
+                }
+                district += skip[0] as usize;
+                if diff.len() <= district {
+                    diff.resize(district + 1, vec![]);
+                }
+            } else {
+                diff[district].push(state as usize);
+            }
+        }
+    }
+}
+
+/// Returns an iterator over the records in BEN output.
+fn ben_records<'a>(reader: impl Read + 'a) -> Result<RecordIter<'a>, ReplayError> {
+    let decoder = BenDecoder::new(reader).map_err(|e| ReplayError::ErrIO {
+        message: e.to_string(),
+    })?;
+    Ok(Box::new(decoder.map(|record| {
+        let (assignment, count) = record?;
+        Ok(ReplayRecord::Assignment {
+            assignment: assignment.into_iter().map(|a| a as u32).collect(),
+            count: count as usize,
+        })
+    })))
+}
+
+/// Returns an iterator over the records in canonical JSONL output.
+fn canonical_records<'a>(reader: impl Read + 'a) -> RecordIter<'a> {
+    Box::new(
+        BufReader::new(reader)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(contents) if contents.trim().is_empty()))
+            .map(|(idx, line)| {
+                let data: Value = serde_json::from_str(&line?)
+                    .map_err(|_| ReplayError::ErrCanonicalParse { line: idx + 1 })?;
+                let assignment = data["assignment"]
+                    .as_array()
+                    .ok_or(ReplayError::ErrCanonicalParse { line: idx + 1 })?
+                    .iter()
+                    .map(|a| a.as_u64().map(|a| a as u32))
+                    .collect::<Option<Vec<u32>>>()
+                    .ok_or(ReplayError::ErrCanonicalParse { line: idx + 1 })?;
+                Ok(ReplayRecord::Assignment {
+                    assignment,
+                    count: 1,
+                })
+            }),
+    )
+}
+
+/// Builds a ReCom proposal for districts `a` and `b` from their new node sets.
+fn proposal_from_nodes(
+    graph: &Graph,
+    a: usize,
+    b: usize,
+    a_nodes: Vec<usize>,
+    b_nodes: Vec<usize>,
+) -> RecomProposal {
+    RecomProposal {
+        a_label: a,
+        b_label: b,
+        a_pop: a_nodes.iter().map(|&n| graph.pops[n]).sum(),
+        b_pop: b_nodes.iter().map(|&n| graph.pops[n]).sum(),
+        a_nodes,
+        b_nodes,
+    }
+}
+
+/// Reconstructs the chain state from stored output and drives a writer.
+struct Replayer<'a> {
+    graph: &'a Graph,
+    writer: Box<dyn StatsWriter>,
+    /// The current chain state (`None` before the initial plan is read).
+    partition: Option<Partition>,
+    /// The step count of the last accepted proposal.
+    step: u64,
+    /// The number of self-loops since the last accepted proposal.
+    loops: usize,
+}
+
+impl<'a> Replayer<'a> {
+    /// Initializes the chain state from the first record.
+    fn init(&mut self, record: ReplayRecord) -> Result<(), ReplayError> {
+        let (assignment, count) = match record {
+            ReplayRecord::Assignment { assignment, count } => (assignment, count),
+            ReplayRecord::Diff(diff) => {
+                let mut assignment = vec![0; self.graph.neighbors.len()];
+                for (dist, nodes) in diff.iter().enumerate() {
+                    for &node in nodes.iter() {
+                        if node >= assignment.len() {
+                            return Err(ReplayError::ErrNodeCountMismatch {
+                                graph_nodes: assignment.len(),
+                                record_nodes: node + 1,
+                            });
+                        }
+                        assignment[node] = dist as u32 + 1;
+                    }
+                }
+                (assignment, 1)
+            }
+        };
+        let partition = Partition::from_assignments(self.graph, &assignment).map_err(|e| {
+            ReplayError::ErrInitialPartition {
+                reason: e.to_string(),
+            }
+        })?;
+        self.writer.init(self.graph, &partition)?;
+        self.partition = Some(partition);
+        self.loops = count.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Applies an accepted proposal and passes it to the writer.
+    fn accept(&mut self, proposal: RecomProposal) -> Result<(), ReplayError> {
+        let mut counts = SelfLoopCounts::default();
+        for _ in 0..self.loops {
+            counts.inc(SelfLoopReason::Unknown);
+        }
+        self.step += self.loops as u64 + 1;
+        self.loops = 0;
+        let partition = self.partition.as_mut().unwrap();
+        partition.update(&proposal);
+        self.writer
+            .step(self.step, self.graph, partition, &proposal, &counts)?;
+        Ok(())
+    }
+
+    /// Finds the ReCom proposal (if any) that takes the current partition
+    /// to a new 1-indexed assignment vector.
+    fn diff_assignment(&self, assignment: &[u32]) -> Result<Option<RecomProposal>, ReplayError> {
+        let partition = self.partition.as_ref().unwrap();
+        let step = self.step + self.loops as u64 + 1;
+        if assignment.len() != partition.assignments.len() {
+            return Err(ReplayError::ErrNodeCountMismatch {
+                graph_nodes: partition.assignments.len(),
+                record_nodes: assignment.len(),
+            });
+        }
+        let mut changed = BTreeSet::<usize>::new();
+        for (&old, &new) in partition.assignments.iter().zip(assignment.iter()) {
+            if new == 0 || new > partition.num_dists {
+                return Err(ReplayError::ErrDistrictLabel {
+                    step,
+                    label: new as usize,
+                });
+            }
+            if old != new - 1 {
+                changed.insert(old as usize);
+                changed.insert(new as usize - 1);
+            }
+        }
+        match changed.len() {
+            0 => Ok(None),
+            2 => {
+                let mut labels = changed.into_iter();
+                let (a, b) = (labels.next().unwrap(), labels.next().unwrap());
+                let mut a_nodes = vec![];
+                let mut b_nodes = vec![];
+                for (node, &dist) in assignment.iter().enumerate() {
+                    if dist as usize - 1 == a {
+                        a_nodes.push(node);
+                    } else if dist as usize - 1 == b {
+                        b_nodes.push(node);
+                    }
+                }
+                Ok(Some(proposal_from_nodes(
+                    self.graph, a, b, a_nodes, b_nodes,
+                )))
+            }
+            changed => Err(ReplayError::ErrNotRecomStep { step, changed }),
+        }
+    }
+
+    /// Processes a single record after initialization.
+    fn next(&mut self, record: ReplayRecord) -> Result<(), ReplayError> {
+        match record {
+            ReplayRecord::Assignment { assignment, count } => {
+                match self.diff_assignment(&assignment)? {
+                    Some(proposal) => {
+                        self.accept(proposal)?;
+                        self.loops = count.saturating_sub(1);
+                    }
+                    None => self.loops += count,
+                }
+            }
+            ReplayRecord::Diff(mut diff) => {
+                let step = self.step + self.loops as u64 + 1;
+                let num_dists = self.partition.as_ref().unwrap().num_dists as usize;
+                let labels: Vec<usize> = (0..diff.len()).filter(|&d| !diff[d].is_empty()).collect();
+                if let Some(&label) = labels.iter().find(|&&d| d >= num_dists) {
+                    return Err(ReplayError::ErrDistrictLabel { step, label });
+                }
+                match labels.len() {
+                    0 => self.loops += 1,
+                    2 => {
+                        let (a, b) = (labels[0], labels[1]);
+                        let a_nodes = std::mem::take(&mut diff[a]);
+                        let b_nodes = std::mem::take(&mut diff[b]);
+                        let proposal = proposal_from_nodes(self.graph, a, b, a_nodes, b_nodes);
+                        self.accept(proposal)?;
+                    }
+                    changed => return Err(ReplayError::ErrNotRecomStep { step, changed }),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replays stored chain output through a statistics writer.
+///
+/// Returns the number of steps replayed (including self-loops that
+/// precede an accepted proposal), or a [ReplayError] if the output
+/// cannot be decoded or is inconsistent with `graph`.
+///
+/// # Arguments
+///
+/// * `graph` - The graph the chain was run on.
+/// * `reader` - The stored chain output.
+/// * `format` - The format of the stored chain output.
+/// * `writer` - The statistics writer to replay the chain through.
+pub fn replay_chain(
+    graph: &Graph,
+    reader: impl Read,
+    format: ReplayFormat,
+    writer: Box<dyn StatsWriter>,
+) -> Result<u64, ReplayError> {
+    let mut records: RecordIter = match format {
+        ReplayFormat::Ben => ben_records(reader)?,
+        ReplayFormat::Pcompress => Box::new(PcompressRecords { reader }),
+        ReplayFormat::Canonical => canonical_records(reader),
+    };
+    let mut replayer = Replayer {
+        graph,
+        writer,
+        partition: None,
+        step: 0,
+        loops: 0,
+    };
+    match records.next() {
+        Some(record) => replayer.init(record?)?,
+        None => return Err(ReplayError::ErrEmptyChain),
+    }
+    for record in records {
+        replayer.next(record?)?;
+    }
+    replayer.writer.close()?;
+    Ok(replayer.step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_2x2, SharedBuffer};
+    use crate::stats::{BenWriter, CanonicalWriter, PcompressWriter};
+    use std::io::{Result as IOResult, Write};
+    use std::sync::{Arc, Mutex};
+
+    /// A step observed by a writer: (step, assignments, self-loops, labels, nodes).
+    type ObservedStep = (
+        u64,
+        Vec<u32>,
+        usize,
+        (usize, usize),
+        (Vec<usize>, Vec<usize>),
+    );
+
+    /// A writer that records every step it observes.
+    struct RecordingWriter {
+        steps: Arc<Mutex<Vec<ObservedStep>>>,
+    }
+
+    impl StatsWriter for RecordingWriter {
+        fn init(&mut self, _graph: &Graph, partition: &Partition) -> IOResult<()> {
+            self.steps.lock().unwrap().push((
+                0,
+                partition.assignments.clone(),
+                0,
+                (0, 0),
+                (vec![], vec![]),
+            ));
+            Ok(())
+        }
+
+        fn step(
+            &mut self,
+            step: u64,
+            _graph: &Graph,
+            partition: &Partition,
+            proposal: &RecomProposal,
+            counts: &SelfLoopCounts,
+        ) -> IOResult<()> {
+            let mut a_nodes = proposal.a_nodes.clone();
+            let mut b_nodes = proposal.b_nodes.clone();
+            a_nodes.sort();
+            b_nodes.sort();
+            self.steps.lock().unwrap().push((
+                step,
+                partition.assignments.clone(),
+                counts.sum(),
+                (proposal.a_label, proposal.b_label),
+                (a_nodes, b_nodes),
+            ));
+            Ok(())
+        }
+
+        fn close(&mut self) -> IOResult<()> {
+            Ok(())
+        }
+    }
+
+    /// Feeds a short synthetic chain on a 2x2 grid to a writer:
+    /// two accepted proposals, with self-loops before each.
+    fn synthetic_chain(writer: &mut dyn StatsWriter) {
+        let (grid, mut partition, _) = grid_2x2();
+        writer.init(&grid, &partition).unwrap();
+        let proposals = [(3, vec![0, 2], vec![1, 3]), (5, vec![0, 1], vec![2, 3])];
+        let mut step = 0;
+        for (loops, a_nodes, b_nodes) in proposals.iter() {
+            let mut counts = SelfLoopCounts::default();
+            for _ in 0..*loops {
+                counts.inc(SelfLoopReason::NoSplit);
+            }
+            step += *loops as u64 + 1;
+            let proposal = proposal_from_nodes(&grid, 0, 1, a_nodes.clone(), b_nodes.clone());
+            partition.update(&proposal);
+            writer
+                .step(step, &grid, &partition, &proposal, &counts)
+                .unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    /// Encodes the synthetic chain, replays it, and checks that the replayed
+    /// chain matches the original.
+    fn round_trip(format: ReplayFormat) {
+        let expected = Arc::new(Mutex::new(vec![]));
+        let buf = SharedBuffer::default();
+        let output: Box<dyn Write + Send> = Box::new(buf.clone());
+        let mut encoder: Box<dyn StatsWriter> = match format {
+            ReplayFormat::Ben => Box::new(BenWriter::new(output)),
+            ReplayFormat::Pcompress => Box::new(PcompressWriter::new(output)),
+            ReplayFormat::Canonical => Box::new(CanonicalWriter::new(output)),
+        };
+        synthetic_chain(encoder.as_mut());
+        synthetic_chain(&mut RecordingWriter {
+            steps: expected.clone(),
+        });
+
+        let replayed = Arc::new(Mutex::new(vec![]));
+        let bytes = buf.bytes();
+        let grid = Graph::rect_grid(2, 2);
+        let writer = Box::new(RecordingWriter {
+            steps: replayed.clone(),
+        });
+        assert_eq!(
+            replay_chain(&grid, bytes.as_slice(), format, writer).unwrap(),
+            10
+        );
+        assert_eq!(*replayed.lock().unwrap(), *expected.lock().unwrap());
+    }
+
+    #[test]
+    fn replay_ben_round_trip() {
+        round_trip(ReplayFormat::Ben);
+    }
+
+    #[test]
+    fn replay_pcompress_round_trip() {
+        round_trip(ReplayFormat::Pcompress);
+    }
+
+    #[test]
+    fn replay_canonical_round_trip() {
+        round_trip(ReplayFormat::Canonical);
+    }
+
+    #[test]
+    fn replay_empty_chain() {
+        let grid = Graph::rect_grid(2, 2);
+        let writer = Box::new(RecordingWriter {
+            steps: Arc::new(Mutex::new(vec![])),
+        });
+        assert_eq!(
+            replay_chain(&grid, "".as_bytes(), ReplayFormat::Canonical, writer).unwrap_err(),
+            ReplayError::ErrEmptyChain
+        );
+    }
+
+    #[test]
+    fn replay_not_recom_step() {
+        let grid = Graph::rect_grid(3, 1);
+        let chain = "{\"assignment\": [1, 2, 3], \"sample\": 1}\n\
+                     {\"assignment\": [2, 3, 1], \"sample\": 2}\n";
+        let writer = Box::new(RecordingWriter {
+            steps: Arc::new(Mutex::new(vec![])),
+        });
+        assert_eq!(
+            replay_chain(&grid, chain.as_bytes(), ReplayFormat::Canonical, writer).unwrap_err(),
+            ReplayError::ErrNotRecomStep {
+                step: 1,
+                changed: 3
+            }
+        );
+    }
+
+    #[test]
+    fn replay_truncated_pcompress() {
+        let grid = Graph::rect_grid(2, 2);
+        let writer = Box::new(RecordingWriter {
+            steps: Arc::new(Mutex::new(vec![])),
+        });
+        let chain: &[u8] = &[0, 0, 0, 1];
+        assert_eq!(
+            replay_chain(&grid, chain, ReplayFormat::Pcompress, writer).unwrap_err(),
+            ReplayError::ErrTruncated
+        );
+    }
+}
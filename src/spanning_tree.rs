@@ -286,11 +286,10 @@ mod rmst {
             rng.fill(&mut self.weights[..]);
             for (region_col, region_weight) in self.region_weights.iter() {
                 for (idx, edge) in graph.edges.iter().enumerate() {
-                    if graph.attr[region_col][edge.0] == "null"
-                        || graph.attr[region_col][edge.0] == ""
-                        || graph.attr[region_col][edge.1] == "null"
-                        || graph.attr[region_col][edge.1] == ""
-                        || graph.attr[region_col][edge.0] != graph.attr[region_col][edge.1]
+                    let regions = &graph.attr[region_col];
+                    if regions.is_missing(edge.0)
+                        || regions.is_missing(edge.1)
+                        || !regions.same_value(edge.0, edge.1)
                     {
                         self.weights[idx] += region_weight;
                     }
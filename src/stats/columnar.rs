@@ -0,0 +1,402 @@
+//! Columnar (Parquet and Arrow IPC) statistics writers.
+use crate::attr::{AttrColumn, AttrSum};
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::{proposal_sums, SelfLoopCounts, SelfLoopReason, StatsWriter};
+use arrow::array::{ArrayRef, Float64Builder, Int64Builder, UInt32Builder, UInt64Builder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use std::io::{Error, Result, Write};
+use std::sync::Arc;
+
+/// Column builder and type for district populations (which depend on [Pop]).
+#[cfg(not(feature = "pop64"))]
+type PopBuilder = UInt32Builder;
+#[cfg(not(feature = "pop64"))]
+const POP_TYPE: DataType = DataType::UInt32;
+#[cfg(feature = "pop64")]
+type PopBuilder = UInt64Builder;
+#[cfg(feature = "pop64")]
+const POP_TYPE: DataType = DataType::UInt64;
+
+/// The number of steps buffered in memory before a record batch is written.
+const BATCH_ROWS: usize = 8192;
+
+/// Supported columnar output formats.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColumnarFormat {
+    /// A single Parquet file.
+    Parquet,
+    /// An Arrow IPC stream.
+    ArrowIPC,
+}
+
+/// An open columnar output stream (created once the schema is known).
+enum ColumnarSink {
+    Parquet(ArrowWriter<Box<dyn Write + Send>>),
+    ArrowIPC(StreamWriter<Box<dyn Write + Send>>),
+}
+
+/// A column builder for attribute sums (typed by the attribute column).
+enum SumBuilder {
+    Int(Int64Builder),
+    Float(Float64Builder),
+}
+
+impl SumBuilder {
+    fn new(data_type: &DataType) -> SumBuilder {
+        match data_type {
+            DataType::Float64 => SumBuilder::Float(Float64Builder::with_capacity(BATCH_ROWS)),
+            _ => SumBuilder::Int(Int64Builder::with_capacity(BATCH_ROWS)),
+        }
+    }
+
+    fn append(&mut self, sum: AttrSum) {
+        match (self, sum) {
+            (SumBuilder::Int(builder), AttrSum::Int(sum)) => builder.append_value(sum),
+            (SumBuilder::Float(builder), sum) => builder.append_value(sum.as_f64()),
+            (SumBuilder::Int(_), AttrSum::Float(_)) => {
+                panic!("Float sum in an integer attribute column.")
+            }
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            SumBuilder::Int(builder) => Arc::new(builder.finish()),
+            SumBuilder::Float(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Writes chain statistics in a columnar format (Parquet or Arrow IPC).
+/// Each accepted step in the chain is a row; no statistics are saved about
+/// the initial partition.
+///
+/// Rows in the output contain the following columns:
+///   * `step` - The step count at the accepted proposal (including self-loops).
+///   * `non_adjacent` - The number of self-loops due to non-adjacency.
+///   * `no_split` - The number of self-loops due to the lack of an ε-balanced split.
+///   * `seam_length` - The number of self-loops due to seam length rejection
+///     (Reversible ReCom only).
+///   * `unknown` - The number of self-loops with no recorded reason
+///     (replayed chains only).
+///   * `a_label` - The label of the `a`-district in the proposal.
+///   * `b_label` - The label of the `b`-district in the proposal.
+///   * `a_pop` - The population of the new `a`-district
+///     (a 64-bit integer with the `pop64` feature, 32-bit otherwise).
+///   * `b_pop` - The population of the new `b`-district.
+///   * `a_sum_<col>`, `b_sum_<col>` - The sums over each numeric attribute
+///     column for the new `a`- and `b`-districts (sorted by column name).
+///     Sums over integer columns are 64-bit integers; sums over float
+///     columns are 64-bit floats.
+pub struct ColumnarWriter {
+    /// The output format.
+    format: ColumnarFormat,
+    /// The output stream (moved into `sink` on initialization).
+    output: Option<Box<dyn Write + Send>>,
+    /// The open columnar output stream.
+    sink: Option<ColumnarSink>,
+    /// The schema of the output (determined by the graph's attributes).
+    schema: Option<SchemaRef>,
+    /// The attribute columns to sum over (with their sum types), in output order.
+    sum_cols: Vec<(String, DataType)>,
+    /// Column builders for the current batch.
+    step: UInt64Builder,
+    non_adjacent: UInt64Builder,
+    no_split: UInt64Builder,
+    seam_length: UInt64Builder,
+    unknown: UInt64Builder,
+    a_label: UInt32Builder,
+    b_label: UInt32Builder,
+    a_pop: PopBuilder,
+    b_pop: PopBuilder,
+    a_sums: Vec<SumBuilder>,
+    b_sums: Vec<SumBuilder>,
+    /// The number of rows in the current batch.
+    rows: usize,
+}
+
+/// Wraps Arrow/Parquet errors in standard I/O errors.
+fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> Error {
+    Error::other(err)
+}
+
+impl ColumnarWriter {
+    pub fn new(format: ColumnarFormat, output: Box<dyn Write + Send>) -> ColumnarWriter {
+        ColumnarWriter {
+            format,
+            output: Some(output),
+            sink: None,
+            schema: None,
+            sum_cols: Vec::new(),
+            step: UInt64Builder::with_capacity(BATCH_ROWS),
+            non_adjacent: UInt64Builder::with_capacity(BATCH_ROWS),
+            no_split: UInt64Builder::with_capacity(BATCH_ROWS),
+            seam_length: UInt64Builder::with_capacity(BATCH_ROWS),
+            unknown: UInt64Builder::with_capacity(BATCH_ROWS),
+            a_label: UInt32Builder::with_capacity(BATCH_ROWS),
+            b_label: UInt32Builder::with_capacity(BATCH_ROWS),
+            a_pop: PopBuilder::with_capacity(BATCH_ROWS),
+            b_pop: PopBuilder::with_capacity(BATCH_ROWS),
+            a_sums: Vec::new(),
+            b_sums: Vec::new(),
+            rows: 0,
+        }
+    }
+
+    /// Builds the output schema for a set of sum columns.
+    fn build_schema(sum_cols: &[(String, DataType)]) -> Schema {
+        let mut fields = vec![
+            Field::new("step", DataType::UInt64, false),
+            Field::new("non_adjacent", DataType::UInt64, false),
+            Field::new("no_split", DataType::UInt64, false),
+            Field::new("seam_length", DataType::UInt64, false),
+            Field::new("unknown", DataType::UInt64, false),
+            Field::new("a_label", DataType::UInt32, false),
+            Field::new("b_label", DataType::UInt32, false),
+            Field::new("a_pop", POP_TYPE, false),
+            Field::new("b_pop", POP_TYPE, false),
+        ];
+        for (col, data_type) in sum_cols.iter() {
+            fields.push(Field::new(
+                format!("a_sum_{}", col),
+                data_type.clone(),
+                false,
+            ));
+            fields.push(Field::new(
+                format!("b_sum_{}", col),
+                data_type.clone(),
+                false,
+            ));
+        }
+        Schema::new(fields)
+    }
+
+    /// Writes the buffered rows (if any) to the output as a record batch.
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.step.finish()),
+            Arc::new(self.non_adjacent.finish()),
+            Arc::new(self.no_split.finish()),
+            Arc::new(self.seam_length.finish()),
+            Arc::new(self.unknown.finish()),
+            Arc::new(self.a_label.finish()),
+            Arc::new(self.b_label.finish()),
+            Arc::new(self.a_pop.finish()),
+            Arc::new(self.b_pop.finish()),
+        ];
+        for (a_sums, b_sums) in self.a_sums.iter_mut().zip(self.b_sums.iter_mut()) {
+            columns.push(a_sums.finish());
+            columns.push(b_sums.finish());
+        }
+        let batch =
+            RecordBatch::try_new(self.schema.clone().unwrap(), columns).map_err(to_io_error)?;
+        match self.sink.as_mut() {
+            Some(ColumnarSink::Parquet(writer)) => writer.write(&batch).map_err(to_io_error)?,
+            Some(ColumnarSink::ArrowIPC(writer)) => writer.write(&batch).map_err(to_io_error)?,
+            None => panic!("Columnar writer must be initialized before writing."),
+        }
+        self.rows = 0;
+        Ok(())
+    }
+}
+
+impl StatsWriter for ColumnarWriter {
+    fn init(&mut self, graph: &Graph, _partition: &Partition) -> Result<()> {
+        let mut sum_cols: Vec<(String, DataType)> = graph
+            .attr
+            .iter()
+            .filter_map(|(col, values)| match values {
+                AttrColumn::Int(_) => Some((col.clone(), DataType::Int64)),
+                AttrColumn::Float(_) => Some((col.clone(), DataType::Float64)),
+                AttrColumn::Categorical { .. } => None,
+            })
+            .collect();
+        sum_cols.sort_by(|a, b| a.0.cmp(&b.0));
+        let schema = Arc::new(ColumnarWriter::build_schema(&sum_cols));
+        let output = self
+            .output
+            .take()
+            .expect("Columnar writer must be initialized exactly once.");
+        self.sink = Some(match self.format {
+            ColumnarFormat::Parquet => ColumnarSink::Parquet(
+                ArrowWriter::try_new(output, schema.clone(), None).map_err(to_io_error)?,
+            ),
+            ColumnarFormat::ArrowIPC => {
+                ColumnarSink::ArrowIPC(StreamWriter::try_new(output, &schema).map_err(to_io_error)?)
+            }
+        });
+        self.a_sums = sum_cols
+            .iter()
+            .map(|(_, data_type)| SumBuilder::new(data_type))
+            .collect();
+        self.b_sums = sum_cols
+            .iter()
+            .map(|(_, data_type)| SumBuilder::new(data_type))
+            .collect();
+        self.sum_cols = sum_cols;
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        _partition: &Partition,
+        proposal: &RecomProposal,
+        counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        self.step.append_value(step);
+        self.non_adjacent
+            .append_value(counts.get(SelfLoopReason::NonAdjacent) as u64);
+        self.no_split
+            .append_value(counts.get(SelfLoopReason::NoSplit) as u64);
+        self.seam_length
+            .append_value(counts.get(SelfLoopReason::SeamLength) as u64);
+        self.unknown
+            .append_value(counts.get(SelfLoopReason::Unknown) as u64);
+        self.a_label.append_value(proposal.a_label as u32);
+        self.b_label.append_value(proposal.b_label as u32);
+        self.a_pop.append_value(proposal.a_pop);
+        self.b_pop.append_value(proposal.b_pop);
+        let sums = proposal_sums(graph, proposal);
+        for (idx, (col, _)) in self.sum_cols.iter().enumerate() {
+            let (a_sum, b_sum) = sums[col];
+            self.a_sums[idx].append(a_sum);
+            self.b_sums[idx].append(b_sum);
+        }
+        self.rows += 1;
+        if self.rows >= BATCH_ROWS {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.flush_batch()?;
+        let mut output = match self.sink.take() {
+            Some(ColumnarSink::Parquet(writer)) => writer.into_inner().map_err(to_io_error)?,
+            Some(ColumnarSink::ArrowIPC(writer)) => writer.into_inner().map_err(to_io_error)?,
+            None => return Ok(()),
+        };
+        output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_2x2, SharedBuffer};
+    use arrow::array::{Array, Float64Array, Int64Array, UInt64Array};
+    use arrow::ipc::reader::StreamReader;
+
+    /// Runs a writer over two steps on a 2x2 grid with `votes` (integer),
+    /// `share` (float), and `county` (categorical) attributes.
+    fn write_steps(format: ColumnarFormat) -> Vec<u8> {
+        let (mut grid, mut partition, proposal) = grid_2x2();
+        grid.attr
+            .insert("votes".to_string(), AttrColumn::parse(["1", "2", "3", "4"]));
+        grid.attr.insert(
+            "share".to_string(),
+            AttrColumn::parse(["0.5", "0.25", "1", "0"]),
+        );
+        grid.attr.insert(
+            "county".to_string(),
+            AttrColumn::parse(["a", "a", "b", "b"]),
+        );
+        let buf = SharedBuffer::default();
+        let mut writer = ColumnarWriter::new(format, Box::new(buf.clone()));
+        writer.init(&grid, &partition).unwrap();
+
+        let mut counts = SelfLoopCounts::default();
+        counts.inc(SelfLoopReason::NoSplit);
+        counts.inc(SelfLoopReason::Unknown);
+        partition.update(&proposal);
+        writer
+            .step(2, &grid, &partition, &proposal, &counts)
+            .unwrap();
+        partition.update(&proposal);
+        writer
+            .step(3, &grid, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        writer.close().unwrap();
+        buf.bytes()
+    }
+
+    #[test]
+    fn arrow_ipc_round_trip() {
+        let bytes = write_steps(ColumnarFormat::ArrowIPC);
+        let reader = StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), 13);
+
+        let steps = batch
+            .column_by_name("step")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(steps.values(), &[2, 3]);
+        let no_split = batch
+            .column_by_name("no_split")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(no_split.values(), &[1, 0]);
+        let unknown = batch
+            .column_by_name("unknown")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(unknown.values(), &[1, 0]);
+        let a_pop = batch.column_by_name("a_pop").unwrap();
+        assert_eq!(a_pop.data_type(), &POP_TYPE);
+        let a_pop = arrow::compute::cast(a_pop, &DataType::UInt64).unwrap();
+        let a_pop = a_pop.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(a_pop.values(), &[2, 2]);
+        let a_votes = batch
+            .column_by_name("a_sum_votes")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        let b_votes = batch
+            .column_by_name("b_sum_votes")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(a_votes.values(), &[4, 4]);
+        assert_eq!(b_votes.values(), &[6, 6]);
+        assert_eq!(a_votes.null_count(), 0);
+        let a_share = batch
+            .column_by_name("a_sum_share")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(a_share.values(), &[1.5, 1.5]);
+        assert!(batch.column_by_name("a_sum_county").is_none());
+    }
+
+    #[test]
+    fn parquet_magic_bytes() {
+        let bytes = write_steps(ColumnarFormat::Parquet);
+        assert!(bytes.len() > 8);
+        assert_eq!(&bytes[..4], b"PAR1");
+        assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+    }
+}
@@ -0,0 +1,204 @@
+//! Geometric compactness scores.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::Updater;
+use serde_json::{json, Value};
+use std::f64::consts::PI;
+
+/// Returns the Polsby-Popper score of a district (the ratio of its area
+/// to the area of a circle with the same perimeter).
+pub fn polsby_popper(area: f64, perimeter: f64) -> f64 {
+    4.0 * PI * area / (perimeter * perimeter)
+}
+
+/// Returns the Schwartzberg score of a district (the ratio of the
+/// circumference of a circle with the same area to its perimeter).
+/// Like the Polsby-Popper score, higher scores are more compact.
+pub fn schwartzberg(area: f64, perimeter: f64) -> f64 {
+    2.0 * (PI * area).sqrt() / perimeter
+}
+
+/// District areas and perimeters, along with the total length of the
+/// boundaries between districts.
+///
+/// Node areas and perimeters are node attribute columns; shared boundary
+/// lengths are an edge attribute column. A district's perimeter is the sum
+/// of its nodes' perimeters less the boundaries shared between its nodes
+/// (which are counted twice in the sum).
+pub struct Compactness {
+    /// The node area column.
+    area_col: String,
+    /// The node perimeter column.
+    perimeter_col: String,
+    /// The edge shared boundary length column.
+    shared_perim_col: String,
+    /// The shared boundary length between each node and its neighbors
+    /// (in the order of the graph's adjacency lists).
+    neighbor_shared: Vec<Vec<f64>>,
+    /// The length of the graph's exterior boundary.
+    exterior: f64,
+    /// The area of each district.
+    areas: Vec<f64>,
+    /// The perimeter of each district.
+    perimeters: Vec<f64>,
+}
+
+impl Compactness {
+    pub fn new(area_col: &str, perimeter_col: &str, shared_perim_col: &str) -> Compactness {
+        Compactness {
+            area_col: area_col.to_string(),
+            perimeter_col: perimeter_col.to_string(),
+            shared_perim_col: shared_perim_col.to_string(),
+            neighbor_shared: vec![],
+            exterior: 0.0,
+            areas: vec![],
+            perimeters: vec![],
+        }
+    }
+
+    /// Returns the area of each district.
+    pub fn areas(&self) -> &[f64] {
+        &self.areas
+    }
+
+    /// Returns the perimeter of each district.
+    pub fn perimeters(&self) -> &[f64] {
+        &self.perimeters
+    }
+
+    /// Returns the Polsby-Popper score of each district.
+    pub fn polsby_popper(&self) -> Vec<f64> {
+        self.areas
+            .iter()
+            .zip(self.perimeters.iter())
+            .map(|(&area, &perimeter)| polsby_popper(area, perimeter))
+            .collect()
+    }
+
+    /// Returns the Schwartzberg score of each district.
+    pub fn schwartzberg(&self) -> Vec<f64> {
+        self.areas
+            .iter()
+            .zip(self.perimeters.iter())
+            .map(|(&area, &perimeter)| schwartzberg(area, perimeter))
+            .collect()
+    }
+
+    /// Returns the total length of the boundaries between districts.
+    pub fn boundary_length(&self) -> f64 {
+        (self.perimeters.iter().sum::<f64>() - self.exterior) / 2.0
+    }
+
+    /// Computes the area and perimeter of the district `dist` with `nodes`.
+    fn measure(&mut self, graph: &Graph, partition: &Partition, dist: usize, nodes: &[usize]) {
+        let area = graph.attr[&self.area_col].sum(nodes).unwrap().as_f64();
+        let mut perimeter = graph.attr[&self.perimeter_col].sum(nodes).unwrap().as_f64();
+        for &node in nodes.iter() {
+            for (&neighbor, &shared) in graph.neighbors[node]
+                .iter()
+                .zip(self.neighbor_shared[node].iter())
+            {
+                if partition.assignments[neighbor] as usize == dist {
+                    perimeter -= shared;
+                }
+            }
+        }
+        self.areas[dist] = area;
+        self.perimeters[dist] = perimeter;
+    }
+}
+
+impl Updater for Compactness {
+    fn name(&self) -> &str {
+        "compactness"
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        let shared_perim = &graph.edge_attr[&self.shared_perim_col];
+        self.neighbor_shared = graph
+            .neighbors
+            .iter()
+            .enumerate()
+            .map(|(node, neighbors)| {
+                neighbors
+                    .iter()
+                    .map(|&neighbor| {
+                        let edge = graph.edge_index(node, neighbor).unwrap();
+                        shared_perim.get_f64(edge).unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        let all_nodes: Vec<usize> = (0..graph.neighbors.len()).collect();
+        let all_edges: Vec<usize> = (0..graph.edges.len()).collect();
+        let total_perimeter = graph.attr[&self.perimeter_col]
+            .sum(&all_nodes)
+            .unwrap()
+            .as_f64();
+        let total_shared = shared_perim.sum(&all_edges).unwrap().as_f64();
+        self.exterior = total_perimeter - 2.0 * total_shared;
+
+        let num_dists = partition.num_dists as usize;
+        self.areas = vec![0.0; num_dists];
+        self.perimeters = vec![0.0; num_dists];
+        for (dist, nodes) in partition.dist_nodes.iter().enumerate() {
+            self.measure(graph, partition, dist, nodes);
+        }
+    }
+
+    fn update(&mut self, graph: &Graph, partition: &Partition, proposal: &RecomProposal) {
+        self.measure(graph, partition, proposal.a_label, &proposal.a_nodes);
+        self.measure(graph, partition, proposal.b_label, &proposal.b_nodes);
+    }
+
+    fn value(&self) -> Value {
+        json!({
+            "polsby_popper": self.polsby_popper(),
+            "schwartzberg": self.schwartzberg(),
+            "boundary_length": self.boundary_length(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::stats::test_util::grid_4x4;
+
+    /// Checks compactness scores on a 4x4 grid of unit squares, before and
+    /// after a proposal that turns two vertical strips into two squares.
+    #[test]
+    fn compactness_rect_grid() {
+        let (mut graph, mut partition, proposal) = grid_4x4();
+        graph
+            .attr
+            .insert("area".to_string(), AttrColumn::parse((0..16).map(|_| "1")));
+        graph.attr.insert(
+            "perimeter".to_string(),
+            AttrColumn::parse((0..16).map(|_| "4")),
+        );
+        graph.edge_attr.insert(
+            "shared_perim".to_string(),
+            AttrColumn::Float(vec![1.0; graph.edges.len()]),
+        );
+        let mut compactness = Compactness::new("area", "perimeter", "shared_perim");
+        compactness.init(&graph, &partition);
+        assert_eq!(compactness.areas(), &[4.0; 4]);
+        assert_eq!(compactness.perimeters(), &[10.0; 4]);
+        assert_eq!(compactness.boundary_length(), 12.0);
+        assert_eq!(compactness.polsby_popper()[0], 16.0 * PI / 100.0);
+
+        partition.update(&proposal);
+        compactness.update(&graph, &partition, &proposal);
+        assert_eq!(compactness.perimeters(), &[8.0, 8.0, 10.0, 10.0]);
+        assert_eq!(compactness.boundary_length(), 10.0);
+        assert_eq!(compactness.polsby_popper()[0], PI / 4.0);
+        assert_eq!(compactness.schwartzberg()[0], (PI / 4.0).sqrt());
+
+        let value = compactness.value();
+        compactness.init(&graph, &partition);
+        assert_eq!(value, compactness.value());
+    }
+}
@@ -0,0 +1,206 @@
+//! Transparent stream compression for statistics writers.
+use super::StatsWriter;
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::SelfLoopCounts;
+use std::io::{Error, Result, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use xz2::write::XzEncoder;
+
+/// The zstd compression level (zstd's default).
+const ZSTD_LEVEL: i32 = 3;
+/// The xz compression level (xz's default).
+const XZ_LEVEL: u32 = 6;
+
+/// Stream compression formats for writer output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Zstandard (`.zst`).
+    Zstd,
+    /// XZ/LZMA2 (`.xz`).
+    Xz,
+}
+
+impl Compression {
+    /// Parses a compression format name (`zstd` or `xz`).
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "zstd" | "zst" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// Infers a compression format from an output path's extension.
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("zst") => Some(Compression::Zstd),
+            Some("xz") => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+}
+
+/// A streaming compressor wrapping the final output.
+enum Encoder {
+    Zstd(zstd::stream::write::Encoder<'static, Box<dyn Write + Send>>),
+    Xz(XzEncoder<Box<dyn Write + Send>>),
+}
+
+impl Encoder {
+    /// Writes the end of the compressed stream and flushes the output.
+    fn finish(self) -> Result<()> {
+        let mut output = match self {
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+        };
+        output.flush()
+    }
+}
+
+/// A handle to a shared compressor, passed to the wrapped writer as its output.
+/// The compressor is taken (and finalized) when the wrapped writer is closed.
+struct SharedEncoder(Arc<Mutex<Option<Encoder>>>);
+
+impl Write for SharedEncoder {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self.0.lock().unwrap().as_mut() {
+            Some(Encoder::Zstd(encoder)) => encoder.write(buf),
+            Some(Encoder::Xz(encoder)) => encoder.write(buf),
+            None => Err(Error::other("write after compressed output was finalized")),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self.0.lock().unwrap().as_mut() {
+            Some(Encoder::Zstd(encoder)) => encoder.flush(),
+            Some(Encoder::Xz(encoder)) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Wraps a [StatsWriter] so that its output is compressed on the fly.
+///
+/// The wrapped writer's output passes through a streaming compressor;
+/// closing this writer closes the wrapped writer and then writes the end
+/// of the compressed stream. (Dropping the writer without closing it
+/// leaves the compressed stream truncated.)
+pub struct CompressedWriter {
+    /// The wrapped writer.
+    inner: Box<dyn StatsWriter>,
+    /// The compressor shared with the wrapped writer's output.
+    encoder: Arc<Mutex<Option<Encoder>>>,
+}
+
+impl CompressedWriter {
+    /// Returns a new compressed writer.
+    ///
+    /// Arguments:
+    ///   * `compression`: The compression format.
+    ///   * `output`: The destination of the compressed output.
+    ///   * `make_writer`: Builds the wrapped writer from its (uncompressed) output.
+    pub fn new<F>(
+        compression: Compression,
+        output: Box<dyn Write + Send>,
+        make_writer: F,
+    ) -> Result<CompressedWriter>
+    where
+        F: FnOnce(Box<dyn Write + Send>) -> Box<dyn StatsWriter>,
+    {
+        let encoder = match compression {
+            Compression::Zstd => {
+                Encoder::Zstd(zstd::stream::write::Encoder::new(output, ZSTD_LEVEL)?)
+            }
+            Compression::Xz => Encoder::Xz(XzEncoder::new(output, XZ_LEVEL)),
+        };
+        let encoder = Arc::new(Mutex::new(Some(encoder)));
+        let inner = make_writer(Box::new(SharedEncoder(encoder.clone())));
+        Ok(CompressedWriter { inner, encoder })
+    }
+}
+
+impl StatsWriter for CompressedWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        self.inner.init(graph, partition)
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        partition: &Partition,
+        proposal: &RecomProposal,
+        counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        self.inner.step(step, graph, partition, proposal, counts)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.inner.close()?;
+        match self.encoder.lock().unwrap().take() {
+            Some(encoder) => encoder.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_2x2, SharedBuffer};
+    use crate::stats::TSVWriter;
+    use std::io::Read;
+
+    /// Runs a short chain through a compressed TSV writer and returns
+    /// the compressed output.
+    fn compressed_tsv(compression: Compression) -> Vec<u8> {
+        let (graph, partition, proposal) = grid_2x2();
+        let buf = SharedBuffer::default();
+        let mut writer = CompressedWriter::new(compression, Box::new(buf.clone()), |output| {
+            Box::new(TSVWriter::new(output))
+        })
+        .unwrap();
+        writer.init(&graph, &partition).unwrap();
+        writer
+            .step(1, &graph, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        writer.close().unwrap();
+        buf.bytes()
+    }
+
+    #[test]
+    fn zstd_round_trip() {
+        let compressed = compressed_tsv(Compression::Zstd);
+        let decompressed = zstd::stream::decode_all(compressed.as_slice()).unwrap();
+        let text = String::from_utf8(decompressed).unwrap();
+        assert!(text.starts_with("1\t0\t0\t0\t0\t0\t1\t2\t2\t[0, 2]\t[1, 3]"));
+        assert_eq!(text.lines().count(), 1);
+    }
+
+    #[test]
+    fn xz_round_trip() {
+        let compressed = compressed_tsv(Compression::Xz);
+        let mut text = String::new();
+        xz2::read::XzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.starts_with("1\t0\t0\t0\t0\t0\t1\t2\t2\t[0, 2]\t[1, 3]"));
+        assert_eq!(text.lines().count(), 1);
+    }
+
+    #[test]
+    fn compression_from_path() {
+        assert_eq!(
+            Compression::from_path(Path::new("chain.jsonl.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::from_path(Path::new("chain.ben.xz")),
+            Some(Compression::Xz)
+        );
+        assert_eq!(Compression::from_path(Path::new("chain.jsonl")), None);
+    }
+}
@@ -0,0 +1,601 @@
+//! Convergence diagnostics: autocorrelation functions and effective
+//! sample sizes of scalar chain statistics.
+//!
+//! Chain statistics are time series over steps (self-loops included), so
+//! a plan held for many steps contributes a run of identical values. The
+//! [Autocorrelation] accumulator adds such runs in time proportional to the
+//! maximum lag (rather than the length of the run), which keeps diagnostics
+//! cheap for reversible chains that mostly self-loop.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::held::HeldSteps;
+use crate::stats::{SelfLoopCounts, StatsWriter, Updater};
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Result, Write};
+
+/// A running autocorrelation function of a scalar time series
+/// (up to a maximum lag).
+///
+/// Values are shifted by the first observation before accumulating sums
+/// of lagged products, which keeps the sums small (the autocovariance
+/// is shift-invariant).
+#[derive(Clone, Debug)]
+pub struct Autocorrelation {
+    /// The maximum lag.
+    max_lag: usize,
+    /// The first observation (subtracted from all observations).
+    shift: Option<f64>,
+    /// The length of the series.
+    len: u64,
+    /// The sum of all (shifted) observations.
+    sum: f64,
+    /// The sums of lagged products of (shifted) observations by lag.
+    lagged: Vec<f64>,
+    /// The first `max_lag` (shifted) observations.
+    head: Vec<f64>,
+    /// The last `max_lag` (shifted) observations, most recent first.
+    recent: VecDeque<f64>,
+}
+
+impl Autocorrelation {
+    pub fn new(max_lag: usize) -> Autocorrelation {
+        Autocorrelation {
+            max_lag,
+            shift: None,
+            len: 0,
+            sum: 0.0,
+            lagged: vec![0.0; max_lag + 1],
+            head: Vec::with_capacity(max_lag),
+            recent: VecDeque::with_capacity(max_lag),
+        }
+    }
+
+    /// Appends a run of `repeat` copies of `value` to the series.
+    pub fn push(&mut self, value: f64, repeat: u64) {
+        if repeat == 0 {
+            return;
+        }
+        let y = value - *self.shift.get_or_insert(value);
+        let w = repeat as f64;
+        // Prefix sums of recent observations (most recent first).
+        let mut prefix = Vec::with_capacity(self.recent.len() + 1);
+        prefix.push(0.0);
+        for (idx, &recent) in self.recent.iter().enumerate() {
+            prefix.push(prefix[idx] + recent);
+        }
+        self.lagged[0] += w * y * y;
+        for lag in 1..=self.max_lag {
+            // Pairs within the run.
+            if repeat > lag as u64 {
+                self.lagged[lag] += (repeat - lag as u64) as f64 * y * y;
+            }
+            // Pairs between the run and earlier observations: the first
+            // `min(repeat, lag)` observations in the run are paired with
+            // the observations `lag - 1` back to `lag - min(repeat, lag)`
+            // back from the end of the series (if they exist).
+            let span = (lag as u64).min(repeat) as usize;
+            let lo = lag - span;
+            let hi = lag.min(self.recent.len());
+            if lo < hi {
+                self.lagged[lag] += y * (prefix[hi] - prefix[lo]);
+            }
+        }
+        self.sum += w * y;
+        self.len += repeat;
+        let head_count = (self.max_lag - self.head.len()).min(repeat as usize);
+        self.head.extend(std::iter::repeat_n(y, head_count));
+        for _ in 0..(repeat.min(self.max_lag as u64)) {
+            self.recent.push_front(y);
+        }
+        self.recent.truncate(self.max_lag);
+    }
+
+    /// Returns the length of the series.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the series is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the mean of the series.
+    pub fn mean(&self) -> Option<f64> {
+        match self.len {
+            0 => None,
+            len => Some(self.shift.unwrap() + self.sum / len as f64),
+        }
+    }
+
+    /// Returns the (biased) autocovariance of the series at `lag`.
+    fn autocovariance(&self, lag: usize) -> f64 {
+        let n = self.len as f64;
+        let mean = self.sum / n;
+        let head: f64 = self.head[..lag].iter().sum();
+        let tail: f64 = self.recent.iter().take(lag).sum();
+        (self.lagged[lag] - mean * (2.0 * self.sum - head - tail) + (n - lag as f64) * mean * mean)
+            / n
+    }
+
+    /// Returns the variance of the series.
+    pub fn variance(&self) -> Option<f64> {
+        match self.len {
+            0 => None,
+            _ => Some(self.autocovariance(0).max(0.0)),
+        }
+    }
+
+    /// Returns the autocorrelation function of the series from lag 0 up to
+    /// the maximum lag (or the length of the series less one), or `None` if
+    /// the series is empty or constant.
+    pub fn acf(&self) -> Option<Vec<f64>> {
+        let variance = self.variance()?;
+        if variance <= 0.0 {
+            return None;
+        }
+        let max_lag = (self.max_lag as u64).min(self.len - 1) as usize;
+        Some(
+            (0..=max_lag)
+                .map(|lag| self.autocovariance(lag) / variance)
+                .collect(),
+        )
+    }
+
+    /// Returns the integrated autocorrelation time of the series, estimated
+    /// with Geyer's initial positive sequence, and whether the estimate was
+    /// truncated at the maximum lag (in which case it is a lower bound).
+    pub fn integrated_time(&self) -> Option<(f64, bool)> {
+        let acf = self.acf()?;
+        let mut total = 0.0;
+        for pair in acf.chunks_exact(2) {
+            let sum = pair[0] + pair[1];
+            if sum <= 0.0 {
+                return Some((2.0 * total - 1.0, false));
+            }
+            total += sum;
+        }
+        Some((2.0 * total - 1.0, true))
+    }
+
+    /// Returns the effective sample size of the series (the length of the
+    /// series divided by its integrated autocorrelation time).
+    pub fn ess(&self) -> Option<f64> {
+        self.integrated_time()
+            .map(|(time, _)| self.len as f64 / time)
+    }
+
+    /// Returns the mean, variance, integrated autocorrelation time, and
+    /// effective sample size (and optionally the autocorrelation function)
+    /// as JSON.
+    pub fn to_json(&self, with_acf: bool) -> Value {
+        let time = self.integrated_time();
+        let mut stats = json!({
+            "mean": self.mean(),
+            "variance": self.variance(),
+            "integrated_time": time.map(|(time, _)| time),
+            "truncated": time.map(|(_, truncated)| truncated),
+            "ess": self.ess(),
+        });
+        if with_acf {
+            stats
+                .as_object_mut()
+                .unwrap()
+                .insert("acf".to_string(), json!(self.acf()));
+        }
+        stats
+    }
+}
+
+/// The pointer that selects the number of self-loops before each accepted
+/// proposal.
+const SELF_LOOPS_POINTER: &str = "/self_loops";
+
+/// Tracks autocorrelation functions and effective sample sizes of scalar
+/// statistics, along with acceptance and self-loop counts.
+///
+/// Statistics are selected by JSON pointers into an object mapping the
+/// names of the writer's updaters to their values (e.g. `/num_cut_edges`
+/// or `/PRES16/seats`). Each pointer must resolve to a number (or `null`)
+/// for the initial plan. Non-numeric values (such as `null`, which
+/// updaters report for undefined or infinite statistics) are counted as
+/// missing and skipped, so the remaining values are treated as a
+/// contiguous series. The pointer `/self_loops` instead selects the
+/// series of self-loop counts before each accepted proposal (one value
+/// per accepted proposal, rather than per step).
+///
+/// No per-step output is written; `close()` writes a single JSON line
+/// with the diagnostics, and progress lines (without autocorrelation
+/// functions) can optionally be written to `stderr` at regular step
+/// intervals.
+pub struct DiagnosticsWriter {
+    /// The number of steps each plan was held for.
+    held: HeldSteps,
+    /// The step interval between progress lines (if any).
+    progress_interval: Option<u64>,
+    /// The next step at which to write a progress line.
+    next_progress: u64,
+    /// The number of accepted proposals.
+    accepted: u64,
+    /// Self-loop counts over the whole run.
+    self_loops: SelfLoopCounts,
+    /// Incrementally updated statistics to select scalars from.
+    updaters: Vec<Box<dyn Updater>>,
+    /// JSON pointers to the selected statistics.
+    pointers: Vec<String>,
+    /// The current value of each selected statistic (`None` if missing).
+    current: Vec<Option<f64>>,
+    /// The autocorrelation function of each selected statistic.
+    series: Vec<Autocorrelation>,
+    /// The number of steps at which each selected statistic was missing.
+    missing: Vec<u64>,
+    /// The autocorrelation function of the self-loop counts before each
+    /// accepted proposal (if selected).
+    self_loop_series: Option<Autocorrelation>,
+    /// The number of steps observed.
+    steps: u64,
+    /// The output stream that we would like to write to.
+    output: Box<dyn Write + Send>,
+}
+
+impl DiagnosticsWriter {
+    pub fn new(
+        n_steps: Option<u64>,
+        max_lag: usize,
+        pointers: Vec<String>,
+        progress_interval: Option<u64>,
+        output: Box<dyn Write + Send>,
+    ) -> DiagnosticsWriter {
+        let self_loop_series = pointers
+            .iter()
+            .any(|pointer| pointer == SELF_LOOPS_POINTER)
+            .then(|| Autocorrelation::new(max_lag));
+        let pointers: Vec<String> = pointers
+            .into_iter()
+            .filter(|pointer| pointer != SELF_LOOPS_POINTER)
+            .collect();
+        DiagnosticsWriter {
+            held: HeldSteps::new(n_steps),
+            progress_interval,
+            next_progress: progress_interval.unwrap_or(0),
+            accepted: 0,
+            self_loops: SelfLoopCounts::default(),
+            updaters: vec![],
+            current: vec![],
+            missing: vec![0; pointers.len()],
+            self_loop_series,
+            steps: 0,
+            series: pointers
+                .iter()
+                .map(|_| Autocorrelation::new(max_lag))
+                .collect(),
+            pointers,
+            output,
+        }
+    }
+
+    /// Adds an incrementally updated statistic (whose value can be
+    /// selected by the writer's pointers under the updater's name).
+    pub fn with_updater(mut self, updater: Box<dyn Updater>) -> DiagnosticsWriter {
+        self.updaters.push(updater);
+        self
+    }
+
+    /// Returns the values of the writer's updaters, keyed by name.
+    fn values(&self) -> Value {
+        Value::Object(
+            self.updaters
+                .iter()
+                .map(|updater| (updater.name().to_string(), updater.value()))
+                .collect(),
+        )
+    }
+
+    /// Updates the current values of the selected statistics.
+    fn select(&mut self) {
+        let values = self.values();
+        self.current = self
+            .pointers
+            .iter()
+            .map(|pointer| values.pointer(pointer).and_then(Value::as_f64))
+            .collect();
+    }
+
+    /// Adds the current values of the selected statistics to their
+    /// series (held for `repeat` steps).
+    fn observe(&mut self, repeat: u64) {
+        for ((series, missing), value) in self
+            .series
+            .iter_mut()
+            .zip(self.missing.iter_mut())
+            .zip(self.current.iter())
+        {
+            match value {
+                Some(value) => series.push(*value, repeat),
+                None => *missing += repeat,
+            }
+        }
+        self.steps += repeat;
+    }
+
+    /// Returns the diagnostics as JSON.
+    fn report(&self, with_acf: bool) -> Value {
+        let mut stats: Map<String, Value> = self
+            .pointers
+            .iter()
+            .zip(self.series.iter())
+            .zip(self.missing.iter())
+            .map(|((pointer, series), &missing)| {
+                let mut stats = series.to_json(with_acf);
+                stats
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("missing".to_string(), json!(missing));
+                (pointer.clone(), stats)
+            })
+            .collect();
+        if let Some(series) = &self.self_loop_series {
+            let mut loop_stats = series.to_json(with_acf);
+            loop_stats
+                .as_object_mut()
+                .unwrap()
+                .insert("missing".to_string(), json!(0));
+            stats.insert(SELF_LOOPS_POINTER.to_string(), loop_stats);
+        }
+        let proposals = self.accepted + self.self_loops.sum() as u64;
+        json!({
+            "steps": self.steps,
+            "accepted": self.accepted,
+            "self_loops": self.self_loops,
+            "acceptance_rate": match proposals {
+                0 => None,
+                _ => Some(self.accepted as f64 / proposals as f64),
+            },
+            "stats": stats,
+        })
+    }
+}
+
+impl StatsWriter for DiagnosticsWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        for updater in self.updaters.iter_mut() {
+            updater.init(graph, partition);
+        }
+        // Catch typos and pointers to non-scalar statistics before the run.
+        let values = self.values();
+        for pointer in self.pointers.iter() {
+            match values.pointer(pointer) {
+                Some(value) if value.is_number() || value.is_null() => {}
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("diagnostic statistic {} is not a number", pointer),
+                    ))
+                }
+            }
+        }
+        self.select();
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        partition: &Partition,
+        proposal: &RecomProposal,
+        counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        let held = self.held.accept(step);
+        self.observe(held);
+        self.accepted += 1;
+        if let Some(series) = self.self_loop_series.as_mut() {
+            series.push(counts.sum() as f64, 1);
+        }
+        self.self_loops = std::mem::take(&mut self.self_loops) + counts.clone();
+        for updater in self.updaters.iter_mut() {
+            updater.update(graph, partition, proposal);
+        }
+        self.select();
+        if let Some(interval) = self.progress_interval {
+            if step >= self.next_progress {
+                eprintln!(
+                    "{}",
+                    json!({ "progress": { "step": step, "diagnostics": self.report(false) } })
+                );
+                self.next_progress = (step / interval + 1) * interval;
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.observe(self.held.final_held());
+        self.output
+            .write_all(format!("{}\n", json!({ "diagnostics": self.report(true) })).as_bytes())?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_4x4, SharedBuffer};
+    use crate::stats::{CutEdges, SelfLoopReason};
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    /// A statistic whose values are given in advance (one per step).
+    struct Sequence {
+        values: Vec<Value>,
+        step: usize,
+    }
+
+    impl Updater for Sequence {
+        fn name(&self) -> &str {
+            "seq"
+        }
+
+        fn init(&mut self, _graph: &Graph, _partition: &Partition) {
+            self.step = 0;
+        }
+
+        fn update(&mut self, _graph: &Graph, _partition: &Partition, _proposal: &RecomProposal) {
+            self.step += 1;
+        }
+
+        fn value(&self) -> Value {
+            self.values[self.step].clone()
+        }
+    }
+
+    /// Computes the autocorrelation function of a series directly.
+    fn brute_force_acf(series: &[f64], max_lag: usize) -> Vec<f64> {
+        let n = series.len() as f64;
+        let mean = series.iter().sum::<f64>() / n;
+        let autocov = |lag: usize| -> f64 {
+            (lag..series.len())
+                .map(|t| (series[t] - mean) * (series[t - lag] - mean))
+                .sum::<f64>()
+                / n
+        };
+        let variance = autocov(0);
+        (0..=max_lag.min(series.len() - 1))
+            .map(|lag| autocov(lag) / variance)
+            .collect()
+    }
+
+    #[test]
+    fn acf_matches_brute_force_with_runs() {
+        let mut rng: SmallRng = SeedableRng::seed_from_u64(44);
+        for &(num_runs, max_lag) in [(3, 20), (40, 8), (200, 30)].iter() {
+            let mut acf = Autocorrelation::new(max_lag);
+            let mut series = vec![];
+            for _ in 0..num_runs {
+                let value = 100.0 + rng.gen_range(0..10) as f64;
+                let repeat = rng.gen_range(1..12);
+                acf.push(value, repeat);
+                series.extend(std::iter::repeat_n(value, repeat as usize));
+            }
+            assert_eq!(acf.len(), series.len() as u64);
+            let expected = brute_force_acf(&series, max_lag);
+            let actual = acf.acf().unwrap();
+            assert_eq!(actual.len(), expected.len());
+            for (a, b) in actual.iter().zip(expected.iter()) {
+                assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+            }
+        }
+    }
+
+    /// Independent values held for `r` steps each have an integrated
+    /// autocorrelation time of `r`.
+    #[test]
+    fn ess_of_repeated_independent_values() {
+        let mut rng: SmallRng = SeedableRng::seed_from_u64(44);
+        let mut acf = Autocorrelation::new(50);
+        for _ in 0..20000 {
+            acf.push(rng.gen::<f64>(), 5);
+        }
+        let (time, truncated) = acf.integrated_time().unwrap();
+        assert!(!truncated);
+        assert!((time - 5.0).abs() < 0.5, "{}", time);
+        assert!((acf.ess().unwrap() - 20000.0).abs() < 2000.0);
+
+        let mut constant = Autocorrelation::new(5);
+        constant.push(1.0, 10);
+        assert_eq!(constant.ess(), None);
+        assert_eq!(constant.mean(), Some(1.0));
+    }
+
+    #[test]
+    fn diagnostics_writer_counts_self_loops() {
+        let (graph, mut partition, proposal) = grid_4x4();
+        let buf = SharedBuffer::default();
+        let mut writer = DiagnosticsWriter::new(
+            Some(4),
+            3,
+            vec!["/num_cut_edges".to_string(), "/self_loops".to_string()],
+            None,
+            Box::new(buf.clone()),
+        )
+        .with_updater(Box::new(CutEdges::new()));
+        writer.init(&graph, &partition).unwrap();
+
+        let mut counts = SelfLoopCounts::default();
+        counts.inc(SelfLoopReason::NoSplit);
+        counts.inc(SelfLoopReason::NoSplit);
+        partition.update(&proposal);
+        writer
+            .step(3, &graph, &partition, &proposal, &counts)
+            .unwrap();
+        writer.close().unwrap();
+
+        let output = buf.text();
+        let report: Value = serde_json::from_str(&output).unwrap();
+        let report = &report["diagnostics"];
+        assert_eq!(report["steps"], 5);
+        assert_eq!(report["accepted"], 1);
+        assert_eq!(report["self_loops"]["no_split"], 2);
+        assert_eq!(report["acceptance_rate"], 1.0 / 3.0);
+        // The cut edge series is [12, 12, 12, 10, 10].
+        let stats = &report["stats"]["/num_cut_edges"];
+        assert_eq!(stats["mean"], 11.2);
+        let expected = brute_force_acf(&[12.0, 12.0, 12.0, 10.0, 10.0], 3);
+        for (a, b) in stats["acf"].as_array().unwrap().iter().zip(expected.iter()) {
+            assert!((a.as_f64().unwrap() - b).abs() < 1e-9);
+        }
+        // The self-loop series has one value per accepted proposal.
+        let loops = &report["stats"]["/self_loops"];
+        assert_eq!(loops["mean"], 2.0);
+        assert_eq!(loops["missing"], 0);
+    }
+
+    fn sequence_writer(pointer: &str, buf: &SharedBuffer) -> DiagnosticsWriter {
+        let values = vec![json!({"x": 1}), json!({"x": null}), json!({"x": 3})];
+        DiagnosticsWriter::new(
+            Some(4),
+            3,
+            vec![pointer.to_string()],
+            None,
+            Box::new(buf.clone()),
+        )
+        .with_updater(Box::new(Sequence { values, step: 0 }))
+    }
+
+    #[test]
+    fn diagnostics_writer_skips_missing_values() {
+        let (graph, partition, proposal) = grid_4x4();
+        let buf = SharedBuffer::default();
+        let mut writer = sequence_writer("/seq/x", &buf);
+        let counts = SelfLoopCounts::default();
+        writer.init(&graph, &partition).unwrap();
+        writer
+            .step(2, &graph, &partition, &proposal, &counts)
+            .unwrap();
+        writer
+            .step(3, &graph, &partition, &proposal, &counts)
+            .unwrap();
+        writer.close().unwrap();
+
+        let output = buf.text();
+        let report: Value = serde_json::from_str(&output).unwrap();
+        let report = &report["diagnostics"];
+        assert_eq!(report["steps"], 5);
+        // The series is [1, 1, null, 3, 3].
+        let stats = &report["stats"]["/seq/x"];
+        assert_eq!(stats["missing"], 1);
+        assert_eq!(stats["mean"], 2.0);
+    }
+
+    #[test]
+    fn diagnostics_writer_rejects_non_numeric_statistics() {
+        let (graph, partition, _) = grid_4x4();
+        for pointer in ["/seq/y", "/seq", "/missing"] {
+            let mut writer = sequence_writer(pointer, &SharedBuffer::default());
+            let err = writer.init(&graph, &partition).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Partisan fairness metrics for two-party elections.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::{partition_attr_sums, Updater};
+use serde::Serialize;
+use serde_json::{to_value, Value};
+use std::f64::consts::PI;
+
+/// Partisan fairness metrics for a two-party election in a districting plan.
+///
+/// Metrics are reported from the perspective of the first party: positive
+/// values of the efficiency gap, mean-median difference, partisan bias, and
+/// declination indicate an advantage for the first party.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ElectionResults {
+    /// The number of districts won by the first party.
+    pub seats: usize,
+    /// The difference between the second and first parties' wasted votes,
+    /// as a fraction of all votes.
+    pub efficiency_gap: f64,
+    /// The first party's median district vote share minus its mean
+    /// district vote share.
+    pub mean_median: f64,
+    /// Half the difference between the first party's seat share at its mean
+    /// vote share and the second party's seat share under a uniform swing
+    /// that reverses the vote shares.
+    pub partisan_bias: f64,
+    /// The declination (undefined if either party wins no districts).
+    pub declination: Option<f64>,
+}
+
+impl ElectionResults {
+    /// Computes metrics from per-district vote totals for each party.
+    pub fn from_votes(first: &[f64], second: &[f64]) -> ElectionResults {
+        let num_dists = first.len() as f64;
+        let shares = vote_shares(first, second);
+        let seats = shares.iter().filter(|&&s| s > 0.5).count();
+
+        // A district's winner wastes votes beyond half of the district's
+        // votes; the loser wastes all of its votes.
+        let mut net_wasted = 0.0;
+        for (&a, &b) in first.iter().zip(second.iter()) {
+            let half = (a + b) / 2.0;
+            net_wasted += match a > b {
+                true => b - (a - half),
+                false => (b - half) - a,
+            };
+        }
+        let total_votes: f64 = first.iter().chain(second.iter()).sum();
+        let efficiency_gap = match total_votes > 0.0 {
+            true => net_wasted / total_votes,
+            false => 0.0,
+        };
+
+        let mean = shares.iter().sum::<f64>() / num_dists;
+        let mut sorted = shares.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mid = sorted.len() / 2;
+        let median = match sorted.len() % 2 {
+            0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+            _ => sorted[mid],
+        };
+
+        let inverted_seats = shares.iter().filter(|&&s| 2.0 * mean - s > 0.5).count();
+        let partisan_bias = (seats as f64 - inverted_seats as f64) / (2.0 * num_dists);
+
+        ElectionResults {
+            seats,
+            efficiency_gap,
+            mean_median: median - mean,
+            partisan_bias,
+            declination: declination(&shares),
+        }
+    }
+}
+
+/// Computes the first party's two-party vote share in each district
+/// (0.5 in districts with no votes).
+fn vote_shares(first: &[f64], second: &[f64]) -> Vec<f64> {
+    first
+        .iter()
+        .zip(second.iter())
+        .map(|(&a, &b)| if a + b > 0.0 { a / (a + b) } else { 0.5 })
+        .collect()
+}
+
+/// Computes the declination of a plan from the first party's vote share in
+/// each district, or `None` if either party wins no districts.
+fn declination(shares: &[f64]) -> Option<f64> {
+    let won: Vec<f64> = shares.iter().cloned().filter(|&s| s > 0.5).collect();
+    let lost: Vec<f64> = shares.iter().cloned().filter(|&s| s <= 0.5).collect();
+    if won.is_empty() || lost.is_empty() {
+        return None;
+    }
+    let num_dists = shares.len() as f64;
+    let won_mean = won.iter().sum::<f64>() / won.len() as f64;
+    let lost_mean = lost.iter().sum::<f64>() / lost.len() as f64;
+    // The angles of the lines from the point (boundary, 1/2) to the centers
+    // of mass of the won and lost districts in the sorted vote share plot.
+    let theta_won = ((2.0 * won_mean - 1.0) / (won.len() as f64 / num_dists)).atan();
+    let theta_lost = ((1.0 - 2.0 * lost_mean) / (lost.len() as f64 / num_dists)).atan();
+    Some(2.0 * (theta_lost - theta_won) / PI)
+}
+
+/// Tracks per-district vote totals for a two-party election and
+/// computes [ElectionResults] at each step.
+pub struct Election {
+    /// The name of the election (used as a key in output).
+    name: String,
+    /// The first party's vote column.
+    first_col: String,
+    /// The second party's vote column.
+    second_col: String,
+    /// The first party's votes in each district.
+    first: Vec<f64>,
+    /// The second party's votes in each district.
+    second: Vec<f64>,
+}
+
+impl Election {
+    pub fn new(name: &str, first_col: &str, second_col: &str) -> Election {
+        Election {
+            name: name.to_string(),
+            first_col: first_col.to_string(),
+            second_col: second_col.to_string(),
+            first: vec![],
+            second: vec![],
+        }
+    }
+
+    /// Returns the first party's current vote share in each district.
+    pub fn shares(&self) -> Vec<f64> {
+        vote_shares(&self.first, &self.second)
+    }
+
+    /// Returns the current election results.
+    pub fn results(&self) -> ElectionResults {
+        ElectionResults::from_votes(&self.first, &self.second)
+    }
+}
+
+impl Updater for Election {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        let votes = |col: &str| -> Vec<f64> {
+            partition_attr_sums(graph, partition, col)
+                .iter()
+                .map(|sum| sum.as_f64())
+                .collect()
+        };
+        self.first = votes(&self.first_col);
+        self.second = votes(&self.second_col);
+    }
+
+    fn update(&mut self, graph: &Graph, _partition: &Partition, proposal: &RecomProposal) {
+        for (col, votes) in [
+            (&self.first_col, &mut self.first),
+            (&self.second_col, &mut self.second),
+        ] {
+            let values = &graph.attr[col];
+            votes[proposal.a_label] = values.sum(&proposal.a_nodes).unwrap().as_f64();
+            votes[proposal.b_label] = values.sum(&proposal.b_nodes).unwrap().as_f64();
+        }
+    }
+
+    fn value(&self) -> Value {
+        to_value(self.results()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn metrics_for_packed_plan() {
+        // The first party is packed into one district.
+        let first = [90.0, 40.0, 40.0, 40.0];
+        let second = [10.0, 60.0, 60.0, 60.0];
+        let results = ElectionResults::from_votes(&first, &second);
+        assert_eq!(results.seats, 1);
+        // Wasted votes: first = 40 + 3 * 40 = 160, second = 10 + 3 * 10 = 40.
+        assert_close(results.efficiency_gap, (40.0 - 160.0) / 400.0);
+        assert_close(results.mean_median, 0.4 - 0.525);
+        // The mean share is 0.525; inverted shares are [0.15, 0.65, 0.65, 0.65].
+        assert_close(results.partisan_bias, (1.0 - 3.0) / 8.0);
+        let theta_won = (0.8f64 / 0.25).atan();
+        let theta_lost = (0.2f64 / 0.75).atan();
+        assert_close(
+            results.declination.unwrap(),
+            2.0 * (theta_lost - theta_won) / PI,
+        );
+        assert!(results.declination.unwrap() < 0.0);
+    }
+
+    #[test]
+    fn declination_undefined_for_sweep() {
+        let results = ElectionResults::from_votes(&[60.0, 70.0], &[40.0, 30.0]);
+        assert_eq!(results.seats, 2);
+        assert_eq!(results.declination, None);
+    }
+}
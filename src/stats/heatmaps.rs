@@ -0,0 +1,246 @@
+//! Cut edge frequency and node co-assignment heatmaps.
+//!
+//! These frequencies show where district boundaries tend to fall over a
+//! chain run. Each edge's cut status (and each node's co-assignment with
+//! an anchor node) can only change when one of its nodes moves, so we
+//! record when each status last changed and only visit the nodes in the
+//! two districts changed by each accepted proposal.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::held::HeldSteps;
+use crate::stats::{SelfLoopCounts, StatsWriter};
+use serde_json::{json, Map, Value};
+use std::io::{Result, Write};
+
+/// Tracks the number of steps for which each of a collection of
+/// boolean statuses holds.
+struct Occupancy {
+    /// The current status of each element.
+    state: Vec<bool>,
+    /// The step at which each status last became true.
+    since: Vec<u64>,
+    /// The number of steps for which each status held
+    /// (before its current run).
+    total: Vec<u64>,
+}
+
+impl Occupancy {
+    /// Starts tracking statuses (at step 0).
+    fn new(state: Vec<bool>) -> Occupancy {
+        let len = state.len();
+        Occupancy {
+            state,
+            since: vec![0; len],
+            total: vec![0; len],
+        }
+    }
+
+    /// Updates the status of element `idx` at `step`.
+    fn set(&mut self, idx: usize, value: bool, step: u64) {
+        if self.state[idx] == value {
+            return;
+        }
+        match value {
+            true => self.since[idx] = step,
+            false => self.total[idx] += step - self.since[idx],
+        }
+        self.state[idx] = value;
+    }
+
+    /// Returns the fraction of steps (up to but not including `end`)
+    /// for which each status held.
+    fn frequencies(&self, end: u64) -> Vec<f64> {
+        self.state
+            .iter()
+            .zip(self.since.iter().zip(self.total.iter()))
+            .map(|(&state, (&since, &total))| {
+                let steps = total + if state { end - since } else { 0 };
+                steps as f64 / end as f64
+            })
+            .collect()
+    }
+}
+
+/// Records how often each edge is cut and (optionally) how often each node
+/// is in the same district as each of a set of anchor nodes.
+///
+/// No per-step output is written; `close()` writes a single JSON line with
+/// the node identifiers, the edges (as pairs of node indices), the fraction
+/// of steps for which each edge was cut, and the fraction of steps for which
+/// each node shared a district with each anchor, over all steps of the
+/// chain (including self-loops).
+pub struct HeatmapWriter {
+    /// The number of steps each plan was held for.
+    held: HeldSteps,
+    /// The original identifiers of the anchor nodes.
+    anchor_ids: Vec<String>,
+    /// The indices of the anchor nodes.
+    anchors: Vec<usize>,
+    /// The original identifier of each node.
+    nodes: Vec<String>,
+    /// The graph's edges (as pairs of node indices).
+    edges: Vec<(usize, usize)>,
+    /// The indices of the edges incident to each node.
+    incident: Vec<Vec<usize>>,
+    /// Cut edge statuses.
+    cut: Occupancy,
+    /// Co-assignment statuses with each anchor node.
+    together: Vec<Occupancy>,
+    /// The output stream that we would like to write to.
+    output: Box<dyn Write + Send>,
+}
+
+impl HeatmapWriter {
+    /// Creates a heatmap writer with anchor nodes given by their original
+    /// identifiers (see [Graph::node_index]).
+    pub fn new(
+        n_steps: Option<u64>,
+        anchor_ids: Vec<String>,
+        output: Box<dyn Write + Send>,
+    ) -> HeatmapWriter {
+        HeatmapWriter {
+            held: HeldSteps::new(n_steps),
+            anchor_ids,
+            anchors: vec![],
+            nodes: vec![],
+            edges: vec![],
+            incident: vec![],
+            cut: Occupancy::new(vec![]),
+            together: vec![],
+            output,
+        }
+    }
+
+    /// Returns the heatmaps up to (but not including) step `end`.
+    fn heatmaps(&self, end: u64) -> Value {
+        let coassignment: Map<String, Value> = self
+            .anchor_ids
+            .iter()
+            .zip(self.together.iter())
+            .map(|(id, together)| (id.clone(), json!(together.frequencies(end))))
+            .collect();
+        json!({
+            "steps": end,
+            "nodes": self.nodes,
+            "edges": self.edges,
+            "cut_edge_frequency": self.cut.frequencies(end),
+            "coassignment": coassignment,
+        })
+    }
+}
+
+impl StatsWriter for HeatmapWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        let assignments = &partition.assignments;
+        self.anchors = self
+            .anchor_ids
+            .iter()
+            .map(|id| {
+                graph
+                    .node_index(id)
+                    .unwrap_or_else(|| panic!("unknown anchor node {}", id))
+            })
+            .collect();
+        self.held.reset();
+        self.nodes = (0..graph.neighbors.len())
+            .map(|node| graph.node_id(node))
+            .collect();
+        self.edges = graph.edges.iter().map(|edge| (edge.0, edge.1)).collect();
+        self.incident = vec![vec![]; graph.neighbors.len()];
+        for (idx, edge) in graph.edges.iter().enumerate() {
+            self.incident[edge.0].push(idx);
+            self.incident[edge.1].push(idx);
+        }
+        self.cut = Occupancy::new(
+            graph
+                .edges
+                .iter()
+                .map(|edge| assignments[edge.0] != assignments[edge.1])
+                .collect(),
+        );
+        self.together = self
+            .anchors
+            .iter()
+            .map(|&anchor| {
+                Occupancy::new(
+                    assignments
+                        .iter()
+                        .map(|&dist| dist == assignments[anchor])
+                        .collect(),
+                )
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        partition: &Partition,
+        proposal: &RecomProposal,
+        _counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        // Only nodes in the two changed districts can change districts,
+        // so only their edges and co-assignments can change.
+        let assignments = &partition.assignments;
+        for &node in proposal.a_nodes.iter().chain(proposal.b_nodes.iter()) {
+            for &idx in self.incident[node].iter() {
+                let edge = &graph.edges[idx];
+                self.cut
+                    .set(idx, assignments[edge.0] != assignments[edge.1], step);
+            }
+            for (&anchor, together) in self.anchors.iter().zip(self.together.iter_mut()) {
+                together.set(node, assignments[node] == assignments[anchor], step);
+            }
+        }
+        self.held.accept(step);
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let end = self.held.end();
+        self.output
+            .write_all(format!("{}\n", json!({ "heatmaps": self.heatmaps(end) })).as_bytes())?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_4x4, SharedBuffer};
+
+    /// Records heatmaps for a 4-step chain on a 4x4 grid that holds four
+    /// vertical strips for steps 0-2 and then turns two strips into squares.
+    #[test]
+    fn heatmaps_weight_plans_by_steps_held() {
+        let (graph, mut partition, proposal) = grid_4x4();
+        let buf = SharedBuffer::default();
+        let mut writer = HeatmapWriter::new(Some(4), vec!["0".to_string()], Box::new(buf.clone()));
+        writer.init(&graph, &partition).unwrap();
+
+        partition.update(&proposal);
+        writer
+            .step(3, &graph, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        writer.close().unwrap();
+
+        let output = buf.text();
+        let heatmaps: Value = serde_json::from_str(&output).unwrap();
+        let heatmaps = &heatmaps["heatmaps"];
+        assert_eq!(heatmaps["steps"], 5);
+        assert_eq!(heatmaps["nodes"][15], "15");
+        let cut = &heatmaps["cut_edge_frequency"];
+        assert_eq!(cut[graph.edge_index(0, 4).unwrap()], 0.6);
+        assert_eq!(cut[graph.edge_index(1, 2).unwrap()], 0.4);
+        assert_eq!(cut[graph.edge_index(4, 8).unwrap()], 1.0);
+        assert_eq!(cut[graph.edge_index(0, 1).unwrap()], 0.0);
+        let together = &heatmaps["coassignment"]["0"];
+        assert_eq!(together[1], 1.0);
+        assert_eq!(together[2], 0.6);
+        assert_eq!(together[4], 0.4);
+        assert_eq!(together[8], 0.0);
+    }
+}
@@ -0,0 +1,75 @@
+//! Holding times of plans in a chain.
+
+/// Tracks the number of steps for which each plan in a chain is held
+/// (including self-loops), for writers that weight plans by holding time.
+///
+/// The initial plan is accepted at step 0, and each plan is held from the
+/// step it was accepted at until the step before the next plan was
+/// accepted. The final plan is held until the last step of the chain if
+/// the number of steps is known, and for a single step otherwise.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct HeldSteps {
+    /// The number of steps in the chain (if known).
+    n_steps: Option<u64>,
+    /// The step at which the current plan was accepted.
+    last_step: u64,
+}
+
+impl HeldSteps {
+    pub fn new(n_steps: Option<u64>) -> HeldSteps {
+        HeldSteps {
+            n_steps,
+            last_step: 0,
+        }
+    }
+
+    /// Restarts the chain at the initial plan.
+    pub fn reset(&mut self) {
+        self.last_step = 0;
+    }
+
+    /// Records a plan accepted at `step` and returns the number of steps
+    /// for which the previous plan was held.
+    pub fn accept(&mut self, step: u64) -> u64 {
+        let held = step.saturating_sub(self.last_step);
+        self.last_step = step;
+        held
+    }
+
+    /// Returns the number of steps for which the current (final) plan is held.
+    pub fn final_held(&self) -> u64 {
+        match self.n_steps {
+            Some(n_steps) if n_steps >= self.last_step => n_steps + 1 - self.last_step,
+            _ => 1,
+        }
+    }
+
+    /// Returns the step after the last step of the chain.
+    pub fn end(&self) -> u64 {
+        self.last_step + self.final_held()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_steps() {
+        let mut held = HeldSteps::new(Some(10));
+        assert_eq!(held.accept(3), 3);
+        assert_eq!(held.accept(7), 4);
+        assert_eq!(held.final_held(), 4);
+        assert_eq!(held.end(), 11);
+        // Chains that overrun their nominal length end after the last step.
+        assert_eq!(held.accept(12), 5);
+        assert_eq!(held.final_held(), 1);
+        assert_eq!(held.end(), 13);
+
+        let mut held = HeldSteps::new(None);
+        held.accept(5);
+        assert_eq!(held.final_held(), 1);
+        held.reset();
+        assert_eq!(held.end(), 1);
+    }
+}
@@ -0,0 +1,602 @@
+//! Log spanning tree counts via sparse Cholesky factorization.
+//!
+//! By Kirchhoff's matrix-tree theorem, the number of spanning trees of a
+//! connected graph is the determinant of its reduced Laplacian (the graph
+//! Laplacian with one row and column removed). The reduced Laplacian of a
+//! connected graph is symmetric positive definite, so its log-determinant is
+//! twice the sum of the logs of the diagonal of its Cholesky factor.
+//!
+//! Spanning tree counts of real districts overflow `f64`, so we only work
+//! with logs. To keep the Cholesky factor sparse, we use one of two
+//! strategies, depending on the size of the graph:
+//!
+//! * Small graphs are ordered by reverse Cuthill-McKee, which clusters
+//!   nonzeros near the diagonal. The Cholesky factor of a matrix has the
+//!   same envelope (the span from the first nonzero in each row to the
+//!   diagonal) as the matrix itself, so we factor in place within the
+//!   envelope. For planar graphs with `n` nodes, this takes roughly O(n²)
+//!   time and O(n^1.5) space, but the dense inner loops are fast.
+//! * Large graphs are ordered by nested dissection: a separator (a
+//!   breadth-first level near the middle of the graph) splits the graph
+//!   into two parts, which are ordered recursively before the separator.
+//!   Eliminating one part never creates fill in the other. We then use a
+//!   standard up-looking sparse Cholesky factorization (see Davis, *Direct
+//!   Methods for Sparse Linear Systems*, 2006): the nonzero pattern of each
+//!   row of the factor is found by walking the elimination tree, so only
+//!   nonzeros are stored and computed. For planar graphs, the factor has
+//!   O(n log n) nonzeros and the factorization takes O(n^1.5) time.
+//!
+//! On grids, the two strategies break even at several thousand nodes.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::Updater;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+
+/// Graphs with at least this many nodes are factored with a nested
+/// dissection ordering (rather than within the envelope).
+const MIN_SPARSE_SIZE: usize = 8000;
+
+/// Parts of the graph with at most this many nodes are not dissected further.
+const MIN_DISSECTION_SIZE: usize = 16;
+
+/// A sentinel for nodes without a parent in the elimination tree.
+const NO_PARENT: usize = usize::MAX;
+
+/// Returns the adjacency lists of the subgraph induced by `nodes`
+/// (indexed by position in `nodes`).
+fn subgraph_neighbors(graph: &Graph, nodes: &[usize]) -> Vec<Vec<usize>> {
+    let index: HashMap<usize, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, &node)| (node, idx))
+        .collect();
+    nodes
+        .iter()
+        .map(|node| {
+            graph.neighbors[*node]
+                .iter()
+                .filter_map(|neighbor| index.get(neighbor).copied())
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the nodes reachable from `root` in breadth-first order, visiting
+/// the unvisited neighbors of each node in increasing order of degree.
+fn bfs_order(neighbors: &[Vec<usize>], root: usize) -> Vec<usize> {
+    let mut visited = vec![false; neighbors.len()];
+    let mut order = Vec::with_capacity(neighbors.len());
+    let mut queue = VecDeque::new();
+    let mut next: Vec<usize> = vec![];
+    visited[root] = true;
+    queue.push_back(root);
+    while let Some(node) = queue.pop_front() {
+        order.push(node);
+        next.clear();
+        next.extend(neighbors[node].iter().filter(|&&n| !visited[n]));
+        next.sort_by_key(|&n| neighbors[n].len());
+        for &neighbor in next.iter() {
+            visited[neighbor] = true;
+            queue.push_back(neighbor);
+        }
+    }
+    order
+}
+
+/// Returns a reverse Cuthill-McKee ordering of a connected graph.
+///
+/// The ordering starts from a pseudo-peripheral node (the last node in a
+/// breadth-first search from a node of minimum degree).
+fn reverse_cuthill_mckee(neighbors: &[Vec<usize>]) -> Vec<usize> {
+    let start = (0..neighbors.len())
+        .min_by_key(|&n| neighbors[n].len())
+        .unwrap();
+    let order = bfs_order(neighbors, start);
+    let mut order = bfs_order(neighbors, *order.last().unwrap());
+    order.reverse();
+    order
+}
+
+/// Returns the natural log of the determinant of the reduced Laplacian of a
+/// connected graph with adjacency lists `neighbors`, factoring within the
+/// envelope of a reverse Cuthill-McKee ordering.
+fn envelope_log_det(neighbors: &[Vec<usize>]) -> f64 {
+    let order = reverse_cuthill_mckee(neighbors);
+    let mut position = vec![0; order.len()];
+    for (pos, &node) in order.iter().enumerate() {
+        position[node] = pos;
+    }
+
+    // The reduced Laplacian drops the last node in the ordering, so its
+    // envelope is the leading `n - 1` rows of the full Laplacian's envelope.
+    let n = order.len() - 1;
+    let first: Vec<usize> = (0..n)
+        .map(|row| {
+            neighbors[order[row]]
+                .iter()
+                .map(|&neighbor| position[neighbor])
+                .fold(row, usize::min)
+        })
+        .collect();
+    let mut offsets = Vec::with_capacity(n + 1);
+    offsets.push(0);
+    for row in 0..n {
+        offsets.push(offsets[row] + row - first[row] + 1);
+    }
+    // Row `i` of the envelope stores columns `first[i]..=i`.
+    let mut env = vec![0.0; offsets[n]];
+    for row in 0..n {
+        let node = order[row];
+        env[offsets[row + 1] - 1] = neighbors[node].len() as f64;
+        for &neighbor in neighbors[node].iter() {
+            let col = position[neighbor];
+            if col < row {
+                env[offsets[row] + col - first[row]] -= 1.0;
+            }
+        }
+    }
+
+    // In-place envelope Cholesky factorization.
+    let mut log_det = 0.0;
+    for i in 0..n {
+        let row_i = offsets[i];
+        for j in first[i]..i {
+            let row_j = offsets[j];
+            let k_start = first[i].max(first[j]);
+            let mut dot = 0.0;
+            for k in k_start..j {
+                dot += env[row_i + k - first[i]] * env[row_j + k - first[j]];
+            }
+            let diag_j = env[offsets[j + 1] - 1];
+            env[row_i + j - first[i]] = (env[row_i + j - first[i]] - dot) / diag_j;
+        }
+        let diag_idx = offsets[i + 1] - 1;
+        let sum_sq: f64 = env[row_i..diag_idx].iter().map(|v| v * v).sum();
+        let diag = (env[diag_idx] - sum_sq).sqrt();
+        env[diag_idx] = diag;
+        log_det += 2.0 * diag.ln();
+    }
+    log_det
+}
+
+/// State for computing a nested dissection ordering.
+struct NestedDissection<'a> {
+    /// The adjacency lists of the graph.
+    neighbors: &'a [Vec<usize>],
+    /// The part each node currently belongs to (`None` for ordered nodes).
+    part: Vec<Option<usize>>,
+    /// The number of parts created so far.
+    n_parts: usize,
+    /// The breadth-first search each node was last visited by.
+    visited: Vec<usize>,
+    /// The number of breadth-first searches run so far.
+    n_searches: usize,
+    /// The ordering (a list of nodes).
+    order: Vec<usize>,
+}
+
+impl<'a> NestedDissection<'a> {
+    /// Returns the breadth-first levels of the nodes in part `part` that
+    /// are reachable from `root`.
+    fn levels(&mut self, part: usize, root: usize) -> Vec<Vec<usize>> {
+        self.n_searches += 1;
+        self.visited[root] = self.n_searches;
+        let mut levels = vec![vec![root]];
+        loop {
+            let mut next = vec![];
+            for &node in levels.last().unwrap().iter() {
+                for &neighbor in self.neighbors[node].iter() {
+                    if self.part[neighbor] == Some(part)
+                        && self.visited[neighbor] != self.n_searches
+                    {
+                        self.visited[neighbor] = self.n_searches;
+                        next.push(neighbor);
+                    }
+                }
+            }
+            if next.is_empty() {
+                return levels;
+            }
+            levels.push(next);
+        }
+    }
+
+    /// Moves `nodes` to a new part and returns the part.
+    fn new_part(&mut self, nodes: &[usize]) -> usize {
+        let part = self.n_parts;
+        self.n_parts += 1;
+        for &node in nodes.iter() {
+            self.part[node] = Some(part);
+        }
+        part
+    }
+
+    /// Appends the nodes of `part` (which are `nodes`) to the ordering.
+    ///
+    /// If the part is known to be connected, `root` is a node on its
+    /// periphery (the last node reached by a breadth-first search).
+    fn dissect(&mut self, part: usize, nodes: Vec<usize>, root: Option<usize>) {
+        if nodes.len() <= MIN_DISSECTION_SIZE {
+            self.finish(&nodes);
+            return;
+        }
+        let root = match root {
+            Some(root) => root,
+            None => {
+                let levels = self.levels(part, nodes[0]);
+                let n_reached: usize = levels.iter().map(|level| level.len()).sum();
+                let last = *levels.last().unwrap().last().unwrap();
+                if n_reached < nodes.len() {
+                    // The part is disconnected, so its components are
+                    // ordered separately.
+                    let component: Vec<usize> = levels.concat();
+                    let component_part = self.new_part(&component);
+                    let rest: Vec<usize> = nodes
+                        .into_iter()
+                        .filter(|&node| self.part[node] == Some(part))
+                        .collect();
+                    self.dissect(component_part, component, Some(last));
+                    self.dissect(part, rest, None);
+                    return;
+                }
+                last
+            }
+        };
+        // The root is far from the other side of the part, so its levels
+        // give thin separators.
+        let levels = self.levels(part, root);
+        if levels.len() < 3 {
+            self.finish(&nodes);
+            return;
+        }
+        // Use the level containing the median node as the separator.
+        let mut n_below = 0;
+        let mut mid = 1;
+        while mid < levels.len() - 2 && n_below + levels[mid - 1].len() < nodes.len() / 2 {
+            n_below += levels[mid - 1].len();
+            mid += 1;
+        }
+        let below: Vec<usize> = levels[..mid].concat();
+        let above: Vec<usize> = levels[mid + 1..].concat();
+        let below_part = self.new_part(&below);
+        let above_part = self.new_part(&above);
+        // The levels below the separator are connected through the root.
+        let below_root = *levels[mid - 1].last().unwrap();
+        self.dissect(below_part, below, Some(below_root));
+        self.dissect(above_part, above, None);
+        self.finish(&levels[mid]);
+    }
+
+    /// Appends `nodes` to the ordering.
+    fn finish(&mut self, nodes: &[usize]) {
+        for &node in nodes.iter() {
+            self.part[node] = None;
+        }
+        self.order.extend_from_slice(nodes);
+    }
+}
+
+/// Returns a nested dissection ordering of a graph.
+fn nested_dissection(neighbors: &[Vec<usize>]) -> Vec<usize> {
+    let n = neighbors.len();
+    let mut dissection = NestedDissection {
+        neighbors,
+        part: vec![Some(0); n],
+        n_parts: 1,
+        visited: vec![0; n],
+        n_searches: 0,
+        order: Vec::with_capacity(n),
+    };
+    dissection.dissect(0, (0..n).collect(), None);
+    dissection.order
+}
+
+/// Returns `true` if the graph with adjacency lists `neighbors` is
+/// connected (and non-empty).
+fn is_connected(neighbors: &[Vec<usize>]) -> bool {
+    if neighbors.is_empty() {
+        return false;
+    }
+    let mut visited = vec![false; neighbors.len()];
+    let mut stack = vec![0];
+    let mut n_visited = 1;
+    visited[0] = true;
+    while let Some(node) = stack.pop() {
+        for &neighbor in neighbors[node].iter() {
+            if !visited[neighbor] {
+                visited[neighbor] = true;
+                n_visited += 1;
+                stack.push(neighbor);
+            }
+        }
+    }
+    n_visited == neighbors.len()
+}
+
+/// Returns the elimination tree of a symmetric matrix, given the column
+/// indices of the nonzeros below the diagonal in each row.
+fn elimination_tree(lower: &[Vec<usize>]) -> Vec<usize> {
+    let n = lower.len();
+    let mut parent = vec![NO_PARENT; n];
+    // Path-compressed ancestors of each node.
+    let mut ancestor = vec![NO_PARENT; n];
+    for (row, row_lower) in lower.iter().enumerate() {
+        for &col in row_lower.iter() {
+            let mut node = col;
+            while node != NO_PARENT && node < row {
+                let next = ancestor[node];
+                ancestor[node] = row;
+                if next == NO_PARENT {
+                    parent[node] = row;
+                }
+                node = next;
+            }
+        }
+    }
+    parent
+}
+
+/// Finds the nonzero pattern (excluding the diagonal) of row `row` of the
+/// Cholesky factor and returns `top`, where `pattern[top..]` holds the
+/// pattern in topological order.
+///
+/// The pattern is the union of the paths from the nonzeros below the
+/// diagonal in the row to `row` in the elimination tree. `marked` must be
+/// `false` for all nodes (and is restored on return).
+fn row_pattern(
+    lower: &[usize],
+    row: usize,
+    parent: &[usize],
+    marked: &mut [bool],
+    path: &mut Vec<usize>,
+    pattern: &mut [usize],
+) -> usize {
+    let mut top = pattern.len();
+    marked[row] = true;
+    for &col in lower.iter() {
+        let mut node = col;
+        while !marked[node] {
+            path.push(node);
+            marked[node] = true;
+            node = parent[node];
+        }
+        // Each path ends at a node found earlier (or at `row`), so it is
+        // placed before the paths found earlier.
+        while let Some(node) = path.pop() {
+            top -= 1;
+            pattern[top] = node;
+        }
+    }
+    marked[row] = false;
+    for &node in pattern[top..].iter() {
+        marked[node] = false;
+    }
+    top
+}
+
+/// Returns the natural log of the determinant of the reduced Laplacian of a
+/// connected graph with adjacency lists `neighbors`, using a nested
+/// dissection ordering and a sparse factorization.
+fn sparse_log_det(neighbors: &[Vec<usize>]) -> f64 {
+    let order = nested_dissection(neighbors);
+    let mut position = vec![0; order.len()];
+    for (pos, &node) in order.iter().enumerate() {
+        position[node] = pos;
+    }
+    // The reduced Laplacian drops the last node in the ordering.
+    let n = order.len() - 1;
+    let lower: Vec<Vec<usize>> = order[..n]
+        .iter()
+        .enumerate()
+        .map(|(row, &node)| {
+            neighbors[node]
+                .iter()
+                .map(|&neighbor| position[neighbor])
+                .filter(|&col| col < row)
+                .collect()
+        })
+        .collect();
+    let parent = elimination_tree(&lower);
+
+    // Symbolic factorization: find the nonzero pattern of each row of the
+    // factor and count the nonzeros in each column to lay out its columns.
+    let mut marked = vec![false; n];
+    let mut path = vec![];
+    let mut pattern = vec![0; n];
+    let mut patterns = vec![];
+    let mut pattern_starts = Vec::with_capacity(n + 1);
+    let mut col_starts = vec![0; n + 1];
+    pattern_starts.push(0);
+    for (row, row_lower) in lower.iter().enumerate() {
+        let top = row_pattern(
+            row_lower,
+            row,
+            &parent,
+            &mut marked,
+            &mut path,
+            &mut pattern,
+        );
+        for &col in pattern[top..].iter() {
+            col_starts[col + 1] += 1;
+        }
+        col_starts[row + 1] += 1;
+        patterns.extend_from_slice(&pattern[top..]);
+        pattern_starts.push(patterns.len());
+    }
+    for col in 0..n {
+        col_starts[col + 1] += col_starts[col];
+    }
+
+    // Numeric factorization, one row at a time. Each column stores its
+    // diagonal entry first, followed by the entries below the diagonal
+    // in increasing row order.
+    let mut rows = vec![0; col_starts[n]];
+    let mut values = vec![0.0; col_starts[n]];
+    let mut col_ends = col_starts[..n].to_vec();
+    let mut work = vec![0.0; n];
+    let mut log_det = 0.0;
+    for row in 0..n {
+        for &col in lower[row].iter() {
+            work[col] = -1.0;
+        }
+        let mut diag = neighbors[order[row]].len() as f64;
+        for &col in patterns[pattern_starts[row]..pattern_starts[row + 1]].iter() {
+            let val = work[col] / values[col_starts[col]];
+            work[col] = 0.0;
+            for idx in col_starts[col] + 1..col_ends[col] {
+                work[rows[idx]] -= values[idx] * val;
+            }
+            diag -= val * val;
+            rows[col_ends[col]] = row;
+            values[col_ends[col]] = val;
+            col_ends[col] += 1;
+        }
+        let diag = diag.sqrt();
+        rows[col_ends[row]] = row;
+        values[col_ends[row]] = diag;
+        col_ends[row] += 1;
+        log_det += 2.0 * diag.ln();
+    }
+    log_det
+}
+
+/// Returns the natural log of the number of spanning trees of the subgraph
+/// induced by `nodes`.
+///
+/// The count is zero (and its log is negative infinity) if the subgraph
+/// is disconnected; a single node has exactly one spanning tree.
+pub fn subgraph_log_spanning_tree_count(graph: &Graph, nodes: &[usize]) -> f64 {
+    let neighbors = subgraph_neighbors(graph, nodes);
+    if !is_connected(&neighbors) {
+        f64::NEG_INFINITY
+    } else if neighbors.len() < MIN_SPARSE_SIZE {
+        envelope_log_det(&neighbors)
+    } else {
+        sparse_log_det(&neighbors)
+    }
+}
+
+/// Tracks the log spanning tree count of each district.
+#[derive(Default)]
+pub struct LogSpanningTreeCounts {
+    /// The natural log of the number of spanning trees of each district.
+    counts: Vec<f64>,
+}
+
+impl LogSpanningTreeCounts {
+    pub fn new() -> LogSpanningTreeCounts {
+        LogSpanningTreeCounts::default()
+    }
+
+    /// Returns the log spanning tree count of each district.
+    pub fn counts(&self) -> &[f64] {
+        &self.counts
+    }
+}
+
+impl Updater for LogSpanningTreeCounts {
+    fn name(&self) -> &str {
+        "log_spanning_tree_counts"
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        self.counts = partition
+            .dist_nodes
+            .iter()
+            .map(|nodes| subgraph_log_spanning_tree_count(graph, nodes))
+            .collect();
+    }
+
+    fn update(&mut self, graph: &Graph, _partition: &Partition, proposal: &RecomProposal) {
+        self.counts[proposal.a_label] = subgraph_log_spanning_tree_count(graph, &proposal.a_nodes);
+        self.counts[proposal.b_label] = subgraph_log_spanning_tree_count(graph, &proposal.b_nodes);
+    }
+
+    fn value(&self) -> Value {
+        json!(self.counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::grid_4x4;
+    use rstest::rstest;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    /// Checks spanning tree counts of square grids against OEIS A007341.
+    #[rstest]
+    #[case(1, 1.0)]
+    #[case(2, 4.0)]
+    #[case(3, 192.0)]
+    #[case(4, 100352.0)]
+    #[case(5, 557568000.0)]
+    #[case(6, 32565539635200.0)]
+    fn log_spanning_tree_count_square_grid(#[case] size: usize, #[case] count: f64) {
+        let graph = Graph::rect_grid(size, size);
+        let nodes: Vec<usize> = (0..size * size).collect();
+        assert_close(subgraph_log_spanning_tree_count(&graph, &nodes), count.ln());
+        let neighbors = subgraph_neighbors(&graph, &nodes);
+        assert_close(sparse_log_det(&neighbors), count.ln());
+    }
+
+    #[test]
+    fn log_spanning_tree_count_subgraphs() {
+        let graph = Graph::rect_grid(4, 4);
+        // A 2x3 block (in shuffled order) has 15 spanning trees.
+        assert_close(
+            subgraph_log_spanning_tree_count(&graph, &[6, 1, 0, 4, 2, 5]),
+            15f64.ln(),
+        );
+        assert_close(
+            sparse_log_det(&subgraph_neighbors(&graph, &[6, 1, 0, 4, 2, 5])),
+            15f64.ln(),
+        );
+        // A path has one spanning tree.
+        assert_close(subgraph_log_spanning_tree_count(&graph, &[0, 1, 2, 3]), 0.0);
+        // Disconnected subgraphs have none.
+        assert_eq!(
+            subgraph_log_spanning_tree_count(&graph, &[0, 2]),
+            f64::NEG_INFINITY
+        );
+    }
+
+    /// Checks that large grids (whose spanning tree counts overflow `f64`)
+    /// agree with the asymptotic growth rate of grid spanning trees.
+    #[rstest]
+    #[case(60, 60)]
+    #[case(100, 90)]
+    fn log_spanning_tree_count_large_grid(#[case] n: usize, #[case] m: usize) {
+        let graph = Graph::rect_grid(n, m);
+        let nodes: Vec<usize> = (0..n * m).collect();
+        let log_count = subgraph_log_spanning_tree_count(&graph, &nodes);
+        assert!(log_count > f64::MAX.ln());
+        // The count grows as exp(4G/π · nm), where G is Catalan's constant.
+        let rate = 4.0 * 0.915_965_594_177_219 / std::f64::consts::PI;
+        let per_node = log_count / (n * m) as f64;
+        assert!((per_node - rate).abs() < 0.05, "{} vs. {}", per_node, rate);
+        // Both factorizations agree.
+        let neighbors = subgraph_neighbors(&graph, &nodes);
+        assert_close(envelope_log_det(&neighbors), log_count);
+        assert_close(sparse_log_det(&neighbors), log_count);
+    }
+
+    #[test]
+    fn log_spanning_tree_counts_updater() {
+        let (graph, mut partition, proposal) = grid_4x4();
+        let mut counts = LogSpanningTreeCounts::new();
+        counts.init(&graph, &partition);
+        assert_eq!(counts.counts(), &[0.0; 4]);
+
+        partition.update(&proposal);
+        counts.update(&graph, &partition, &proposal);
+        assert_close(counts.counts()[0], 4f64.ln());
+        assert_close(counts.counts()[1], 4f64.ln());
+        assert_eq!(counts.counts()[2..], [0.0; 2]);
+    }
+}
@@ -1,22 +1,65 @@
 //! Statistics for Markov chains.
 
+/// Columnar (Parquet and Arrow IPC) output.
+/// This module depends on `arrow` and `parquet`.
+#[cfg(feature = "columnar")]
+mod columnar;
+/// Geometric compactness scores (Polsby-Popper, Schwartzberg, and boundary length).
+mod compactness;
+/// Transparent (zstd and xz) output compression.
+mod compress;
+/// Convergence diagnostics (autocorrelation and effective sample size).
+mod diagnostics;
+/// Partisan fairness metrics for two-party elections.
+mod elections;
+/// Cut edge frequency and node co-assignment heatmaps.
+mod heatmaps;
+/// Holding times of plans (for writers that weight plans by them).
+mod held;
+/// Log spanning tree counts (pure Rust, via sparse Cholesky factorization).
+mod log_spanning_trees;
 /// Markov chain self-loop statistics.
 mod self_loops;
-/// Spanning tree count statistics.
+/// Exact spanning tree count statistics (via dense eigendecomposition).
 /// This module depends on `ndarray` and `ndarray-linalg` (and therefore depends
 /// on a working BLAS installation).
 #[cfg(feature = "linalg")]
 mod spanning_trees;
+/// SQLite output.
+/// This module depends on `rusqlite` (with a bundled SQLite build).
+#[cfg(feature = "sqlite")]
+mod sqlite;
 /// Graph attribute sum statistics.
 mod sums;
+/// Streaming summaries of district statistics (sorted-district distributions).
+mod summary;
+/// Shared fixtures for statistics tests.
+#[cfg(test)]
+pub(crate) mod test_util;
+/// Incrementally updated statistics (tallies, cut edges, and region splits).
+mod updaters;
 /// I/O for statistics.
 mod writers;
 
+#[cfg(feature = "columnar")]
+pub use crate::stats::columnar::{ColumnarFormat, ColumnarWriter};
+pub use crate::stats::compactness::{polsby_popper, schwartzberg, Compactness};
+pub use crate::stats::compress::{CompressedWriter, Compression};
+pub use crate::stats::diagnostics::{Autocorrelation, DiagnosticsWriter};
+pub use crate::stats::elections::{Election, ElectionResults};
+pub use crate::stats::heatmaps::HeatmapWriter;
+pub use crate::stats::log_spanning_trees::{
+    subgraph_log_spanning_tree_count, LogSpanningTreeCounts,
+};
 pub use crate::stats::self_loops::{SelfLoopCounts, SelfLoopReason};
 #[cfg(feature = "linalg")]
 pub use crate::stats::spanning_trees::subgraph_spanning_tree_count;
+#[cfg(feature = "sqlite")]
+pub use crate::stats::sqlite::SqliteWriter;
 pub use crate::stats::sums::{partition_attr_sums, partition_sums, proposal_sums};
+pub use crate::stats::summary::{Histogram, SummaryWriter};
+pub use crate::stats::updaters::{CutEdges, RegionIndex, RegionSplits, Tally, Updater};
 pub use crate::stats::writers::{
     AssignmentsOnlyWriter, BenWriter, CanonicalWriter, JSONLWriter, PcompressWriter, StatsWriter,
-    TSVWriter,
+    TSVWriter, TeeWriter,
 };
@@ -14,9 +14,13 @@ pub enum SelfLoopReason {
     /// Probabilistic rejection based on seam length
     /// (reversible ReCom only).
     SeamLength,
+    /// A self-loop with no recorded reason
+    /// (e.g. when replaying compact chain output).
+    Unknown,
 }
 
 /// Self-loop statistics since the last accepted proposal.
+#[derive(Clone)]
 pub struct SelfLoopCounts {
     counts: HashMap<SelfLoopReason, usize>,
 }
@@ -94,6 +98,7 @@ impl Serialize for SelfLoopCounts {
                 SelfLoopReason::NonAdjacent => "non_adjacent",
                 SelfLoopReason::NoSplit => "no_split",
                 SelfLoopReason::SeamLength => "seam_length",
+                SelfLoopReason::Unknown => "unknown",
             };
             state.serialize_field(key, count)?;
         }
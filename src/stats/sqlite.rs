@@ -0,0 +1,283 @@
+//! SQLite statistics writer.
+use crate::attr::AttrSum;
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::{partition_sums, proposal_sums, SelfLoopCounts, SelfLoopReason, StatsWriter};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::io::{Error, Result};
+
+/// The number of steps written per transaction.
+const BATCH_STEPS: u64 = 10000;
+
+/// The database schema. Tables are shared by all runs in a database.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id INTEGER PRIMARY KEY,
+    meta TEXT NOT NULL,
+    num_dists INTEGER
+);
+CREATE TABLE IF NOT EXISTS steps (
+    run_id INTEGER NOT NULL REFERENCES runs (run_id),
+    step INTEGER NOT NULL,
+    non_adjacent INTEGER NOT NULL,
+    no_split INTEGER NOT NULL,
+    seam_length INTEGER NOT NULL,
+    unknown INTEGER NOT NULL,
+    a_label INTEGER NOT NULL,
+    b_label INTEGER NOT NULL,
+    PRIMARY KEY (run_id, step)
+);
+CREATE TABLE IF NOT EXISTS district_stats (
+    run_id INTEGER NOT NULL REFERENCES runs (run_id),
+    step INTEGER NOT NULL,
+    district INTEGER NOT NULL,
+    stat TEXT NOT NULL,
+    value NUMERIC NOT NULL,
+    PRIMARY KEY (run_id, step, district, stat)
+);
+";
+
+/// Writes chain statistics to a SQLite database. Several runs can be
+/// written to the same database; each run is keyed by a run ID.
+///
+/// The database contains the following tables:
+///   * `runs` - One row per run, with the run's `meta` JSON and
+///     the number of districts (`num_dists`).
+///   * `steps` - One row per accepted step, with the step count (including
+///     self-loops), the self-loop counts since the last accepted step
+///     (`non_adjacent`, `no_split`, `seam_length`, `unknown`), and the
+///     labels of the changed districts (`a_label`, `b_label`).
+///   * `district_stats` - Per-district statistics (`stat` is either
+///     `population` or the name of an attribute column, and `value` is the
+///     population or the attribute sum, stored as an integer or a real
+///     depending on the attribute column). Statistics for all districts in the
+///     initial partition are recorded at step 0; statistics for the changed
+///     districts are recorded at each accepted step.
+pub struct SqliteWriter {
+    /// The open database connection.
+    conn: Connection,
+    /// The ID of this run.
+    run_id: i64,
+    /// The number of steps written since the last commit.
+    pending: u64,
+}
+
+/// Wraps SQLite errors in standard I/O errors.
+fn to_io_error(err: rusqlite::Error) -> Error {
+    Error::other(err)
+}
+
+/// Converts an attribute sum to a SQLite value.
+fn sql_sum(sum: AttrSum) -> SqlValue {
+    match sum {
+        AttrSum::Int(sum) => SqlValue::Integer(sum),
+        AttrSum::Float(sum) => SqlValue::Real(sum),
+    }
+}
+
+impl SqliteWriter {
+    /// Opens (or creates) the database at `path` and registers a new run
+    /// with metadata `meta`.
+    pub fn new(path: &str, meta: &Value) -> Result<SqliteWriter> {
+        let conn = Connection::open(path).map_err(to_io_error)?;
+        conn.execute_batch(SCHEMA).map_err(to_io_error)?;
+        conn.execute(
+            "INSERT INTO runs (meta) VALUES (?1)",
+            params![meta.to_string()],
+        )
+        .map_err(to_io_error)?;
+        let run_id = conn.last_insert_rowid();
+        Ok(SqliteWriter {
+            conn,
+            run_id,
+            pending: 0,
+        })
+    }
+
+    /// Returns the ID of this run.
+    pub fn run_id(&self) -> i64 {
+        self.run_id
+    }
+
+    /// Records a statistic for a district.
+    fn insert_stat(&self, step: u64, district: usize, stat: &str, value: SqlValue) -> Result<()> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO district_stats (run_id, step, district, stat, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .and_then(|mut stmt| {
+                stmt.execute(params![self.run_id, step as i64, district, stat, value])
+            })
+            .map_err(to_io_error)?;
+        Ok(())
+    }
+}
+
+impl StatsWriter for SqliteWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        self.conn.execute_batch("BEGIN").map_err(to_io_error)?;
+        self.conn
+            .execute(
+                "UPDATE runs SET num_dists = ?1 WHERE run_id = ?2",
+                params![partition.num_dists, self.run_id],
+            )
+            .map_err(to_io_error)?;
+        for (dist, &pop) in partition.dist_pops.iter().enumerate() {
+            self.insert_stat(0, dist, "population", SqlValue::Integer(pop as i64))?;
+        }
+        for (col, sums) in partition_sums(graph, partition).iter() {
+            for (dist, &sum) in sums.iter().enumerate() {
+                self.insert_stat(0, dist, col, sql_sum(sum))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        _partition: &Partition,
+        proposal: &RecomProposal,
+        counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        self.conn
+            .prepare_cached(
+                "INSERT INTO steps
+                 (run_id, step, non_adjacent, no_split, seam_length, unknown,
+                  a_label, b_label)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )
+            .and_then(|mut stmt| {
+                stmt.execute(params![
+                    self.run_id,
+                    step as i64,
+                    counts.get(SelfLoopReason::NonAdjacent),
+                    counts.get(SelfLoopReason::NoSplit),
+                    counts.get(SelfLoopReason::SeamLength),
+                    counts.get(SelfLoopReason::Unknown),
+                    proposal.a_label,
+                    proposal.b_label,
+                ])
+            })
+            .map_err(to_io_error)?;
+        let (a_pop, b_pop) = (proposal.a_pop as i64, proposal.b_pop as i64);
+        self.insert_stat(
+            step,
+            proposal.a_label,
+            "population",
+            SqlValue::Integer(a_pop),
+        )?;
+        self.insert_stat(
+            step,
+            proposal.b_label,
+            "population",
+            SqlValue::Integer(b_pop),
+        )?;
+        for (col, (a_sum, b_sum)) in proposal_sums(graph, proposal).iter() {
+            self.insert_stat(step, proposal.a_label, col, sql_sum(*a_sum))?;
+            self.insert_stat(step, proposal.b_label, col, sql_sum(*b_sum))?;
+        }
+
+        self.pending += 1;
+        if self.pending >= BATCH_STEPS {
+            self.conn
+                .execute_batch("COMMIT; BEGIN")
+                .map_err(to_io_error)?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT").map_err(to_io_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::stats::test_util::grid_2x2;
+    use serde_json::json;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    /// Writes one short run (two steps on a 2x2 grid) to the database at `path`.
+    fn write_run(path: &str, seed: u64) -> i64 {
+        let (mut graph, partition, proposal) = grid_2x2();
+        graph
+            .attr
+            .insert("votes".to_string(), AttrColumn::parse(["1", "2", "3", "4"]));
+        let mut counts = SelfLoopCounts::default();
+        counts.inc(SelfLoopReason::NoSplit);
+
+        let mut writer = SqliteWriter::new(path, &json!({ "rng_seed": seed })).unwrap();
+        writer.init(&graph, &partition).unwrap();
+        writer
+            .step(1, &graph, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        writer
+            .step(3, &graph, &partition, &proposal, &counts)
+            .unwrap();
+        writer.close().unwrap();
+        writer.run_id()
+    }
+
+    #[test]
+    fn sqlite_multiple_runs() {
+        let mut path = env::temp_dir();
+        path.push(format!("frcw_sqlite_{}.db", process::id()));
+        let path = path.into_os_string().into_string().unwrap();
+        let first = write_run(&path, 1);
+        let second = write_run(&path, 2);
+        assert_ne!(first, second);
+
+        let conn = Connection::open(&path).unwrap();
+        let meta: String = conn
+            .query_row(
+                "SELECT meta FROM runs WHERE run_id = ?1",
+                params![second],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(meta, r#"{"rng_seed":2}"#);
+        let steps: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM steps WHERE run_id = ?1",
+                params![first],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(steps, 2);
+        let no_split: i64 = conn
+            .query_row(
+                "SELECT no_split FROM steps WHERE run_id = ?1 AND step = 3",
+                params![first],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(no_split, 1);
+        let votes: Vec<i64> = conn
+            .prepare(
+                "SELECT value FROM district_stats
+                 WHERE run_id = ?1 AND step = 1 AND stat = 'votes' ORDER BY district",
+            )
+            .unwrap()
+            .query_map(params![first], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .unwrap();
+        assert_eq!(votes, vec![4, 6]);
+        drop(conn);
+        fs::remove_file(&path).unwrap();
+    }
+}
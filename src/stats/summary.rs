@@ -0,0 +1,392 @@
+//! Streaming summaries of district statistics over a whole chain run.
+//!
+//! Outlier analyses (e.g. comparing an enacted plan to an ensemble) only
+//! need the marginal distributions of statistics such as sorted minority
+//! shares, cut edges, and seats, not a record for every step. The
+//! [SummaryWriter] accumulates these distributions in streaming histograms
+//! and emits a single compact summary when the chain finishes.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::held::HeldSteps;
+use crate::stats::{CutEdges, Election, SelfLoopCounts, StatsWriter, Tally, Updater};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+/// Quantiles reported for each histogram (boxplot statistics with
+/// 5th and 95th percentile whiskers).
+const QUANTILES: [(&str, f64); 7] = [
+    ("min", 0.0),
+    ("p5", 0.05),
+    ("q1", 0.25),
+    ("median", 0.5),
+    ("q3", 0.75),
+    ("p95", 0.95),
+    ("max", 1.0),
+];
+
+/// A streaming histogram with fixed-width bins over an unbounded range.
+///
+/// Values are rounded to the nearest multiple of the bin width, so quantiles
+/// are accurate to within half a bin (and exact for integer statistics with
+/// unit-width bins). Memory usage depends only on the number of distinct
+/// bins observed, not on the number of observations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// The width of each bin.
+    bin_width: f64,
+    /// The total weight of observations in each (nonempty) bin, keyed by
+    /// the bin's center divided by the bin width.
+    bins: BTreeMap<i64, u64>,
+    /// The total weight of all observations.
+    count: u64,
+    /// The weighted sum of all observations (for computing an exact mean).
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bin_width: f64) -> Histogram {
+        assert!(bin_width > 0.0, "histogram bin width must be positive");
+        Histogram {
+            bin_width,
+            bins: BTreeMap::new(),
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    /// Adds an observation with a weight (e.g. the number of steps a plan
+    /// was held for). Non-finite values are ignored.
+    pub fn add(&mut self, value: f64, weight: u64) {
+        if !value.is_finite() || weight == 0 {
+            return;
+        }
+        let bin = (value / self.bin_width).round() as i64;
+        *self.bins.entry(bin).or_insert(0) += weight;
+        self.count += weight;
+        self.sum += value * weight as f64;
+    }
+
+    /// Returns the total weight of all observations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the weighted mean of all observations.
+    pub fn mean(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            count => Some(self.sum / count as f64),
+        }
+    }
+
+    /// Returns the `q`-quantile of the observations (rounded to a bin), or
+    /// `None` if there are no observations.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let target = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (&bin, &weight) in self.bins.iter() {
+            seen += weight;
+            if seen >= target {
+                return Some(bin as f64 * self.bin_width);
+            }
+        }
+        None
+    }
+
+    /// Returns the histogram's quantiles and (nonempty) bins as JSON.
+    pub fn to_json(&self) -> Value {
+        let quantiles: Map<String, Value> = QUANTILES
+            .iter()
+            .map(|&(name, q)| (name.to_string(), json!(self.quantile(q))))
+            .collect();
+        let bins: Vec<(f64, u64)> = self
+            .bins
+            .iter()
+            .map(|(&bin, &weight)| (bin as f64 * self.bin_width, weight))
+            .collect();
+        json!({
+            "count": self.count,
+            "mean": self.mean(),
+            "quantiles": quantiles,
+            "bin_width": self.bin_width,
+            "bins": bins,
+        })
+    }
+}
+
+/// Adds per-district values (sorted in ascending order) to histograms
+/// indexed by rank.
+fn add_sorted(hists: &mut [Histogram], mut values: Vec<f64>, weight: u64) {
+    values.sort_by(|a, b| a.total_cmp(b));
+    for (hist, value) in hists.iter_mut().zip(values) {
+        hist.add(value, weight);
+    }
+}
+
+/// A ratio of two tallies (e.g. minority voting-age population over
+/// voting-age population) in each district.
+struct ShareSummary {
+    /// The name of the share (used as a key in output).
+    name: String,
+    /// The numerator tally.
+    numerator: Tally,
+    /// The denominator tally.
+    denominator: Tally,
+    /// Histograms of the sorted shares (by rank).
+    sorted: Vec<Histogram>,
+}
+
+/// Seats and sorted vote shares for an election.
+struct ElectionSummary {
+    /// The election's vote tallies.
+    election: Election,
+    /// A histogram of the first party's seats.
+    seats: Histogram,
+    /// Histograms of the first party's sorted vote shares (by rank).
+    sorted: Vec<Histogram>,
+}
+
+/// Summarizes the distributions of district statistics over a whole run
+/// (sorted shares, cut edges, and election seats and sorted vote shares).
+///
+/// No output is written until the chain finishes; `close()` then writes a
+/// single JSON line with quantiles and histograms of each statistic
+/// over all steps of the chain (including self-loops).
+pub struct SummaryWriter {
+    /// The number of steps each plan was held for.
+    held: HeldSteps,
+    /// The histogram bin width for shares.
+    bin_width: f64,
+    /// Summarized shares.
+    shares: Vec<ShareSummary>,
+    /// Summarized elections.
+    elections: Vec<ElectionSummary>,
+    /// Cut edge counts.
+    cut_edges: CutEdges,
+    /// A histogram of cut edge counts.
+    cut_edges_hist: Histogram,
+    /// The number of districts.
+    num_dists: usize,
+    /// The output stream that we would like to write to.
+    output: Box<dyn Write + Send>,
+}
+
+impl SummaryWriter {
+    pub fn new(
+        n_steps: Option<u64>,
+        bin_width: f64,
+        output: Box<dyn Write + Send>,
+    ) -> SummaryWriter {
+        SummaryWriter {
+            held: HeldSteps::new(n_steps),
+            bin_width,
+            shares: vec![],
+            elections: vec![],
+            cut_edges: CutEdges::new(),
+            cut_edges_hist: Histogram::new(1.0),
+            num_dists: 0,
+            output,
+        }
+    }
+
+    /// Adds a share (the ratio of two numeric columns) to summarize.
+    pub fn with_share(
+        mut self,
+        name: &str,
+        numerator_col: &str,
+        denominator_col: &str,
+    ) -> SummaryWriter {
+        self.shares.push(ShareSummary {
+            name: name.to_string(),
+            numerator: Tally::new(numerator_col),
+            denominator: Tally::new(denominator_col),
+            sorted: vec![],
+        });
+        self
+    }
+
+    /// Adds an election to summarize.
+    pub fn with_election(mut self, election: Election) -> SummaryWriter {
+        self.elections.push(ElectionSummary {
+            election,
+            seats: Histogram::new(1.0),
+            sorted: vec![],
+        });
+        self
+    }
+
+    /// Records the current plan with a weight.
+    fn observe(&mut self, weight: u64) {
+        self.cut_edges_hist
+            .add(self.cut_edges.count() as f64, weight);
+        for share in self.shares.iter_mut() {
+            let values = share
+                .numerator
+                .tallies()
+                .iter()
+                .zip(share.denominator.tallies().iter())
+                .map(|(num, den)| num.as_f64() / den.as_f64())
+                .collect();
+            add_sorted(&mut share.sorted, values, weight);
+        }
+        for summary in self.elections.iter_mut() {
+            summary
+                .seats
+                .add(summary.election.results().seats as f64, weight);
+            add_sorted(&mut summary.sorted, summary.election.shares(), weight);
+        }
+    }
+
+    /// Returns the summary of all observed plans.
+    fn summary(&self) -> Value {
+        let sorted_json =
+            |hists: &[Histogram]| -> Value { hists.iter().map(|hist| hist.to_json()).collect() };
+        let shares: Map<String, Value> = self
+            .shares
+            .iter()
+            .map(|share| (share.name.clone(), sorted_json(&share.sorted)))
+            .collect();
+        let elections: Map<String, Value> = self
+            .elections
+            .iter()
+            .map(|summary| {
+                (
+                    summary.election.name().to_string(),
+                    json!({
+                        "seats": summary.seats.to_json(),
+                        "shares": sorted_json(&summary.sorted),
+                    }),
+                )
+            })
+            .collect();
+        json!({
+            "steps": self.cut_edges_hist.count(),
+            "num_dists": self.num_dists,
+            "cut_edges": self.cut_edges_hist.to_json(),
+            "shares": shares,
+            "elections": elections,
+        })
+    }
+}
+
+impl StatsWriter for SummaryWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        self.num_dists = partition.num_dists as usize;
+        self.held.reset();
+        self.cut_edges.init(graph, partition);
+        for share in self.shares.iter_mut() {
+            share.numerator.init(graph, partition);
+            share.denominator.init(graph, partition);
+            share.sorted = vec![Histogram::new(self.bin_width); self.num_dists];
+        }
+        for summary in self.elections.iter_mut() {
+            summary.election.init(graph, partition);
+            summary.sorted = vec![Histogram::new(self.bin_width); self.num_dists];
+        }
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        partition: &Partition,
+        proposal: &RecomProposal,
+        _counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        let held = self.held.accept(step);
+        self.observe(held);
+        self.cut_edges.update(graph, partition, proposal);
+        for share in self.shares.iter_mut() {
+            share.numerator.update(graph, partition, proposal);
+            share.denominator.update(graph, partition, proposal);
+        }
+        for summary in self.elections.iter_mut() {
+            summary.election.update(graph, partition, proposal);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.observe(self.held.final_held());
+        self.output
+            .write_all(format!("{}\n", json!({ "summary": self.summary() })).as_bytes())?;
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::stats::test_util::{grid_4x4, SharedBuffer};
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut hist = Histogram::new(0.1);
+        hist.add(0.32, 1);
+        hist.add(0.48, 2);
+        hist.add(0.91, 1);
+        hist.add(f64::NAN, 5);
+        assert_eq!(hist.count(), 4);
+        assert_eq!(hist.quantile(0.0), Some(0.30000000000000004));
+        assert_eq!(hist.quantile(0.5), Some(0.5));
+        assert_eq!(hist.quantile(1.0), Some(0.9));
+        assert!((hist.mean().unwrap() - 0.5475).abs() < 1e-12);
+        assert_eq!(Histogram::new(1.0).quantile(0.5), None);
+    }
+
+    /// Summarizes a 4-step chain on a 4x4 grid that holds four vertical
+    /// strips for steps 0-2 and then turns two strips into squares.
+    #[test]
+    fn summary_weights_plans_by_steps_held() {
+        let (mut graph, mut partition, proposal) = grid_4x4();
+        graph.attr.insert(
+            "minority".to_string(),
+            AttrColumn::parse((0..16).map(|n| if n < 4 { "1" } else { "0" })),
+        );
+        graph.attr.insert(
+            "other".to_string(),
+            AttrColumn::parse((0..16).map(|n| if n < 4 { "0" } else { "1" })),
+        );
+        graph
+            .attr
+            .insert("total".to_string(), AttrColumn::parse((0..16).map(|_| "1")));
+        let buf = SharedBuffer::default();
+        let mut writer = SummaryWriter::new(Some(4), 0.1, Box::new(buf.clone()))
+            .with_share("minority", "minority", "total")
+            .with_election(Election::new("E", "minority", "other"));
+        writer.init(&graph, &partition).unwrap();
+
+        partition.update(&proposal);
+        writer
+            .step(3, &graph, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        assert!(buf.bytes().is_empty());
+        writer.close().unwrap();
+
+        let output = buf.text();
+        assert_eq!(output.lines().count(), 1);
+        let summary: Value = serde_json::from_str(&output).unwrap();
+        let summary = &summary["summary"];
+        assert_eq!(summary["steps"], 5);
+        assert_eq!(summary["num_dists"], 4);
+        assert_eq!(summary["cut_edges"]["bins"], json!([[10.0, 2], [12.0, 3]]));
+        assert_eq!(summary["cut_edges"]["quantiles"]["median"], 12.0);
+        assert_eq!(summary["cut_edges"]["mean"], 11.2);
+
+        let top_share = &summary["shares"]["minority"][3];
+        assert_eq!(top_share["bins"], json!([[0.5, 2], [1.0, 3]]));
+        assert_eq!(top_share["quantiles"]["min"], 0.5);
+        assert_eq!(summary["shares"]["minority"][0]["quantiles"]["max"], 0.0);
+
+        let seats = &summary["elections"]["E"]["seats"];
+        assert_eq!(seats["bins"], json!([[0.0, 2], [1.0, 3]]));
+        assert_eq!(
+            summary["elections"]["E"]["shares"][3]["quantiles"]["max"],
+            1.0
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Shared fixtures for statistics tests.
+use crate::graph::Graph;
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use std::io::{Result, Write};
+use std::sync::{Arc, Mutex};
+
+/// A cloneable in-memory output stream that can be inspected after a
+/// writer takes ownership of it.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Returns the bytes written so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Returns the bytes written so far as a UTF-8 string.
+    pub fn text(&self) -> String {
+        String::from_utf8(self.bytes()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A 2x2 grid with two districts (nodes 0 and 1, and nodes 2 and 3) and a
+/// proposal that reassigns the grid to two districts (nodes 0 and 2, and
+/// nodes 1 and 3).
+pub fn grid_2x2() -> (Graph, Partition, RecomProposal) {
+    let graph = Graph::rect_grid(2, 2);
+    let partition = Partition::from_assignments(&graph, &vec![1, 1, 2, 2]).unwrap();
+    let proposal = RecomProposal {
+        a_label: 0,
+        b_label: 1,
+        a_pop: 2,
+        b_pop: 2,
+        a_nodes: vec![0, 2],
+        b_nodes: vec![1, 3],
+    };
+    (graph, partition, proposal)
+}
+
+/// A 4x4 grid with four vertical districts and a proposal that merges and
+/// splits the two leftmost districts into two squares.
+pub fn grid_4x4() -> (Graph, Partition, RecomProposal) {
+    let graph = Graph::rect_grid(4, 4);
+    let assignments: Vec<u32> = (0..16).map(|n| (n / 4) + 1).collect();
+    let partition = Partition::from_assignments(&graph, &assignments).unwrap();
+    let proposal = RecomProposal {
+        a_label: 0,
+        b_label: 1,
+        a_pop: 4,
+        b_pop: 4,
+        a_nodes: vec![0, 1, 4, 5],
+        b_nodes: vec![2, 3, 6, 7],
+    };
+    (graph, partition, proposal)
+}
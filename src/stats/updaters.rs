@@ -0,0 +1,435 @@
+//! Incrementally updated district statistics.
+use crate::attr::AttrSum;
+use crate::graph::{Graph, Pop};
+use crate::partition::Partition;
+use crate::recom::RecomProposal;
+use crate::stats::partition_attr_sums;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// A GerryChain-style updater: a statistic that is computed once for the
+/// initial partition and then updated from the two districts changed by
+/// each accepted proposal (rather than recomputed from scratch).
+pub trait Updater: Send {
+    /// The name of the statistic (used as a key in output).
+    fn name(&self) -> &str;
+
+    /// Computes the statistic for the initial partition.
+    fn init(&mut self, graph: &Graph, partition: &Partition);
+
+    /// Updates the statistic for a proposal which has been applied
+    /// to `partition`.
+    fn update(&mut self, graph: &Graph, partition: &Partition, proposal: &RecomProposal);
+
+    /// Returns the current value of the statistic.
+    fn value(&self) -> Value;
+}
+
+/// Per-district sums over a numeric attribute column.
+pub struct Tally {
+    /// The attribute column to sum over.
+    col: String,
+    /// The sum in each district.
+    tallies: Vec<AttrSum>,
+}
+
+impl Tally {
+    pub fn new(col: &str) -> Tally {
+        Tally {
+            col: col.to_string(),
+            tallies: vec![],
+        }
+    }
+
+    /// Returns the sum in each district.
+    pub fn tallies(&self) -> &[AttrSum] {
+        &self.tallies
+    }
+}
+
+impl Updater for Tally {
+    fn name(&self) -> &str {
+        &self.col
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        self.tallies = partition_attr_sums(graph, partition, &self.col);
+    }
+
+    fn update(&mut self, graph: &Graph, _partition: &Partition, proposal: &RecomProposal) {
+        let values = &graph.attr[&self.col];
+        self.tallies[proposal.a_label] = values.sum(&proposal.a_nodes).unwrap();
+        self.tallies[proposal.b_label] = values.sum(&proposal.b_nodes).unwrap();
+    }
+
+    fn value(&self) -> Value {
+        json!(self.tallies)
+    }
+}
+
+/// The number of cut edges (edges between nodes in different districts),
+/// along with the number of cut edges between each pair of districts.
+#[derive(Default)]
+pub struct CutEdges {
+    /// The number of districts.
+    num_dists: usize,
+    /// A flattened matrix of cut edge counts between district pairs.
+    between: Vec<usize>,
+    /// The total number of cut edges.
+    count: usize,
+}
+
+impl CutEdges {
+    pub fn new() -> CutEdges {
+        CutEdges::default()
+    }
+
+    /// Returns the total number of cut edges.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of cut edges between districts `a` and `b`.
+    pub fn between(&self, a: usize, b: usize) -> usize {
+        self.between[a * self.num_dists + b]
+    }
+
+    /// Resets the cut edge counts involving district `dist`.
+    fn clear_dist(&mut self, dist: usize) {
+        for other in 0..self.num_dists {
+            let count = self.between[dist * self.num_dists + other];
+            self.count -= count;
+            self.between[dist * self.num_dists + other] = 0;
+            self.between[other * self.num_dists + dist] = 0;
+        }
+    }
+
+    /// Counts a cut edge between districts `a` and `b`.
+    fn add_edge(&mut self, a: usize, b: usize) {
+        self.between[a * self.num_dists + b] += 1;
+        self.between[b * self.num_dists + a] += 1;
+        self.count += 1;
+    }
+}
+
+impl Updater for CutEdges {
+    fn name(&self) -> &str {
+        "num_cut_edges"
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        self.num_dists = partition.num_dists as usize;
+        self.between = vec![0; self.num_dists * self.num_dists];
+        self.count = 0;
+        for edge in graph.edges.iter() {
+            let dist_a = partition.assignments[edge.0] as usize;
+            let dist_b = partition.assignments[edge.1] as usize;
+            if dist_a != dist_b {
+                self.add_edge(dist_a, dist_b);
+            }
+        }
+    }
+
+    fn update(&mut self, graph: &Graph, partition: &Partition, proposal: &RecomProposal) {
+        // Only edges incident to the changed districts can change, so we
+        // recount those edges.
+        let (a, b) = (proposal.a_label, proposal.b_label);
+        self.clear_dist(a);
+        self.clear_dist(b);
+        for &node in proposal.a_nodes.iter().chain(proposal.b_nodes.iter()) {
+            let dist = partition.assignments[node] as usize;
+            for &neighbor in graph.neighbors[node].iter() {
+                let neighbor_dist = partition.assignments[neighbor] as usize;
+                let changed = neighbor_dist == a || neighbor_dist == b;
+                // Edges within the changed districts are seen from both ends.
+                if neighbor_dist != dist && (!changed || node < neighbor) {
+                    self.add_edge(dist, neighbor_dist);
+                }
+            }
+        }
+    }
+
+    fn value(&self) -> Value {
+        json!(self.count)
+    }
+}
+
+/// The regions of a region (categorical) column, such as a county column.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegionIndex {
+    /// The region index of each node (`None` for nodes with missing values).
+    pub node_regions: Vec<Option<usize>>,
+    /// The number of regions.
+    pub num_regions: usize,
+}
+
+impl RegionIndex {
+    /// Indexes the distinct values of the region column `col` of `graph`
+    /// (in order of first appearance).
+    pub fn new(graph: &Graph, col: &str) -> RegionIndex {
+        let values = &graph.attr[col];
+        let mut regions = HashMap::<String, usize>::new();
+        let node_regions = (0..values.len())
+            .map(|node| match values.is_missing(node) {
+                true => None,
+                false => {
+                    let next = regions.len();
+                    Some(*regions.entry(values.value_string(node)).or_insert(next))
+                }
+            })
+            .collect();
+        RegionIndex {
+            node_regions,
+            num_regions: regions.len(),
+        }
+    }
+}
+
+/// Region (e.g. county) split statistics: the number of regions split
+/// across more than one district, the total number of region pieces
+/// (region-district intersections), and the split entropy.
+/// Nodes with missing region values are not counted.
+///
+/// The split entropy is the population-weighted average over regions of
+/// the (base 2) entropy of each region's population across districts, so
+/// it is 0 when no region is split.
+pub struct RegionSplits {
+    /// The name of the statistic.
+    name: String,
+    /// The region column.
+    col: String,
+    /// The number of districts.
+    num_dists: usize,
+    /// The region index of each node.
+    node_regions: Vec<Option<usize>>,
+    /// A flattened matrix of node counts by region and district.
+    counts: Vec<u32>,
+    /// A flattened matrix of populations by region and district.
+    pops: Vec<Pop>,
+    /// The population of each region.
+    region_pops: Vec<Pop>,
+    /// The number of districts intersecting each region.
+    region_dists: Vec<u32>,
+    /// Each region's contribution to the (unnormalized) split entropy.
+    region_entropy: Vec<f64>,
+    /// The total population of nodes with regions.
+    total_pop: f64,
+    /// The number of split regions.
+    splits: usize,
+    /// Marks regions touched by the current update (reused across steps).
+    touched: Vec<bool>,
+}
+
+impl RegionSplits {
+    pub fn new(col: &str) -> RegionSplits {
+        RegionSplits {
+            name: format!("{}_splits", col),
+            col: col.to_string(),
+            num_dists: 0,
+            node_regions: vec![],
+            counts: vec![],
+            pops: vec![],
+            region_pops: vec![],
+            region_dists: vec![],
+            region_entropy: vec![],
+            total_pop: 0.0,
+            splits: 0,
+            touched: vec![],
+        }
+    }
+
+    /// Returns the number of split regions.
+    pub fn splits(&self) -> usize {
+        self.splits
+    }
+
+    /// Returns the total number of region pieces (the number of
+    /// districts intersecting each region, summed over regions).
+    pub fn pieces(&self) -> usize {
+        self.region_dists.iter().map(|&dists| dists as usize).sum()
+    }
+
+    /// Returns the split entropy.
+    pub fn entropy(&self) -> f64 {
+        match self.total_pop > 0.0 {
+            true => self.region_entropy.iter().sum::<f64>() / self.total_pop,
+            false => 0.0,
+        }
+    }
+
+    /// Adds a node in `region` with population `pop` to district `dist`.
+    fn add_node(&mut self, region: usize, dist: usize, pop: Pop) {
+        let idx = region * self.num_dists + dist;
+        if self.counts[idx] == 0 {
+            self.region_dists[region] += 1;
+        }
+        self.counts[idx] += 1;
+        self.pops[idx] += pop;
+    }
+
+    /// Recomputes the contribution of `region` to the split entropy.
+    fn update_entropy(&mut self, region: usize) {
+        let region_pop = self.region_pops[region] as f64;
+        let dist_pops = &self.pops[region * self.num_dists..(region + 1) * self.num_dists];
+        self.region_entropy[region] = dist_pops
+            .iter()
+            .filter(|&&pop| pop > 0)
+            .map(|&pop| pop as f64 * (region_pop / pop as f64).log2())
+            .sum();
+    }
+
+    /// Returns `true` if `region` is split.
+    fn is_split(&self, region: usize) -> bool {
+        self.region_dists[region] > 1
+    }
+}
+
+impl Updater for RegionSplits {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn init(&mut self, graph: &Graph, partition: &Partition) {
+        let index = RegionIndex::new(graph, &self.col);
+        let num_regions = index.num_regions;
+        self.node_regions = index.node_regions;
+        self.num_dists = partition.num_dists as usize;
+        self.counts = vec![0; num_regions * self.num_dists];
+        self.pops = vec![0; num_regions * self.num_dists];
+        self.region_pops = vec![0; num_regions];
+        self.region_dists = vec![0; num_regions];
+        self.region_entropy = vec![0.0; num_regions];
+        self.touched = vec![false; num_regions];
+        for (node, region) in self.node_regions.clone().into_iter().enumerate() {
+            if let Some(region) = region {
+                let dist = partition.assignments[node] as usize;
+                self.add_node(region, dist, graph.pops[node]);
+                self.region_pops[region] += graph.pops[node];
+            }
+        }
+        self.total_pop = self.region_pops.iter().map(|&pop| pop as f64).sum();
+        for region in 0..num_regions {
+            self.update_entropy(region);
+        }
+        self.splits = (0..num_regions).filter(|&r| self.is_split(r)).count();
+    }
+
+    fn update(&mut self, graph: &Graph, partition: &Partition, proposal: &RecomProposal) {
+        let (a, b) = (proposal.a_label, proposal.b_label);
+        // Clear the changed districts' node counts in the regions they touch.
+        for &node in proposal.a_nodes.iter().chain(proposal.b_nodes.iter()) {
+            if let Some(region) = self.node_regions[node] {
+                if !self.touched[region] {
+                    self.touched[region] = true;
+                    self.splits -= self.is_split(region) as usize;
+                    let a_idx = region * self.num_dists + a;
+                    let b_idx = region * self.num_dists + b;
+                    self.region_dists[region] -=
+                        (self.counts[a_idx] > 0) as u32 + (self.counts[b_idx] > 0) as u32;
+                    self.counts[a_idx] = 0;
+                    self.counts[b_idx] = 0;
+                    self.pops[a_idx] = 0;
+                    self.pops[b_idx] = 0;
+                }
+            }
+        }
+        for &node in proposal.a_nodes.iter().chain(proposal.b_nodes.iter()) {
+            if let Some(region) = self.node_regions[node] {
+                let dist = partition.assignments[node] as usize;
+                self.add_node(region, dist, graph.pops[node]);
+            }
+        }
+        for &node in proposal.a_nodes.iter().chain(proposal.b_nodes.iter()) {
+            if let Some(region) = self.node_regions[node] {
+                if self.touched[region] {
+                    self.touched[region] = false;
+                    self.splits += self.is_split(region) as usize;
+                    self.update_entropy(region);
+                }
+            }
+        }
+    }
+
+    fn value(&self) -> Value {
+        json!({
+            "splits": self.splits,
+            "pieces": self.pieces(),
+            "entropy": self.entropy(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attr::AttrColumn;
+    use crate::stats::test_util::grid_4x4;
+
+    /// A 4x4 grid with four vertical districts, vote and county columns,
+    /// and a proposal that merges and splits the two leftmost districts.
+    fn fixture() -> (Graph, Partition, RecomProposal) {
+        let (mut graph, partition, proposal) = grid_4x4();
+        graph.attr.insert(
+            "votes".to_string(),
+            AttrColumn::parse((0..16).map(|v| v.to_string())),
+        );
+        // Counties are 2x2 blocks.
+        graph.attr.insert(
+            "county".to_string(),
+            AttrColumn::parse((0..16).map(|n| format!("c{}{}", n / 8, (n % 4) / 2))),
+        );
+        (graph, partition, proposal)
+    }
+
+    /// Checks that incrementally updated statistics match statistics
+    /// computed from scratch after a proposal.
+    #[test]
+    fn updates_match_recomputation() {
+        let (graph, mut partition, proposal) = fixture();
+        let mut updaters: Vec<Box<dyn Updater>> = vec![
+            Box::new(Tally::new("votes")),
+            Box::new(CutEdges::new()),
+            Box::new(RegionSplits::new("county")),
+        ];
+        for updater in updaters.iter_mut() {
+            updater.init(&graph, &partition);
+        }
+        assert_eq!(updaters[1].value(), json!(12));
+        assert_eq!(
+            updaters[2].value(),
+            json!({"splits": 4, "pieces": 8, "entropy": 1.0})
+        );
+
+        partition.update(&proposal);
+        for updater in updaters.iter_mut() {
+            updater.update(&graph, &partition, &proposal);
+        }
+        for updater in updaters.iter_mut() {
+            let value = updater.value();
+            updater.init(&graph, &partition);
+            assert_eq!(value, updater.value(), "{}", updater.name());
+        }
+        assert_eq!(updaters[0].value(), json!([10, 18, 38, 54]));
+        assert_eq!(updaters[1].value(), json!(10));
+        assert_eq!(
+            updaters[2].value(),
+            json!({"splits": 2, "pieces": 6, "entropy": 0.5})
+        );
+    }
+
+    #[test]
+    fn cut_edges_between_districts() {
+        let (graph, mut partition, proposal) = fixture();
+        let mut cut_edges = CutEdges::new();
+        cut_edges.init(&graph, &partition);
+        assert_eq!(cut_edges.between(0, 1), 4);
+        assert_eq!(cut_edges.between(0, 2), 0);
+        partition.update(&proposal);
+        cut_edges.update(&graph, &partition, &proposal);
+        assert_eq!(cut_edges.between(0, 1), 2);
+        assert_eq!(cut_edges.between(1, 2), 2);
+        assert_eq!(cut_edges.between(0, 2), 2);
+        assert_eq!(cut_edges.between(2, 3), 4);
+        assert_eq!(cut_edges.count(), 10);
+    }
+}
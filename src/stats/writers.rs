@@ -1,16 +1,16 @@
 use crate::graph::Graph;
 use crate::partition::Partition;
 use crate::recom::RecomProposal;
-#[cfg(feature = "linalg")]
-use crate::stats::subgraph_spanning_tree_count;
-use crate::stats::{partition_sums, proposal_sums, SelfLoopCounts, SelfLoopReason};
+use crate::stats::{
+    partition_sums, proposal_sums, CutEdges, LogSpanningTreeCounts, SelfLoopCounts, SelfLoopReason,
+    Updater,
+};
 use pcompress::diff::Diff;
 use pcompress::encode::export_diff;
-use serde_json::{json, to_value, Value};
+use serde_json::json;
 use std::io::{BufWriter, Result, Write};
 
 /// A standard interface for writing steps and statistics to stdout.
-/// TODO: allow direct output to a file (e.g. in Parquet format).
 /// TODO: move outside of this module.
 pub trait StatsWriter: Send {
     /// Prints data from the initial partition.
@@ -41,6 +41,8 @@ pub trait StatsWriter: Send {
 ///   * `no_split` - The number of self-loops due to the lack of an ε-balanced split.
 ///   * `seam_length` - The number of self-loops due to seam length rejection
 ///     (Reversible ReCom only).
+///   * `unknown` - The number of self-loops with no recorded reason
+///     (replayed chains only).
 ///   * `a_label` - The label of the `a`-district in the proposal.
 ///   * `b_label` - The label of the `b`-district in the proposal.
 ///   * `a_pop` - The population of the new `a`-district.
@@ -95,14 +97,20 @@ pub struct PcompressWriter {
 pub struct JSONLWriter {
     /// Determines whether node deltas should be saved for each step.
     nodes: bool,
-    /// Determines whether to compute spanning tree counts for each step.
-    spanning_tree_counts: bool,
-    /// Determines whether to compute cut edge counts for each step.
-    cut_edges_count: bool,
+    /// Incrementally updated statistics (e.g. cut edge counts) to save
+    /// for each step, keyed by name.
+    updaters: Vec<Box<dyn Updater>>,
     // The output stream that we would like to write to.
     output: Box<dyn Write + Send>,
 }
 
+/// Fans out chain steps to several writers (for instance, to write
+/// assignments and summary statistics to separate files in one run).
+pub struct TeeWriter {
+    /// The writers to fan out to, in order.
+    writers: Vec<Box<dyn StatsWriter>>,
+}
+
 impl TSVWriter {
     pub fn new(output: Box<dyn Write + Send>) -> TSVWriter {
         TSVWriter { output: output }
@@ -164,52 +172,33 @@ impl JSONLWriter {
         cut_edges_count: bool,
         output: Box<dyn Write + Send>,
     ) -> JSONLWriter {
+        let mut updaters: Vec<Box<dyn Updater>> = vec![];
+        if spanning_tree_counts {
+            updaters.push(Box::new(LogSpanningTreeCounts::new()));
+        }
+        if cut_edges_count {
+            updaters.push(Box::new(CutEdges::new()));
+        }
         JSONLWriter {
             nodes: nodes,
-            spanning_tree_counts: spanning_tree_counts,
-            cut_edges_count: cut_edges_count,
+            updaters,
             output: output,
         }
     }
 
-    #[cfg(feature = "linalg")]
-    /// Adds initial spanning tree count statistics to `stats`.
-    fn init_spanning_tree_counts(graph: &Graph, partition: &Partition, stats: &mut Value) {
-        stats.as_object_mut().unwrap().insert(
-            "spanning_tree_counts".to_string(),
-            partition
-                .dist_nodes
-                .iter()
-                .map(|nodes| subgraph_spanning_tree_count(graph, nodes))
-                .collect(),
-        );
-    }
-
-    #[cfg(not(feature = "linalg"))]
-    /// Dummy function---spanning tree counts depend on linear algebra libraries.
-    fn init_spanning_tree_counts(_graph: &Graph, _partition: &Partition, _stats: &mut Value) {}
-
-    #[cfg(feature = "linalg")]
-    /// Adds step spanning tree count statistics to `stats`.
-    fn step_spanning_tree_counts(graph: &Graph, proposal: &RecomProposal, stats: &mut Value) {
-        stats.as_object_mut().unwrap().insert(
-            "spanning_tree_counts".to_string(),
-            json!((
-                subgraph_spanning_tree_count(graph, &proposal.a_nodes),
-                subgraph_spanning_tree_count(graph, &proposal.b_nodes)
-            )),
-        );
+    /// Adds an incrementally updated statistic to the output
+    /// (under the updater's name).
+    pub fn with_updater(mut self, updater: Box<dyn Updater>) -> JSONLWriter {
+        self.updaters.push(updater);
+        self
     }
-
-    #[cfg(not(feature = "linalg"))]
-    /// Dummy function---spanning tree counts depend on linear algebra libraries.
-    fn step_spanning_tree_counts(_graph: &Graph, _proposal: &RecomProposal, _stats: &mut Value) {}
 }
 
 impl StatsWriter for TSVWriter {
     fn init(&mut self, _graph: &Graph, _partition: &Partition) -> Result<()> {
         // TSV column header.
-        print!("step\tnon_adjacent\tno_split\tseam_length\ta_label\tb_label\t");
+        print!("step\tnon_adjacent\tno_split\tseam_length\tunknown\t");
+        print!("a_label\tb_label\t");
         println!("a_pop\tb_pop\ta_nodes\tb_nodes");
         Ok(())
     }
@@ -225,11 +214,12 @@ impl StatsWriter for TSVWriter {
         self.output
             .write_all(
                 format!(
-                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:?}\t{:?}\n",
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:?}\t{:?}\n",
                     step,
                     counts.get(SelfLoopReason::NonAdjacent),
                     counts.get(SelfLoopReason::NoSplit),
                     counts.get(SelfLoopReason::SeamLength),
+                    counts.get(SelfLoopReason::Unknown),
                     proposal.a_label,
                     proposal.b_label,
                     proposal.a_pop,
@@ -256,16 +246,12 @@ impl StatsWriter for JSONLWriter {
             "populations": partition.dist_pops,
             "sums": partition_sums(graph, partition)
         });
-        if self.spanning_tree_counts {
-            JSONLWriter::init_spanning_tree_counts(graph, partition, &mut stats);
-        }
-        if self.cut_edges_count {
-            let mut partition = partition.clone();
-            let cut_edges_count = partition.cut_edges(graph).len();
-            stats.as_object_mut().unwrap().insert(
-                "num_cut_edges".to_string(),
-                to_value(cut_edges_count).unwrap(),
-            );
+        for updater in self.updaters.iter_mut() {
+            updater.init(graph, partition);
+            stats
+                .as_object_mut()
+                .unwrap()
+                .insert(updater.name().to_string(), updater.value());
         }
         self.output
             .write_all(format!("{}\n", json!({ "init": stats }).to_string()).as_bytes())
@@ -294,16 +280,11 @@ impl StatsWriter for JSONLWriter {
                 json!((proposal.a_nodes.clone(), proposal.b_nodes.clone())),
             );
         }
-        if self.spanning_tree_counts {
-            JSONLWriter::step_spanning_tree_counts(graph, proposal, &mut step);
-        }
-        if self.cut_edges_count {
-            let mut partition = partition.clone();
-            let cut_edges_count = partition.cut_edges(graph).len();
-            step.as_object_mut().unwrap().insert(
-                "num_cut_edges".to_string(),
-                to_value(cut_edges_count).unwrap(),
-            );
+        for updater in self.updaters.iter_mut() {
+            updater.update(graph, partition, proposal);
+            step.as_object_mut()
+                .unwrap()
+                .insert(updater.name().to_string(), updater.value());
         }
         self.output
             .write_all(format!("{}\n", json!({ "step": step }).to_string()).as_bytes())
@@ -543,3 +524,76 @@ impl StatsWriter for PcompressWriter {
         self.writer.flush()
     }
 }
+
+impl TeeWriter {
+    pub fn new(writers: Vec<Box<dyn StatsWriter>>) -> TeeWriter {
+        TeeWriter { writers }
+    }
+}
+
+impl StatsWriter for TeeWriter {
+    fn init(&mut self, graph: &Graph, partition: &Partition) -> Result<()> {
+        for writer in self.writers.iter_mut() {
+            writer.init(graph, partition)?;
+        }
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        step: u64,
+        graph: &Graph,
+        partition: &Partition,
+        proposal: &RecomProposal,
+        counts: &SelfLoopCounts,
+    ) -> Result<()> {
+        for writer in self.writers.iter_mut() {
+            writer.step(step, graph, partition, proposal, counts)?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        // Close every writer (so that each output is finalized),
+        // even if an earlier writer fails to close.
+        let mut result = Ok(());
+        for writer in self.writers.iter_mut() {
+            let closed = writer.close();
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::test_util::{grid_2x2, SharedBuffer};
+
+    #[test]
+    fn tee_writes_to_all_outputs() {
+        let (graph, mut partition, proposal) = grid_2x2();
+        let assignments_buf = SharedBuffer::default();
+        let canonical_buf = SharedBuffer::default();
+        let mut writer = TeeWriter::new(vec![
+            Box::new(AssignmentsOnlyWriter::new(
+                false,
+                Box::new(assignments_buf.clone()),
+            )),
+            Box::new(CanonicalWriter::new(Box::new(canonical_buf.clone()))),
+        ]);
+        writer.init(&graph, &partition).unwrap();
+        partition.update(&proposal);
+        writer
+            .step(1, &graph, &partition, &proposal, &SelfLoopCounts::default())
+            .unwrap();
+        writer.close().unwrap();
+
+        let assignments = assignments_buf.text();
+        let canonical = canonical_buf.text();
+        assert_eq!(assignments.lines().count(), 2);
+        assert_eq!(canonical.lines().count(), 2);
+    }
+}
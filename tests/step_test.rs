@@ -1,5 +1,5 @@
 // Functional tests that verify ReCom chain invariants at each step.
-use frcw::graph::Graph;
+use frcw::graph::{Graph, Pop};
 use frcw::partition::Partition;
 use frcw::recom::run::multi_chain;
 use frcw::recom::{RecomParams, RecomProposal, RecomVariant};
@@ -46,7 +46,7 @@ fn proposal_connected_invariant(graph: &Graph, proposal: &RecomProposal) -> bool
 }
 
 /// Verifies all districts in a partition are within population bounds.
-fn population_tolerance_invariant(partition: &Partition, min_pop: u32, max_pop: u32) -> bool {
+fn population_tolerance_invariant(partition: &Partition, min_pop: Pop, max_pop: Pop) -> bool {
     return partition
         .dist_pops
         .iter()
@@ -59,7 +59,7 @@ fn population_sum_invariant(graph: &Graph, partition: &Partition) -> bool {
         .dist_pops
         .iter()
         .zip(partition.dist_nodes.iter())
-        .all(|(&pop, nodes)| pop == nodes.iter().map(|&n| graph.pops[n]).sum::<u32>());
+        .all(|(&pop, nodes)| pop == nodes.iter().map(|&n| graph.pops[n]).sum::<Pop>());
 }
 
 /// Verifies that a partition's `cut_edges` match its `assignments`.
@@ -119,7 +119,7 @@ fn step_count_invariant(step: u64, last_step: u64, counts: &SelfLoopCounts) -> b
 /// total population) are consistent with its graph.
 fn graph_partition_invariant(graph: &Graph, partition: &Partition) -> bool {
     return graph.neighbors.len() == partition.assignments.len()
-        && graph.total_pop == partition.dist_pops.iter().sum::<u32>();
+        && graph.total_pop == partition.dist_pops.iter().sum::<Pop>();
 }
 
 /// The state of a chain, generated from step deltas.
@@ -283,7 +283,7 @@ const RNG_SEED: u64 = 153434375;
 #[rstest]
 fn test_chain_invariants_recom_grid(
     #[values(2500)] num_steps: u64,
-    #[values((6, 6), (5, 7), (4, 8))] pop_range: (u32, u32),
+    #[values((6, 6), (5, 7), (4, 8))] pop_range: (Pop, Pop),
     #[values(RecomVariant::DistrictPairsRMST, RecomVariant::CutEdgesRMST)] variant: RecomVariant,
     #[values(1, 4)] n_threads: usize,
     #[values(1, 4)] batch_size: usize,
@@ -305,17 +305,19 @@ fn test_chain_invariants_recom_grid(
 #[rstest]
 fn test_chain_invariants_revrecom_grid(
     #[values(25000)] num_steps: u64,
-    #[values((6, 6), (5, 7), (4, 8))] pop_range: (u32, u32),
+    #[values((6, 6), (5, 7), (4, 8))] pop_range: (Pop, Pop),
     #[values(1, 4)] n_threads: usize,
     #[values(1, 4)] batch_size: usize,
 ) {
     let (graph, partition) = fixture_with_attributes("6x6", vec!["a_share", "b_share"]);
+    #[allow(clippy::useless_conversion)] // `Pop` is `u32` without the `pop64` feature.
+    let balance_ub = u32::try_from(pop_range.1 - pop_range.0 + 1).unwrap();
     let params = RecomParams {
         min_pop: pop_range.0,
         max_pop: pop_range.1,
         num_steps: num_steps,
         rng_seed: RNG_SEED,
-        balance_ub: pop_range.1 - pop_range.0 + 1,
+        balance_ub,
         variant: RecomVariant::Reversible,
         region_weights: None,
     };
@@ -333,8 +335,8 @@ fn test_chain_invariants_recom_iowa(
     let (graph, partition) = default_fixture("IA");
     let avg_pop = (graph.total_pop as f64) / (partition.num_dists as f64);
     let params = RecomParams {
-        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as Pop,
         num_steps: 1000,
         rng_seed: RNG_SEED,
         balance_ub: 0,
@@ -357,8 +359,8 @@ fn test_chain_invariants_revrecom_iowa(
     let pop_tol = pop_tol_balance_ub.0;
     let balance_ub = pop_tol_balance_ub.1;
     let params = RecomParams {
-        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as Pop,
         num_steps: 20000,
         rng_seed: RNG_SEED,
         balance_ub: balance_ub,
@@ -381,8 +383,8 @@ fn test_chain_invariants_revrecom_large_states(
     let avg_pop = (graph.total_pop as f64) / (partition.num_dists as f64);
     let pop_tol = 0.05;
     let params = RecomParams {
-        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as u32,
-        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as u32,
+        min_pop: ((1.0 - pop_tol) * avg_pop as f64).floor() as Pop,
+        max_pop: ((1.0 + pop_tol) * avg_pop as f64).ceil() as Pop,
         num_steps: num_steps,
         rng_seed: RNG_SEED,
         balance_ub: 30,